@@ -1,10 +1,14 @@
 use std::{
     collections::{HashMap, HashSet},
     path::PathBuf,
-    sync::{LazyLock, Mutex, RwLock},
+    str::FromStr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex, RwLock,
+    },
 };
 
-use log::info;
+use log::{error, info, warn};
 use semver::Version;
 use skyline::nn;
 use skyline_config::*;
@@ -47,6 +51,7 @@ fn generate_default_config<CS: ConfigStorage>(storage: &mut StorageHolder<CS>) -
 
     // Just so we don't keep outdated fields
     storage.clear_storage();
+    invalidate_active_overrides();
 
     storage.set_field("version", get_arcropolis_version().to_string())?;
     storage.set_field("logging_level", "Warn")?;
@@ -60,12 +65,51 @@ fn generate_default_config<CS: ConfigStorage>(storage: &mut StorageHolder<CS>) -
     storage.set_field("workspace", "Default")
 }
 
+/// Overrides of the active workspace. Some settings are read for every log line, so they are read from the SD once and kept until the
+/// active workspace or the overrides change.
+static ACTIVE_OVERRIDES: LazyLock<Mutex<Option<HashMap<String, String>>>> = LazyLock::new(|| Mutex::new(None));
+
+/// Gets the value the active workspace sets for a setting, if any, from [`ACTIVE_OVERRIDES`]
+fn active_override(storage: &StorageHolder<ArcStorage>, key: &str) -> Option<String> {
+    ACTIVE_OVERRIDES
+        .lock()
+        .unwrap()
+        .get_or_insert_with(|| overrides::read_active_all(storage))
+        .get(key)
+        .cloned()
+}
+
+/// Has the overrides of the active workspace read again on the next use, after switching workspaces or changing the overrides
+fn invalidate_active_overrides() {
+    *ACTIVE_OVERRIDES.lock().unwrap() = None;
+}
+
+/// Reads a field as set for the active workspace, which falls through to the global configuration unless the workspace overrides it
+fn read_layered_field<CS: ConfigStorage>(storage: &StorageHolder<CS>, key: &str) -> Option<String> {
+    overrides::read_active_all(storage).remove(key).or_else(|| storage.get_field(key).ok())
+}
+
+fn layered_field<T: FromStr>(key: &str) -> Option<T> {
+    let storage = GLOBAL_CONFIG.lock().unwrap();
+
+    active_override(&storage, key).or_else(|| storage.get_field(key).ok()).and_then(|value| value.parse().ok())
+}
+
+/// Same as [`layered_field`] for flags, which the overrides hold as `true` or `false`
+fn layered_flag(key: &str) -> bool {
+    let storage = GLOBAL_CONFIG.lock().unwrap();
+
+    active_override(&storage, key)
+        .and_then(|value| value.parse().ok())
+        .unwrap_or_else(|| storage.get_flag(key))
+}
+
 pub fn auto_update_enabled() -> bool {
     GLOBAL_CONFIG.lock().unwrap().get_flag("auto_update")
 }
 
 pub fn debug_enabled() -> bool {
-    GLOBAL_CONFIG.lock().unwrap().get_flag("debug")
+    layered_flag("debug")
 }
 
 pub fn beta_updates() -> bool {
@@ -73,11 +117,11 @@ pub fn beta_updates() -> bool {
 }
 
 pub fn skip_cutscene() -> bool {
-    GLOBAL_CONFIG.lock().unwrap().get_flag("skip_cutscene")
+    layered_flag("skip_cutscene")
 }
 
 pub fn skip_title_scene() -> bool {
-    GLOBAL_CONFIG.lock().unwrap().get_flag("skip_title_scene")
+    layered_flag("skip_title_scene")
 }
 
 pub static REGION: RwLock<Region> = RwLock::new(Region::UsEnglish);
@@ -86,17 +130,197 @@ pub fn region() -> Region {
     *REGION.read().unwrap()
 }
 
+/// Token that can take the place of a regional suffix (`+us_en`) so that a single file is used as the regional variant of every region.
+/// It is matched whatever its case, like regional suffixes.
+/// I.E.: ui/message/msg_menu+region+.msbt
+pub const REGION_WILDCARD: &str = "+region+";
+
+/// Removes the regional suffix (`+us_en`) or the region wildcard from a path, whatever their case. A regional suffix is the `+` and a
+/// five character region, a name cut shorter than that loses everything after the `+`.
+pub fn strip_regional_suffix(path: &str) -> String {
+    // ASCII lowercasing keeps the offsets the same as in the original path
+    let mut path = match path.to_ascii_lowercase().find(REGION_WILDCARD) {
+        Some(index) => format!("{}{}", &path[..index], &path[index + REGION_WILDCARD.len()..]),
+        None => path.to_string(),
+    };
+
+    if let Some(regional_idx) = path.find('+') {
+        let end = path[regional_idx..].char_indices().nth(6).map_or(path.len(), |(offset, _)| regional_idx + offset);
+        path.replace_range(regional_idx..end, "")
+    }
+
+    path
+}
+
+/// Whether a file name has a regional suffix for another region than `region`, whatever their case. A file using the region wildcard
+/// is used for every region.
+pub fn is_out_of_region(name: &str, region: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+
+    name.find('+')
+        .map_or(false, |index| !name[index + 1..].starts_with(&region.to_ascii_lowercase()) && !name.contains(REGION_WILDCARD))
+}
+
+/// Folder holding the per-title contents of Atmosphère, where the web applet reads the menu pages from. Only needs to be set for
+/// unusual setups.
+pub fn web_contents_base() -> Option<String> {
+    GLOBAL_CONFIG.lock().unwrap().get_field("web_contents_base").ok().filter(|base: &String| !base.is_empty())
+}
+
+/// Development flag to load the modded files from a server on the network instead of the SD. None unless both the `dev_server` flag
+/// and the `dev_server_url` field are set.
+pub fn dev_server_url() -> Option<String> {
+    if !layered_flag("dev_server") {
+        return None;
+    }
+
+    layered_field("dev_server_url").filter(|url: &String| !url.is_empty())
+}
+
+/// Mods directory to use instead of the regular one, for the current boot only
+pub static MODS_PATH_OVERRIDE: RwLock<Option<String>> = RwLock::new(None);
+
+pub fn mods_path_override() -> Option<String> {
+    MODS_PATH_OVERRIDE.read().unwrap().clone()
+}
+
+/// Directories searched for mods in addition to the regular mods directory, such as a folder shared with another setup
+pub fn extra_mods_paths() -> Vec<String> {
+    GLOBAL_CONFIG.lock().unwrap().get_field_json("extra_mods_paths").unwrap_or_default()
+}
+
+/// Serve every file from the game's data instead of the mods, for the current boot only. Not saved to the configuration, as
+/// it is meant to quickly compare modded and vanilla files.
+static BYPASS_REPLACEMENTS: AtomicBool = AtomicBool::new(false);
+
+pub fn replacements_bypassed() -> bool {
+    BYPASS_REPLACEMENTS.load(Ordering::Relaxed)
+}
+
+/// Cleared while the SD card can't be read, so that the game keeps loading its own files until the card is back
+static SD_AVAILABLE: AtomicBool = AtomicBool::new(true);
+
+pub fn sd_available() -> bool {
+    SD_AVAILABLE.load(Ordering::Relaxed)
+}
+
+pub fn set_sd_available(state: bool) {
+    SD_AVAILABLE.store(state, Ordering::Relaxed);
+}
+
+pub fn set_replacements_bypassed(state: bool) {
+    BYPASS_REPLACEMENTS.store(state, Ordering::Relaxed);
+    info!("Replacements are now {}.", if state { "bypassed" } else { "enabled" });
+}
+
 pub fn logger_level() -> String {
-    let level: String = GLOBAL_CONFIG
-        .lock()
-        .unwrap()
-        .get_field("logging_level")
-        .unwrap_or_else(|_| String::from("Warn"));
-    level
+    layered_field("logging_level").unwrap_or_else(|| String::from("Warn"))
+}
+
+/// How long repeated log messages about the same file are collapsed for, in milliseconds. 0 disables the deduplication.
+pub fn log_dedup_window_ms() -> u64 {
+    layered_field("log_dedup_window").unwrap_or(1000)
 }
 
 pub fn file_logging_enabled() -> bool {
-    GLOBAL_CONFIG.lock().unwrap().get_flag("log_to_file")
+    layered_flag("log_to_file")
+}
+
+/// Development flag which checks every mapped file at boot, not exposed in the configuration editor
+pub fn integrity_check_enabled() -> bool {
+    layered_flag("integrity_check")
+}
+
+/// Development flag to close the game once the integrity check is done instead of continuing to boot
+pub fn integrity_check_exit() -> bool {
+    layered_flag("integrity_check_exit")
+}
+
+/// Development flag which lists the files loaded by the game that no mod replaces, not exposed in the configuration editor
+pub fn log_vanilla_files_enabled() -> bool {
+    layered_flag("log_vanilla_files")
+}
+
+/// Tell the user how many files and mods were loaded once the game boots, along with the amount of errors
+pub fn boot_summary_enabled() -> bool {
+    layered_flag("boot_summary")
+}
+
+/// Watch for the rescan file so mods uploaded while the game is running get loaded without a reboot
+pub fn rescan_watcher_enabled() -> bool {
+    GLOBAL_CONFIG.lock().unwrap().get_flag("rescan_watcher")
+}
+
+/// Top-level folders (such as `stage`) that are skipped during discovery and only scanned the first time the game requests a file from them
+pub fn lazy_namespaces() -> Vec<String> {
+    GLOBAL_CONFIG.lock().unwrap().get_field_json("lazy_namespaces").unwrap_or_default()
+}
+
+/// Largest size in bytes the game can handle for some kinds of files, keyed by their extension. Replacements past it are skipped.
+pub fn size_limits() -> HashMap<String, usize> {
+    GLOBAL_CONFIG.lock().unwrap().get_field_json("size_limits").unwrap_or_default()
+}
+
+/// How a file provided by several mods is resolved during discovery
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConflictStrategy {
+    /// The mod whose folder name comes first alphabetically wins
+    FirstWins,
+    /// The mod whose folder name comes last alphabetically wins
+    LastWins,
+    /// The mod with the highest priority wins, then the one whose folder name comes first
+    Priority,
+    /// No mod is loaded as long as some of them conflict
+    Error,
+}
+
+impl ConflictStrategy {
+    /// Sorts mods in the order they are mounted, where the first one wins the files they share. The SD doesn't list directories in any
+    /// particular order, so mods are sorted by folder name first for the winner of a conflict not to change between boots.
+    pub fn sort_mods<T>(self, mods: &mut [T], folder_name: impl Fn(&T) -> String, priority: impl Fn(&T) -> i32) {
+        mods.sort_by_cached_key(|entry| folder_name(entry).to_lowercase());
+
+        match self {
+            // Stable, so mods with the same priority stay ordered by name
+            ConflictStrategy::Priority | ConflictStrategy::Error => mods.sort_by_key(|entry| std::cmp::Reverse(priority(entry))),
+            ConflictStrategy::FirstWins => {},
+            ConflictStrategy::LastWins => mods.reverse(),
+        }
+    }
+}
+
+/// Whether a mod comes from the game's own files rather than the SD, which happens when an additional mods directory points into `rom:/`,
+/// such as files extracted to the `arc` folder of a romfs
+pub fn is_rom_mod(path: &str) -> bool {
+    path.starts_with("rom:")
+}
+
+/// Mounts the mods from the game's files and the mods on the SD in two groups, whatever order the conflict strategy sorted them in.
+/// The mods on the SD come first and win the files both provide, unless `rom_first` is set for users that rely on extracted files as
+/// overrides.
+pub fn sort_mods_by_source<T>(mods: &mut [T], path: impl Fn(&T) -> String, rom_first: bool) {
+    // Stable, so each group keeps the order of the conflict strategy
+    mods.sort_by_cached_key(|entry| is_rom_mod(&path(entry)) != rom_first);
+}
+
+/// Let the mods found in the game's files win over the mods on the SD, see [`sort_mods_by_source`]
+pub fn rom_overrides_sd() -> bool {
+    layered_flag("rom_overrides_sd")
+}
+
+pub fn conflict_strategy() -> ConflictStrategy {
+    let strategy: String = GLOBAL_CONFIG.lock().unwrap().get_field("conflict_strategy").unwrap_or_else(|_| String::from("Priority"));
+
+    match strategy.as_str() {
+        "FirstWins" => ConflictStrategy::FirstWins,
+        "LastWins" => ConflictStrategy::LastWins,
+        "Priority" => ConflictStrategy::Priority,
+        "Error" => ConflictStrategy::Error,
+        _ => {
+            error!("Unknown conflict strategy '{}', resolving conflicts by priority instead.", strategy);
+            ConflictStrategy::Priority
+        },
+    }
 }
 
 pub fn legacy_discovery() -> bool {
@@ -128,6 +352,8 @@ pub mod workspaces {
         ConfigError(#[from] ConfigError),
         #[error("a workspace with this name already exists")]
         AlreadyExists,
+        #[error("workspace {0} ends up inheriting from itself")]
+        InheritanceCycle(String),
         #[error("failed to find workspace with name: {0}")]
         MissingWorkspace(String), // #[error("failed to call from_str for the desired type")]
                                   // FromStrErr,
@@ -156,16 +382,24 @@ pub mod workspaces {
         }
     }
 
+    /// Reads the active workspace name. Every user profile has a storage of its own (see [`ArcStorage`]), so each one keeps its own
+    /// active workspace. Takes the storage directly so callers that already hold the config lock (like the menus) can use it.
+    pub fn read_active_workspace_name<CS: ConfigStorage>(storage: &StorageHolder<CS>) -> Result<String, WorkspaceError> {
+        storage.get_field("workspace").map_err(WorkspaceError::ConfigError)
+    }
+
+    pub fn write_active_workspace_name<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, name: String) -> Result<(), WorkspaceError> {
+        storage.set_field("workspace", name).map_err(WorkspaceError::ConfigError)?;
+        invalidate_active_overrides();
+        Ok(())
+    }
+
     pub fn set_active_workspace(name: String) -> Result<(), WorkspaceError> {
         let workspace_list = get_list()?;
         // Make sure the workspace actually exists before setting it
         if workspace_list.contains_key(&name) {
             // If we couldn't write the new active workspace, return an error
-            GLOBAL_CONFIG
-                .lock()
-                .unwrap()
-                .set_field("workspace", name)
-                .map_err(WorkspaceError::ConfigError)
+            write_active_workspace_name(&mut *GLOBAL_CONFIG.lock().unwrap(), name)
         } else {
             // Couldn't find the workspace in our list, something is wrong
             Err(WorkspaceError::MissingWorkspace(name))
@@ -173,12 +407,12 @@ pub mod workspaces {
     }
 
     pub fn get_active_workspace_name() -> Result<String, WorkspaceError> {
-        GLOBAL_CONFIG.lock().unwrap().get_field("workspace").map_err(WorkspaceError::ConfigError)
+        read_active_workspace_name(&*GLOBAL_CONFIG.lock().unwrap())
     }
 
     pub fn get_active_workspace() -> Result<String, WorkspaceError> {
         let workspace_list = get_list()?;
-        let workspace_name: String = GLOBAL_CONFIG.lock().unwrap().get_field("workspace")?;
+        let workspace_name = get_active_workspace_name()?;
         workspace_list
             .get(&workspace_name)
             .map(|x| x.to_owned())
@@ -202,11 +436,200 @@ pub mod workspaces {
         // Reinsert the preset name with the new workspace name
         workspace_list.insert(to.to_string(), preset_name);
         // Overwrite the list with the changes
-        GLOBAL_CONFIG
-            .lock()
-            .unwrap()
-            .set_field_json("workspace_list", &workspace_list)
-            .map_err(WorkspaceError::ConfigError)
+        let mut storage = GLOBAL_CONFIG.lock().unwrap();
+        storage.set_field_json("workspace_list", &workspace_list).map_err(WorkspaceError::ConfigError)?;
+        super::overrides::relink(&mut *storage, from, Some(to))?;
+        super::priorities::relink(&mut *storage, from, Some(to))?;
+        relink_parents(&mut *storage, from, Some(to))
+    }
+
+    /// Workspaces that inherit the mods of another one, keyed by the name of the inheriting workspace
+    pub fn read_parents<CS: ConfigStorage>(storage: &StorageHolder<CS>) -> HashMap<String, String> {
+        storage.get_field_json("workspace_parents").unwrap_or_default()
+    }
+
+    pub fn get_parent(name: &str) -> Option<String> {
+        read_parents(&*GLOBAL_CONFIG.lock().unwrap()).remove(name)
+    }
+
+    /// Lists the workspaces whose presets make up the one of `name`, starting with the one that doesn't inherit from any other and
+    /// ending with `name` itself
+    pub fn inheritance_chain(parents: &HashMap<String, String>, name: &str) -> Result<Vec<String>, WorkspaceError> {
+        let mut chain = vec![name.to_string()];
+
+        while let Some(parent) = parents.get(chain.last().unwrap()) {
+            if chain.contains(parent) {
+                return Err(WorkspaceError::InheritanceCycle(name.to_string()));
+            }
+
+            chain.push(parent.clone());
+        }
+
+        chain.reverse();
+        Ok(chain)
+    }
+
+    /// Makes `name` inherit the mods of `parent`, or stop inheriting if there is none. Refuses to create a cycle.
+    pub fn write_parent<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, name: &str, parent: Option<&str>) -> Result<(), WorkspaceError> {
+        let mut parents = read_parents(storage);
+
+        match parent {
+            Some(parent) => {
+                parents.insert(name.to_string(), parent.to_string());
+                inheritance_chain(&parents, name)?;
+            },
+            None => {
+                parents.remove(name);
+            },
+        }
+
+        storage.set_field_json("workspace_parents", &parents).map_err(WorkspaceError::ConfigError)
+    }
+
+    pub fn set_parent(name: &str, parent: Option<&str>) -> Result<(), WorkspaceError> {
+        let workspace_list = get_list()?;
+
+        for workspace in std::iter::once(name).chain(parent) {
+            if !workspace_list.contains_key(workspace) {
+                return Err(WorkspaceError::MissingWorkspace(workspace.to_string()));
+            }
+        }
+
+        write_parent(&mut *GLOBAL_CONFIG.lock().unwrap(), name, parent)
+    }
+
+    /// Keeps the inheritance in line when a workspace is renamed to `to`, or removed if there is none. The workspaces that inherited
+    /// from a removed one inherit from its own parent instead.
+    pub fn relink_parents<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, from: &str, to: Option<&str>) -> Result<(), WorkspaceError> {
+        let mut parents = read_parents(storage);
+        let own_parent = parents.remove(from);
+
+        let replacement = match to {
+            Some(to) => {
+                if let Some(own_parent) = own_parent {
+                    parents.insert(to.to_string(), own_parent);
+                }
+                Some(to.to_string())
+            },
+            None => own_parent,
+        };
+
+        parents = parents
+            .into_iter()
+            .filter_map(|(child, parent)| {
+                if parent != from {
+                    Some((child, parent))
+                } else {
+                    replacement.clone().map(|replacement| (child, replacement))
+                }
+            })
+            .collect();
+
+        storage.set_field_json("workspace_parents", &parents).map_err(WorkspaceError::ConfigError)
+    }
+}
+
+/// Settings that a workspace sets for itself, such as a more verbose logging level for a workspace used to debug mods. They are layered
+/// over the global configuration while the workspace is active, and the settings a workspace doesn't set are read from the global
+/// configuration instead. Only the settings that affect how the game runs can be overridden, not the ones about workspaces and presets.
+pub mod overrides {
+    use super::*;
+
+    use skyline_config::ConfigError;
+
+    /// Settings a workspace can override, which are the ones read through [`layered_field`] and [`layered_flag`]
+    pub const KEYS: &[&str] = &[
+        "logging_level",
+        "log_dedup_window",
+        "log_to_file",
+        "log_vanilla_files",
+        "debug",
+        "dev_server",
+        "dev_server_url",
+        "skip_cutscene",
+        "skip_title_scene",
+        "integrity_check",
+        "integrity_check_exit",
+        "boot_summary",
+        "rom_overrides_sd",
+    ];
+
+    /// Overrides of every workspace, keyed by workspace name and then by the key of the setting
+    pub fn read_all<CS: ConfigStorage>(storage: &StorageHolder<CS>) -> HashMap<String, HashMap<String, String>> {
+        storage.get_field_json("workspace_overrides").unwrap_or_default()
+    }
+
+    /// Gets the settings the active workspace overrides
+    pub fn read_active_all<CS: ConfigStorage>(storage: &StorageHolder<CS>) -> HashMap<String, String> {
+        workspaces::read_active_workspace_name(storage)
+            .ok()
+            .and_then(|name| read_all(storage).remove(&name))
+            .unwrap_or_default()
+    }
+
+    fn save<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, overrides: &HashMap<String, HashMap<String, String>>) -> Result<(), ConfigError> {
+        storage.set_field_json("workspace_overrides", overrides)?;
+        invalidate_active_overrides();
+        Ok(())
+    }
+
+    pub fn get(workspace_name: &str) -> HashMap<String, String> {
+        read_all(&*GLOBAL_CONFIG.lock().unwrap()).remove(workspace_name).unwrap_or_default()
+    }
+
+    /// Sets the value a workspace uses for a setting, or has it use the global one again if there is none
+    pub fn set(workspace_name: &str, key: &str, value: Option<&str>) -> Result<(), ConfigError> {
+        write(&mut *GLOBAL_CONFIG.lock().unwrap(), workspace_name, key, value)
+    }
+
+    pub fn write<CS: ConfigStorage>(
+        storage: &mut StorageHolder<CS>,
+        workspace_name: &str,
+        key: &str,
+        value: Option<&str>,
+    ) -> Result<(), ConfigError> {
+        let mut overrides = read_all(storage);
+        let workspace = overrides.entry(workspace_name.to_string()).or_default();
+
+        match value {
+            Some(value) => {
+                workspace.insert(key.to_string(), value.to_string());
+            },
+            None => {
+                workspace.remove(key);
+            },
+        }
+
+        overrides.retain(|_, workspace| !workspace.is_empty());
+        save(storage, &overrides)
+    }
+
+    /// Keeps the overrides of a workspace when it is renamed to `to`, or drops them if it is removed
+    pub fn relink<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, from: &str, to: Option<&str>) -> Result<(), ConfigError> {
+        let mut overrides = read_all(storage);
+
+        match (overrides.remove(from), to) {
+            (Some(workspace), Some(to)) => {
+                overrides.insert(to.to_string(), workspace);
+            },
+            (None, _) => return Ok(()),
+            _ => {},
+        }
+
+        save(storage, &overrides)
+    }
+
+    /// Gives `target` the same overrides as `source`
+    pub fn copy<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, source: &str, target: &str) -> Result<(), ConfigError> {
+        let mut overrides = read_all(storage);
+
+        match overrides.get(source).cloned() {
+            Some(workspace) => {
+                overrides.insert(target.to_string(), workspace);
+                save(storage, &overrides)
+            },
+            None => Ok(()),
+        }
     }
 }
 
@@ -232,40 +655,504 @@ pub mod presets {
         // FromStrErr,
     }
 
+    /// Mods that a workspace disables among the ones it inherits, keyed by workspace name
+    fn read_exclusions<CS: ConfigStorage>(storage: &StorageHolder<CS>) -> HashMap<String, HashSet<Hash40>> {
+        storage.get_field_json("preset_exclusions").unwrap_or_default()
+    }
+
+    fn preset_name<CS: ConfigStorage>(storage: &StorageHolder<CS>, workspace_name: &str) -> Result<String, PresetError> {
+        let workspace_list: HashMap<String, String> = storage.get_field_json("workspace_list")?;
+        workspace_list
+            .get(workspace_name)
+            .cloned()
+            .ok_or_else(|| WorkspaceError::MissingWorkspace(workspace_name.to_string()).into())
+    }
+
+    /// Reads the mods enabled in a workspace, merged with the ones of the workspaces it inherits from. The mods a workspace enables or
+    /// disables take precedence over the ones of its parent. Takes the storage directly so callers that already hold the config lock can
+    /// use it.
+    pub fn read_preset<CS: ConfigStorage>(storage: &StorageHolder<CS>, workspace_name: &str) -> Result<HashSet<Hash40>, PresetError> {
+        // Cycles are refused when setting a parent, so one can only come from a configuration edited by hand
+        let chain = workspaces::inheritance_chain(&workspaces::read_parents(storage), workspace_name).unwrap_or_else(|e| {
+            error!("Ignoring the inheritance of workspace {}. Reason: {}", workspace_name, e);
+            vec![workspace_name.to_string()]
+        });
+
+        let exclusions = read_exclusions(storage);
+        let mut preset = HashSet::new();
+
+        for name in chain {
+            if let Some(excluded) = exclusions.get(&name) {
+                preset.retain(|hash| !excluded.contains(hash));
+            }
+
+            let own: HashSet<Hash40> = storage.get_field_json(preset_name(storage, &name)?)?;
+            preset.extend(own);
+        }
+
+        Ok(preset)
+    }
+
+    /// Saves the mods enabled in a workspace. For a workspace that inherits from another, only the differences with its parent are
+    /// stored, so that later changes to the parent carry over.
+    pub fn write_preset<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, workspace_name: &str, preset: &HashSet<Hash40>) -> Result<(), PresetError> {
+        let preset_name = preset_name(storage, workspace_name)?;
+        let mut exclusions = read_exclusions(storage);
+
+        let own: HashSet<Hash40> = match workspaces::read_parents(storage).get(workspace_name) {
+            Some(parent) => {
+                let inherited = read_preset(storage, parent)?;
+                exclusions.insert(workspace_name.to_string(), inherited.difference(preset).copied().collect());
+                preset.difference(&inherited).copied().collect()
+            },
+            None => {
+                exclusions.remove(workspace_name);
+                preset.clone()
+            },
+        };
+
+        exclusions.retain(|_, excluded| !excluded.is_empty());
+
+        storage.set_field_json(preset_name, &own)?;
+        storage.set_field_json("preset_exclusions", &exclusions).map_err(PresetError::ConfigError)
+    }
+
     pub fn get_active_preset() -> Result<HashSet<Hash40>, PresetError> {
-        let preset_name = workspaces::get_active_workspace()?;
-        GLOBAL_CONFIG
-            .lock()
-            .unwrap()
-            .get_field_json(preset_name)
-            .map_err(PresetError::ConfigError)
+        let workspace_name = workspaces::get_active_workspace_name()?;
+        read_preset(&*GLOBAL_CONFIG.lock().unwrap(), &workspace_name)
+    }
+
+    /// Reads the mods enabled in a workspace, starting over from an empty preset if it is missing or can't be read, so that the user can
+    /// rebuild their selection instead of being locked out of the mod manager
+    pub fn read_preset_or_reset<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, workspace_name: &str) -> HashSet<Hash40> {
+        match read_preset(storage, workspace_name) {
+            Ok(preset) => preset,
+            Err(e) => {
+                warn!("Failed to read the preset for workspace {}, starting from an empty one. Reason: {}", workspace_name, e);
+
+                let preset = HashSet::new();
+
+                if let Err(e) = write_preset(storage, workspace_name, &preset) {
+                    error!("Failed to create an empty preset for workspace {}. Reason: {}", workspace_name, e);
+                }
+
+                preset
+            },
+        }
     }
 
     pub fn get_preset(workspace_name: &str) -> Result<HashSet<Hash40>, PresetError> {
-        let preset_name = workspaces::get_workspace_by_name(workspace_name)?;
-        GLOBAL_CONFIG
-            .lock()
-            .unwrap()
-            .get_field_json(preset_name)
-            .map_err(PresetError::ConfigError)
+        read_preset(&*GLOBAL_CONFIG.lock().unwrap(), workspace_name)
+    }
+
+    pub fn get_preset_or_reset(workspace_name: &str) -> HashSet<Hash40> {
+        read_preset_or_reset(&mut *GLOBAL_CONFIG.lock().unwrap(), workspace_name)
     }
 
     pub fn replace_preset(workspace_name: &str, preset: &HashSet<Hash40>) -> Result<(), PresetError> {
-        let preset_name = workspaces::get_workspace_by_name(workspace_name)?;
-        GLOBAL_CONFIG
-            .lock()
-            .unwrap()
-            .set_field_json(preset_name, preset)
-            .map_err(PresetError::ConfigError)
+        write_preset(&mut *GLOBAL_CONFIG.lock().unwrap(), workspace_name, preset)
     }
 
     pub fn replace_active_preset(preset: &HashSet<Hash40>) -> Result<(), PresetError> {
-        let preset_name = workspaces::get_active_workspace()?;
+        let workspace_name = workspaces::get_active_workspace_name()?;
+        write_preset(&mut *GLOBAL_CONFIG.lock().unwrap(), &workspace_name, preset)
+    }
+
+    /// Mods enabled in a workspace whose folder isn't installed anymore, which do nothing but linger in the preset. `installed` holds
+    /// the hashes of the installed mod folders.
+    pub fn read_stale_entries<CS: ConfigStorage>(
+        storage: &StorageHolder<CS>,
+        workspace_name: &str,
+        installed: &HashSet<Hash40>,
+    ) -> Result<HashSet<Hash40>, PresetError> {
+        Ok(read_preset(storage, workspace_name)?.difference(installed).copied().collect())
+    }
+
+    pub fn find_stale_entries(workspace_name: &str, installed: &HashSet<Hash40>) -> Result<HashSet<Hash40>, PresetError> {
+        read_stale_entries(&*GLOBAL_CONFIG.lock().unwrap(), workspace_name, installed)
+    }
+
+    /// Removes the stale entries of a workspace, along with the ones of the workspaces it inherits from since it would keep getting them
+    /// from there otherwise. Returns how many entries were removed from the workspace.
+    pub fn remove_stale_entries<CS: ConfigStorage>(
+        storage: &mut StorageHolder<CS>,
+        workspace_name: &str,
+        installed: &HashSet<Hash40>,
+    ) -> Result<usize, PresetError> {
+        let stale = read_stale_entries(storage, workspace_name, installed)?;
+
+        if stale.is_empty() {
+            return Ok(0);
+        }
+
+        let chain = workspaces::inheritance_chain(&workspaces::read_parents(&*storage), workspace_name)?;
+        let mut exclusions = read_exclusions(&*storage);
+
+        for name in chain {
+            let preset_name = preset_name(&*storage, &name)?;
+            let mut own: HashSet<Hash40> = storage.get_field_json(&preset_name)?;
+            own.retain(|hash| !stale.contains(hash));
+            storage.set_field_json(preset_name, &own)?;
+
+            if let Some(excluded) = exclusions.get_mut(&name) {
+                excluded.retain(|hash| !stale.contains(hash));
+            }
+        }
+
+        exclusions.retain(|_, excluded| !excluded.is_empty());
+        storage.set_field_json("preset_exclusions", &exclusions)?;
+
+        info!("Removed {} stale entries from the preset of workspace {}.", stale.len(), workspace_name);
+
+        Ok(stale.len())
+    }
+
+    pub fn clean_up_preset(workspace_name: &str, installed: &HashSet<Hash40>) -> Result<usize, PresetError> {
+        remove_stale_entries(&mut *GLOBAL_CONFIG.lock().unwrap(), workspace_name, installed)
+    }
+}
+
+pub mod priorities {
+    use super::*;
+
+    use skyline_config::ConfigError;
+
+    /// Identifies a mod by the full path of its folder, like the presets, so mods with the same folder name in different directories
+    /// don't share their priority
+    pub fn mod_key(mod_path: &str) -> u64 {
+        Hash40::from(mod_path).0
+    }
+
+    /// Priorities set from the mod manager for every workspace, keyed by workspace name and then by [`mod_key`]. These take precedence
+    /// over the `priority` field of a mod's info.toml
+    pub fn read_all<CS: ConfigStorage>(storage: &StorageHolder<CS>) -> HashMap<String, HashMap<u64, i32>> {
+        storage.get_field_json("workspace_priorities").unwrap_or_default()
+    }
+
+    pub fn get_overrides(workspace_name: &str) -> HashMap<u64, i32> {
+        read_all(&*GLOBAL_CONFIG.lock().unwrap()).remove(workspace_name).unwrap_or_default()
+    }
+
+    pub fn get_active_overrides() -> HashMap<u64, i32> {
+        let storage = GLOBAL_CONFIG.lock().unwrap();
+
+        match workspaces::read_active_workspace_name(&*storage) {
+            Ok(name) => read_all(&*storage).remove(&name).unwrap_or_default(),
+            Err(_) => HashMap::new(),
+        }
+    }
+
+    /// Sets the priority of some mods in a workspace, keyed by [`mod_key`]. The priorities of the other mods are kept.
+    pub fn write_overrides<CS: ConfigStorage>(
+        storage: &mut StorageHolder<CS>,
+        workspace_name: &str,
+        priorities: impl IntoIterator<Item = (u64, i32)>,
+    ) -> Result<(), ConfigError> {
+        let mut overrides = read_all(storage);
+        overrides.entry(workspace_name.to_string()).or_default().extend(priorities);
+        storage.set_field_json("workspace_priorities", &overrides)
+    }
+
+    pub fn set_overrides(workspace_name: &str, priorities: impl IntoIterator<Item = (u64, i32)>) -> Result<(), ConfigError> {
+        write_overrides(&mut *GLOBAL_CONFIG.lock().unwrap(), workspace_name, priorities)
+    }
+
+    pub fn set_override(workspace_name: &str, mod_key: u64, priority: i32) -> Result<(), ConfigError> {
+        set_overrides(workspace_name, std::iter::once((mod_key, priority)))
+    }
+
+    /// Keeps the priorities of a workspace when it is renamed to `to`, or drops them if it is removed
+    pub fn relink<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, from: &str, to: Option<&str>) -> Result<(), ConfigError> {
+        let mut overrides = read_all(storage);
+
+        match (overrides.remove(from), to) {
+            (Some(workspace), Some(to)) => {
+                overrides.insert(to.to_string(), workspace);
+            },
+            (None, _) => return Ok(()),
+            _ => {},
+        }
+
+        storage.set_field_json("workspace_priorities", &overrides)
+    }
+
+    /// Gives `target` the same priorities as `source`
+    pub fn copy<CS: ConfigStorage>(storage: &mut StorageHolder<CS>, source: &str, target: &str) -> Result<(), ConfigError> {
+        let mut overrides = read_all(storage);
+
+        match overrides.get(source).cloned() {
+            Some(workspace) => {
+                overrides.insert(target.to_string(), workspace);
+                storage.set_field_json("workspace_priorities", &overrides)
+            },
+            None => Ok(()),
+        }
+    }
+}
+
+pub mod groups {
+    use super::*;
+
+    use skyline_config::ConfigError;
+
+    /// Identifies a file group of a mod, `mod_path` being the full path of the mod folder like for the presets
+    pub fn group_hash(mod_path: &str, group_name: &str) -> Hash40 {
+        Hash40::from(format!("{}:{}", mod_path, group_name).as_str())
+    }
+
+    /// File groups that are disabled in a workspace. Groups are enabled unless they are in here, so a mod can add groups at any time.
+    pub fn get_disabled(workspace_name: &str) -> HashSet<Hash40> {
+        let mut disabled: HashMap<String, HashSet<Hash40>> = GLOBAL_CONFIG.lock().unwrap().get_field_json("disabled_groups").unwrap_or_default();
+        disabled.remove(workspace_name).unwrap_or_default()
+    }
+
+    pub fn set_disabled(workspace_name: &str, groups: &HashSet<Hash40>) -> Result<(), ConfigError> {
+        let mut storage = GLOBAL_CONFIG.lock().unwrap();
+        let mut disabled: HashMap<String, HashSet<Hash40>> = storage.get_field_json("disabled_groups").unwrap_or_default();
+        disabled.insert(workspace_name.to_string(), groups.clone());
+        storage.set_field_json("disabled_groups", &disabled)
+    }
+}
+
+pub mod blocklist {
+    use super::*;
+
+    use skyline_config::ConfigError;
+
+    /// Where to download the blocklist from on boot, if anywhere
+    pub fn get_url() -> Option<String> {
+        GLOBAL_CONFIG.lock().unwrap().get_field("blocklist_url").ok().filter(|url: &String| !url.is_empty())
+    }
+
+    /// Blocklist entries the user chose to ignore, accepting the risk of loading the mods they match
+    pub fn get_overrides() -> HashSet<String> {
+        GLOBAL_CONFIG.lock().unwrap().get_field_json("blocklist_overrides").unwrap_or_default()
+    }
+
+    pub fn set_override(id: &str, state: bool) -> Result<(), ConfigError> {
+        let mut storage = GLOBAL_CONFIG.lock().unwrap();
+        let mut overrides: HashSet<String> = storage.get_field_json("blocklist_overrides").unwrap_or_default();
+
+        if state {
+            overrides.insert(id.to_string());
+        } else {
+            overrides.remove(id);
+        }
+
+        storage.set_field_json("blocklist_overrides", &overrides)
+    }
+
+    /// Blocked mods the user was already told about, as `entry id/folder name`
+    pub fn get_notified() -> HashSet<String> {
+        GLOBAL_CONFIG.lock().unwrap().get_field_json("blocklist_notified").unwrap_or_default()
+    }
+
+    pub fn set_notified(notified: &HashSet<String>) -> Result<(), ConfigError> {
+        GLOBAL_CONFIG.lock().unwrap().set_field_json("blocklist_notified", notified)
+    }
+}
+
+pub mod collections {
+    use super::*;
+
+    use serde::{Deserialize, Serialize};
+    use skyline_config::ConfigError;
+    use thiserror::Error;
+
+    #[derive(Debug, Error)]
+    pub enum CollectionError {
+        #[error("a configuration error happened: {0}")]
+        ConfigError(#[from] ConfigError),
+        #[error("a collection with this name already exists")]
+        AlreadyExists,
+        #[error("failed to find collection with name: {0}")]
+        MissingCollection(String),
+    }
+
+    /// A user-defined group of mods, which can be nested in another collection. Collections are shared by every workspace.
+    #[derive(Debug, Default, Clone, Serialize, Deserialize)]
+    pub struct Collection {
+        pub parent: Option<String>,
+        /// Folder names of the mods in this collection. Mods that were deleted from the SD are kept, in case they come back.
+        pub mods: HashSet<String>,
+    }
+
+    pub fn get_list() -> HashMap<String, Collection> {
+        GLOBAL_CONFIG.lock().unwrap().get_field_json("collections").unwrap_or_default()
+    }
+
+    fn save_list(collections: &HashMap<String, Collection>) -> Result<(), CollectionError> {
         GLOBAL_CONFIG
             .lock()
             .unwrap()
-            .set_field_json(preset_name, preset)
-            .map_err(PresetError::ConfigError)
+            .set_field_json("collections", collections)
+            .map_err(CollectionError::ConfigError)
+    }
+
+    pub fn create_collection(name: String, parent: Option<String>) -> Result<(), CollectionError> {
+        let mut collections = get_list();
+
+        if collections.contains_key(&name) {
+            return Err(CollectionError::AlreadyExists);
+        }
+
+        if let Some(parent) = parent.as_ref().filter(|parent| !collections.contains_key(*parent)) {
+            return Err(CollectionError::MissingCollection(parent.to_owned()));
+        }
+
+        collections.insert(name, Collection { parent, mods: HashSet::new() });
+        save_list(&collections)
+    }
+
+    /// Deletes a collection, moving the collections nested in it up a level. The mods themselves are left untouched.
+    pub fn delete_collection(name: &str) -> Result<(), CollectionError> {
+        let mut collections = get_list();
+        let removed = collections.remove(name).ok_or_else(|| CollectionError::MissingCollection(name.to_owned()))?;
+
+        for collection in collections.values_mut().filter(|collection| collection.parent.as_deref() == Some(name)) {
+            collection.parent = removed.parent.clone();
+        }
+
+        save_list(&collections)
+    }
+
+    /// Adds the mods to the collection, or removes them from it if `state` is false
+    pub fn set_mods_state(name: &str, folder_names: &[&str], state: bool) -> Result<(), CollectionError> {
+        let mut collections = get_list();
+        let collection = collections.get_mut(name).ok_or_else(|| CollectionError::MissingCollection(name.to_owned()))?;
+
+        for folder_name in folder_names {
+            if state {
+                collection.mods.insert(folder_name.to_string());
+            } else {
+                collection.mods.remove(*folder_name);
+            }
+        }
+
+        save_list(&collections)
+    }
+
+    /// Gets the mods of a collection along with the ones of every collection nested in it
+    pub fn get_mods_recursive(collections: &HashMap<String, Collection>, name: &str) -> HashSet<String> {
+        let mut mods = HashSet::new();
+        let mut pending = vec![name];
+        // Guards against cycles from a hand-edited configuration
+        let mut visited = HashSet::new();
+
+        while let Some(current) = pending.pop() {
+            if !visited.insert(current) {
+                continue;
+            }
+
+            if let Some(collection) = collections.get(current) {
+                mods.extend(collection.mods.iter().cloned());
+            }
+
+            pending.extend(
+                collections
+                    .iter()
+                    .filter(|(_, collection)| collection.parent.as_deref() == Some(current))
+                    .map(|(child, _)| child.as_str()),
+            );
+        }
+
+        mods
+    }
+}
+
+pub mod snapshots {
+    use super::*;
+
+    use skyline_config::ConfigError;
+
+    /// Path of every installed mod folder, along with the last time its contents were modified (in seconds since the UNIX epoch)
+    pub type ModSnapshot = HashMap<String, u64>;
+
+    /// Saves the snapshot taken on this boot, keeping the one from the previous boot around to compare against
+    pub fn rotate(current: &ModSnapshot) -> Result<(), ConfigError> {
+        let mut storage = GLOBAL_CONFIG.lock().unwrap();
+
+        // Nothing to keep on the first boot, so make sure a stale previous snapshot doesn't stick around either
+        let previous: Option<ModSnapshot> = storage.get_field_json("mod_snapshot").ok();
+        storage.set_field_json("previous_mod_snapshot", &previous)?;
+        storage.set_field_json("mod_snapshot", current)
+    }
+
+    pub fn get_current() -> Option<ModSnapshot> {
+        GLOBAL_CONFIG.lock().unwrap().get_field_json("mod_snapshot").ok()
+    }
+
+    /// None if this is the first boot with snapshots
+    pub fn get_previous() -> Option<ModSnapshot> {
+        GLOBAL_CONFIG.lock().unwrap().get_field_json::<Option<ModSnapshot>>("previous_mod_snapshot").ok().flatten()
+    }
+}
+
+pub mod recovery {
+    use super::*;
+    use std::path::Path;
+
+    use skyline_config::ConfigError;
+    use thiserror::Error;
+
+    use super::presets::PresetError;
+
+    #[derive(Debug, Error)]
+    pub enum RecoveryError {
+        #[error("failed to back up the configuration: {0}")]
+        BackupFailed(#[from] std::io::Error),
+        #[error("a configuration error happened: {0}")]
+        ConfigError(#[from] ConfigError),
+        #[error("a preset error happened: {0}")]
+        PresetError(#[from] PresetError),
+    }
+
+    fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+        std::fs::create_dir_all(to)?;
+
+        for entry in std::fs::read_dir(from)? {
+            let entry = entry?;
+            let target = to.join(entry.file_name());
+
+            if entry.file_type()?.is_dir() {
+                copy_dir(&entry.path(), &target)?;
+            } else {
+                std::fs::copy(entry.path(), target)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Copies the whole configuration folder, the settings of every user included, to a new timestamped folder and returns its path
+    pub fn backup() -> Result<PathBuf, RecoveryError> {
+        let seconds = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or_default();
+        let destination = utils::paths::config_backups().join(seconds.to_string()).into_std_path_buf();
+
+        copy_dir(&ArcStorage::new().root_path(), &destination)?;
+        info!("Backed up the configuration to '{}'.", destination.display());
+
+        Ok(destination)
+    }
+
+    /// Backs up the configuration, then replaces it with the one generated on a first boot. Every workspace and preset is lost
+    /// apart from the copy in the backup.
+    pub fn reset_config() -> Result<PathBuf, RecoveryError> {
+        let destination = backup()?;
+        generate_default_config(&mut *GLOBAL_CONFIG.lock().unwrap())?;
+
+        Ok(destination)
+    }
+
+    /// Backs up the configuration, then disables every mod of a workspace, including the ones it inherits
+    pub fn clear_preset(workspace_name: &str) -> Result<PathBuf, RecoveryError> {
+        let destination = backup()?;
+        presets::replace_preset(workspace_name, &HashSet::new())?;
+
+        Ok(destination)
     }
 }
 
@@ -310,3 +1197,211 @@ impl ConfigStorage for ArcStorage {
         self.root_path().join(&self.0)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Storage in a temporary folder, removed once dropped
+    struct TestStorage(PathBuf);
+
+    impl TestStorage {
+        fn new(name: &str) -> Self {
+            let path = std::env::temp_dir().join(name);
+            let _ = std::fs::remove_dir_all(&path);
+            Self(path)
+        }
+    }
+
+    impl Drop for TestStorage {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    impl ConfigStorage for TestStorage {
+        fn initialize(&self) -> Result<(), ConfigError> {
+            std::fs::create_dir_all(self.storage_path())?;
+            Ok(())
+        }
+
+        fn root_path(&self) -> PathBuf {
+            self.0.clone()
+        }
+
+        fn storage_path(&self) -> PathBuf {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn users_keep_their_own_active_workspace() {
+        // Each user profile gets a storage folder of its own
+        let mut first = StorageHolder::new(TestStorage::new("arcropolis_user_workspaces_first"));
+        let mut second = StorageHolder::new(TestStorage::new("arcropolis_user_workspaces_second"));
+
+        workspaces::write_active_workspace_name(&mut first, String::from("Casual")).unwrap();
+        workspaces::write_active_workspace_name(&mut second, String::from("Competitive")).unwrap();
+
+        assert_eq!(workspaces::read_active_workspace_name(&first).unwrap(), "Casual");
+        assert_eq!(workspaces::read_active_workspace_name(&second).unwrap(), "Competitive");
+    }
+
+    #[test]
+    fn switching_workspaces_changes_the_log_level() {
+        let mut storage = StorageHolder::new(TestStorage::new("arcropolis_workspace_overrides"));
+        storage.set_field("workspace", "Default").unwrap();
+        storage.set_field("logging_level", "Warn").unwrap();
+
+        overrides::write(&mut storage, "Debugging", "logging_level", Some("Trace")).unwrap();
+        assert_eq!(read_layered_field(&storage, "logging_level").unwrap(), "Warn");
+
+        workspaces::write_active_workspace_name(&mut storage, String::from("Debugging")).unwrap();
+        assert_eq!(read_layered_field(&storage, "logging_level").unwrap(), "Trace");
+
+        // Settings the workspace doesn't override fall through to the global ones
+        storage.set_field("log_dedup_window", "500").unwrap();
+        assert_eq!(read_layered_field(&storage, "log_dedup_window").unwrap(), "500");
+
+        overrides::write(&mut storage, "Debugging", "logging_level", None).unwrap();
+        assert_eq!(read_layered_field(&storage, "logging_level").unwrap(), "Warn");
+        assert!(overrides::read_all(&storage).is_empty());
+    }
+
+    #[test]
+    fn workspaces_keep_their_own_priorities() {
+        let mut storage = StorageHolder::new(TestStorage::new("arcropolis_workspace_priorities"));
+
+        priorities::write_overrides(&mut storage, "Casual", [(1, 10), (2, 5)]).unwrap();
+        priorities::write_overrides(&mut storage, "Competitive", [(1, -1)]).unwrap();
+        // Only the mods given are changed
+        priorities::write_overrides(&mut storage, "Casual", [(2, 20), (3, 1)]).unwrap();
+
+        let all = priorities::read_all(&storage);
+        assert_eq!(all["Casual"], HashMap::from([(1, 10), (2, 20), (3, 1)]));
+        assert_eq!(all["Competitive"], HashMap::from([(1, -1)]));
+
+        priorities::relink(&mut storage, "Casual", Some("Friendlies")).unwrap();
+        priorities::copy(&mut storage, "Friendlies", "Copy").unwrap();
+        priorities::relink(&mut storage, "Competitive", None).unwrap();
+
+        let all = priorities::read_all(&storage);
+        assert_eq!(all["Friendlies"], HashMap::from([(1, 10), (2, 20), (3, 1)]));
+        assert_eq!(all["Copy"], all["Friendlies"]);
+        assert!(!all.contains_key("Casual") && !all.contains_key("Competitive"));
+    }
+
+    #[test]
+    fn missing_presets_start_over_empty() {
+        let mut storage = StorageHolder::new(TestStorage::new("arcropolis_missing_presets"));
+        storage.set_field_json("workspace_list", &HashMap::from([("Default", "Default_preset"), ("Broken", "Broken_preset")])).unwrap();
+        storage.set_field("Broken_preset", "{").unwrap();
+
+        for workspace in ["Default", "Broken"] {
+            assert!(presets::read_preset(&storage, workspace).is_err());
+            assert!(presets::read_preset_or_reset(&mut storage, workspace).is_empty());
+            // The empty preset was saved, so the selection can be rebuilt from there
+            assert!(presets::read_preset(&storage, workspace).unwrap().is_empty());
+        }
+    }
+
+    #[test]
+    fn workspaces_merge_the_presets_they_inherit() {
+        let mut storage = StorageHolder::new(TestStorage::new("arcropolis_inherited_presets"));
+        let workspace_list = HashMap::from([("Base", "Base_preset"), ("Online", "Online_preset"), ("Tourney", "Tourney_preset")]);
+        storage.set_field_json("workspace_list", &workspace_list).unwrap();
+
+        presets::write_preset(&mut storage, "Base", &HashSet::from([Hash40(1), Hash40(2)])).unwrap();
+        workspaces::write_parent(&mut storage, "Online", Some("Base")).unwrap();
+        workspaces::write_parent(&mut storage, "Tourney", Some("Online")).unwrap();
+        assert_eq!(workspaces::inheritance_chain(&workspaces::read_parents(&storage), "Tourney").unwrap(), ["Base", "Online", "Tourney"]);
+
+        // The child disables a mod of its parent and enables one of its own
+        presets::write_preset(&mut storage, "Online", &HashSet::from([Hash40(2), Hash40(3)])).unwrap();
+        presets::write_preset(&mut storage, "Tourney", &HashSet::from([Hash40(2), Hash40(3), Hash40(4)])).unwrap();
+        assert_eq!(presets::read_preset(&storage, "Tourney").unwrap(), HashSet::from([Hash40(2), Hash40(3), Hash40(4)]));
+
+        // Later changes to the base carry over to the workspaces inheriting from it
+        presets::write_preset(&mut storage, "Base", &HashSet::from([Hash40(1), Hash40(2), Hash40(5)])).unwrap();
+        assert_eq!(presets::read_preset(&storage, "Online").unwrap(), HashSet::from([Hash40(2), Hash40(3), Hash40(5)]));
+        assert_eq!(presets::read_preset(&storage, "Tourney").unwrap(), HashSet::from([Hash40(2), Hash40(3), Hash40(4), Hash40(5)]));
+    }
+
+    #[test]
+    fn inheritance_cycles_are_refused() {
+        let mut storage = StorageHolder::new(TestStorage::new("arcropolis_inheritance_cycles"));
+
+        workspaces::write_parent(&mut storage, "Online", Some("Base")).unwrap();
+        workspaces::write_parent(&mut storage, "Tourney", Some("Online")).unwrap();
+
+        let is_cycle = |result: Result<(), workspaces::WorkspaceError>| matches!(result, Err(workspaces::WorkspaceError::InheritanceCycle(_)));
+        assert!(is_cycle(workspaces::write_parent(&mut storage, "Base", Some("Tourney"))));
+        assert!(is_cycle(workspaces::write_parent(&mut storage, "Base", Some("Base"))));
+        // Nothing was saved
+        assert!(!workspaces::read_parents(&storage).contains_key("Base"));
+    }
+
+    #[test]
+    fn presets_are_cleaned_of_deleted_mods() {
+        let mut storage = StorageHolder::new(TestStorage::new("arcropolis_stale_presets"));
+        storage.set_field_json("workspace_list", &HashMap::from([("Base", "Base_preset"), ("Online", "Online_preset")])).unwrap();
+        workspaces::write_parent(&mut storage, "Online", Some("Base")).unwrap();
+
+        let mods = std::env::temp_dir().join("arcropolis_stale_presets_mods");
+        let _ = std::fs::remove_dir_all(&mods);
+        let hash = |name: &str| Hash40::from(mods.join(name).to_str().unwrap());
+
+        for name in ["Kept", "Deleted", "Inherited"] {
+            std::fs::create_dir_all(mods.join(name)).unwrap();
+        }
+
+        presets::write_preset(&mut storage, "Base", &HashSet::from([hash("Inherited")])).unwrap();
+        presets::write_preset(&mut storage, "Online", &HashSet::from([hash("Kept"), hash("Deleted"), hash("Inherited")])).unwrap();
+
+        std::fs::remove_dir_all(mods.join("Deleted")).unwrap();
+        std::fs::remove_dir_all(mods.join("Inherited")).unwrap();
+
+        // Installed folders are hashed the same way as the preset entries
+        let installed: HashSet<Hash40> = std::fs::read_dir(&mods)
+            .unwrap()
+            .map(|entry| Hash40::from(entry.unwrap().path().to_str().unwrap()))
+            .collect();
+
+        let stale = HashSet::from([hash("Deleted"), hash("Inherited")]);
+        assert_eq!(presets::read_stale_entries(&storage, "Online", &installed).unwrap(), stale);
+
+        assert_eq!(presets::remove_stale_entries(&mut storage, "Online", &installed).unwrap(), 2);
+        assert_eq!(presets::read_preset(&storage, "Online").unwrap(), HashSet::from([hash("Kept")]));
+        // The parent would otherwise keep handing the deleted mod back
+        assert!(presets::read_preset(&storage, "Base").unwrap().is_empty());
+        assert!(presets::read_stale_entries(&storage, "Online", &installed).unwrap().is_empty());
+
+        std::fs::remove_dir_all(&mods).unwrap();
+    }
+
+    fn mount_order(strategy: ConflictStrategy) -> Vec<&'static str> {
+        let mut mods = vec![("charlie", 0), ("Bravo", 5), ("alpha", 0), ("delta", 5)];
+        strategy.sort_mods(&mut mods, |(name, _)| name.to_string(), |(_, priority)| *priority);
+        mods.into_iter().map(|(name, _)| name).collect()
+    }
+
+    #[test]
+    fn mods_are_mounted_in_the_order_of_the_strategy() {
+        assert_eq!(mount_order(ConflictStrategy::Priority), ["Bravo", "delta", "alpha", "charlie"]);
+        assert_eq!(mount_order(ConflictStrategy::Error), ["Bravo", "delta", "alpha", "charlie"]);
+        assert_eq!(mount_order(ConflictStrategy::FirstWins), ["alpha", "Bravo", "charlie", "delta"]);
+        assert_eq!(mount_order(ConflictStrategy::LastWins), ["delta", "charlie", "Bravo", "alpha"]);
+    }
+
+    #[test]
+    fn sd_mods_win_over_the_game_files_unless_asked_otherwise() {
+        // The same mod, thus the same files, in both places, listed with the extracted files first
+        let mut mods = vec!["rom:/arc/Skins", "sd:/ultimate/mods/Skins", "rom:/arc/Stages", "sd:/ultimate/mods/Music"];
+
+        sort_mods_by_source(&mut mods, |path| path.to_string(), false);
+        assert_eq!(mods, ["sd:/ultimate/mods/Skins", "sd:/ultimate/mods/Music", "rom:/arc/Skins", "rom:/arc/Stages"]);
+
+        sort_mods_by_source(&mut mods, |path| path.to_string(), true);
+        assert_eq!(mods, ["rom:/arc/Skins", "rom:/arc/Stages", "sd:/ultimate/mods/Skins", "sd:/ultimate/mods/Music"]);
+    }
+}
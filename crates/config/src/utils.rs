@@ -13,4 +13,8 @@ pub mod paths {
     pub fn mods() -> Utf8PathBuf {
         Utf8PathBuf::from("sd:/ultimate/mods")
     }
+
+    pub fn config_backups() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/config_backups")
+    }
 }
@@ -1,5 +1,8 @@
 const MOD_MENU = "modMenu";
 const SUB_MENU = "subMenu";
+const LOG_VIEW = "logView";
+const WORKSPACE_VIEW = "workspaceView";
+const COLLECTION_VIEW = "collectionView";
 const categories = [
     "All",
     "Fighter",
@@ -20,13 +23,20 @@ var currentActiveDescription // For reference to the current active description.
 var activeDescHeight = 0; // The height for the current active description so it can't be scrolled out of bounds.
 
 var mods = [];
+// The mods keyed by their id, which is what the mod list and the messages refer to them with
+var modsById = {};
 var currentMods = [];
+// Ids of the mods matching the search box, null until the plugin answers
+var searchMatches = null;
 var modSize = 0;
+// Set while the size of the enabled mods is being computed, as { done, total }
+var modSizeProgress = null;
+var bypassReplacements = false;
 var pageCount = 0;
 
 function createMod(mod_id) {
-    var hidden = mods[mod_id]['is_disabled'] ? "hidden" : "";
-    return `<button id="btn-mods-${mod_id}" data-mod-index="${mod_id}" tabindex="0" class="flex-button abstract-button All ${mods[mod_id]['category']}" nx-se-disabled="">
+    var hidden = modsById[mod_id]['is_disabled'] ? "hidden" : "";
+    return `<button id="btn-mods-${mod_id}" data-mod-index="${mod_id}" tabindex="0" class="flex-button abstract-button All ${modsById[mod_id]['category']}" nx-se-disabled="">
     <div class="abstract-icon-back-decoration"></div>
     <div class="abstract-button-border">
         <div class="abstract-button-inner">
@@ -36,14 +46,21 @@ function createMod(mod_id) {
                 </div>
             </div>
             <div class="abstract-button-text f-u-bold mod-name"
-                style="display: block; font-size: 26px; text-indent: 10px; margin-top: 8px;" data-display_name="${mods[mod_id]['display_name']}">
-                <span class="marquee" data-msgid="textbox_id-4-1">${mods[mod_id]['display_name']}</span>
+                style="display: block; font-size: 26px; text-indent: 10px; margin-top: 8px;" data-display_name="${modsById[mod_id]['display_name']}">
+                <span class="marquee" data-msgid="textbox_id-4-1">${modsById[mod_id]['display_name']}</span>
             </div>
         </div>
     </div>
 </button>`;
 }
 
+function indexMods() {
+    modsById = {};
+    for (var i = 0; i < mods.length; i++) {
+        modsById[mods[i]["id"]] = mods[i];
+    }
+}
+
 function createMods(mods) {
     var res = "";
     for (var i = 0; i < mods.length; i++) {
@@ -53,11 +70,15 @@ function createMods(mods) {
 }
 
 function toggleMod() {
-    var index = parseInt($(".is-focused").attr("data-mod-index"));
+    var index = Number($(".is-focused").attr("data-mod-index"));
     var checkContainer = $(".is-focused .img-check");
+    var enabled = checkContainer.hasClass("hidden");
+
+    if (enabled && !disableConflicts(modsById[index])) { return; }
+    if (enabled) { enableDependencies(modsById[index]); }
+
     checkContainer.toggleClass("hidden");
-    var enabled = !checkContainer.hasClass("hidden");
-    mods[index]["is_disabled"] = !enabled;
+    modsById[index]["is_disabled"] = !enabled;
     // Send mod index and status
     window.nx.sendMessage(JSON.stringify({
         "ToggleMod": {
@@ -67,6 +88,59 @@ function toggleMod() {
     }));
 }
 
+// Asks to disable the enabled mods that can't be used along with the given one. Returns false if the user would rather keep them.
+function disableConflicts(mod) {
+    var conflicts = mod["conflicts"].filter(id => modsById[id] != undefined && !modsById[id]["is_disabled"]);
+    if (conflicts.length == 0) { return true; }
+
+    var names = conflicts.map(id => modsById[id]["display_name"]).join(", ");
+    if (!confirm(`${mod["display_name"]} can't be used along with ${names}. Disable ${conflicts.length > 1 ? 'them' : 'it'}?`)) { return false; }
+
+    for (var i = 0; i < conflicts.length; i++) {
+        modsById[conflicts[i]]["is_disabled"] = true;
+        $(`#btn-mods-${conflicts[i]} .img-check`).addClass("hidden");
+    }
+
+    window.nx.sendMessage(JSON.stringify({
+        "ChangeMods": {
+            "state": false,
+            "ids": conflicts
+        }
+    }));
+
+    return true;
+}
+
+// Offers to enable the disabled mods the given one needs, and warns about the ones that aren't installed
+function enableDependencies(mod) {
+    if (mod["missing_dependencies"].length > 0) {
+        alert(`${mod["display_name"]} needs ${mod["missing_dependencies"].join(", ")}, which ${mod["missing_dependencies"].length > 1 ? 'are' : 'is'} not installed.`);
+    }
+
+    var dependencies = mod["dependencies"].filter(id => modsById[id] != undefined && modsById[id]["is_disabled"]);
+    if (dependencies.length == 0) { return; }
+
+    var names = dependencies.map(id => modsById[id]["display_name"]).join(", ");
+    if (!confirm(`${mod["display_name"]} needs ${names}. Enable ${dependencies.length > 1 ? 'them' : 'it'} as well?`)) { return; }
+
+    // Enabling a dependency can't be allowed to bring in one of its conflicts silently
+    dependencies = dependencies.filter(id => disableConflicts(modsById[id]));
+
+    for (var i = 0; i < dependencies.length; i++) {
+        modsById[dependencies[i]]["is_disabled"] = false;
+        $(`#btn-mods-${dependencies[i]} .img-check`).removeClass("hidden");
+    }
+
+    if (dependencies.length == 0) { return; }
+
+    window.nx.sendMessage(JSON.stringify({
+        "ChangeMods": {
+            "state": true,
+            "ids": dependencies
+        }
+    }));
+}
+
 function updateCurrentDesc() {
     // Reset current description height
     currentDescHeight = 0;
@@ -88,7 +162,10 @@ function checkGamepad(index, gamepad) {
     var axisX = gamepad.axes[0];
     var axisY = gamepad.axes[1];
 
-    if (currentState == MOD_MENU) {
+    if (currentState == LOG_VIEW) {
+        var logContents = $("#log-contents");
+        logContents.scrollTop(logContents.scrollTop() + gamepad.axes[3] * 20);
+    } else if (currentState == MOD_MENU) {
         var RStickYValue = gamepad.axes[3].toFixed(2);
 
         RStickYValue = (((RStickYValue - 0) * (20 - 0)) / (1 - 0)) + 0;
@@ -173,11 +250,12 @@ function move(source, target) {
         }
         target.classList.add("is-focused");
         target.focus();
-        var mod = mods[target.getAttribute("data-mod-index")];
+        var mod = modsById[target.getAttribute("data-mod-index")];
         $("#description").html(mod["description"]);
         $("#version").html(mod["version"]);
         $("#author").html(mod["author"]);
-        $("#preview").attr("src", `img/${mod['id']}`);
+        showGroups(mod);
+        $("#preview").attr("src", `arcadia-previews/${mod['id']}`);
         updateCurrentDesc();
     }
 }
@@ -236,15 +314,21 @@ function sizeToFormattedBytes(size) {
     return `${size.toFixed(2)} gb`;
 }
 
+function showModSize() {
+    if (modSizeProgress != null)
+        $("#modSize").html(`Measuring enabled mods... (${modSizeProgress["done"]}/${modSizeProgress["total"]})`)
+    else if (modSize == 0)
+        $("#modSize").html("")
+    else
+        $("#modSize").html(`${sizeToFormattedBytes(modSize)} of mods enabled`)
+}
+
 function showSubMenu() {
     $("#modsCount").html(`${mods.length} mod${mods.length > 1 ? 's' : ''}`);
     var activeMods = 0;
     mods.forEach(mod => activeMods = mod["is_disabled"] ? activeMods : activeMods + 1);
     $("#activeModsCount").html(`${activeMods} active mod${activeMods > 1 ? 's' : ''}`);
-    if (modSize == 0)
-        $("#modSize").html("")
-    else
-        $("#modSize").html(`${sizeToFormattedBytes(modSize)} of mods enabled`)
+    showModSize();
 
     $("#submenu").css("display", "flex");
     $("#Fighter").focus();
@@ -258,14 +342,40 @@ function updateCurrentModsWCategories() {
         categoriesToUse.push($(this).attr('id'));
     });
     currentMods = categoriesToUse.length == 0 ? mods.map(x => x["id"]) : mods.filter(mod => categoriesToUse.includes(mod["category"])).map(x => x["id"]);
+
+    // The mods matching the search, as last answered by the plugin
+    if (searchMatches != null) {
+        currentMods = currentMods.filter(id => searchMatches.has(id));
+    }
+}
+
+function searchQuery() {
+    return ($("#search").val() || "").trim();
+}
+
+// The plugin does the matching, the list is shown again once it answers
+function requestSearch() {
+    window.nx.sendMessage(JSON.stringify({
+        "SearchMods": {
+            "query": searchQuery()
+        }
+    }));
 }
 
 function showModMenu() {
+    // The list may have changed since the last search, so it is run again
+    requestSearch();
+    showSearchedMods();
+}
+
+function showSearchedMods() {
     $("#submenu").css("display", "none");
     document.querySelector('meta[name="focus-ring-visibility"]').setAttribute("content", "hidden");
     updateCurrentModsWCategories();
     if (currentMods.length == 0) {
-        $("#description").html(`No mods found under:<br />${categoriesToUse.join("<br />")}`);
+        var search = searchQuery();
+        var matching = search.length > 0 ? `<br />matching "${$("<span>").text(search).html()}"` : "";
+        $("#description").html(`No mods found under:<br />${categoriesToUse.join("<br />")}${matching}`);
     }
     refreshCurrentMods();
     currentState = MOD_MENU;
@@ -318,19 +428,528 @@ function setAllState(state, src) {
 function setCurrentModsState(state, src) {
     updateCurrentModsWCategories();
     for (var i = 0; i < currentMods.length; i++) {
-        mods[currentMods[i]]["is_disabled"] = !state;
+        modsById[currentMods[i]]["is_disabled"] = !state;
     }
     refreshCurrentMods();
     src != undefined || src != null ? src.focus() : false;
     if (currentMods.length <= 0) { return; }
     window.nx.sendMessage(JSON.stringify({
-        "ChangeIndexes": {
+        "ChangeMods": {
             "state": state,
-            "indexes": currentMods
+            "ids": currentMods
+        }
+    }));
+}
+
+function setPriority() {
+    var index = Number($(".is-focused").attr("data-mod-index"));
+    if (isNaN(index)) { return; }
+
+    var input = prompt(`Priority for ${modsById[index]["display_name"]} (higher wins conflicts), or + / - to move it above / below the next enabled mod`, modsById[index]["priority"] || 0);
+    if (input == null) { return; }
+
+    if (input.trim() == "+" || input.trim() == "-") {
+        moveMod(index, input.trim() == "+");
+        return;
+    }
+
+    var priority = parseInt(input);
+    if (isNaN(priority)) { return; }
+
+    window.nx.sendMessage(JSON.stringify({
+        "SetPriority": {
+            "id": index,
+            "priority": priority
         }
     }));
 }
 
+// Swaps a mod with its neighbour in the order the enabled mods win conflicts in, and saves the whole order as priorities
+function moveMod(index, up) {
+    var order = mods
+        .filter(mod => !mod["is_disabled"])
+        .sort((a, b) => (b["priority"] || 0) - (a["priority"] || 0) || (a["folder_name"] || "").toLowerCase().localeCompare((b["folder_name"] || "").toLowerCase()))
+        .map(mod => mod["id"]);
+
+    var position = order.indexOf(index);
+    if (position == -1) {
+        alert(`${modsById[index]["display_name"]} has to be enabled to be moved.`);
+        return;
+    }
+
+    var target = up ? position - 1 : position + 1;
+    if (target < 0 || target >= order.length) { return; }
+
+    order.splice(position, 1);
+    order.splice(target, 0, index);
+
+    window.nx.sendMessage(JSON.stringify({
+        "ReorderMods": {
+            "ids": order
+        }
+    }));
+}
+
+function showOrder(priorities) {
+    priorities.forEach(entry => { modsById[entry["id"]]["priority"] = entry["priority"]; });
+
+    var lines = priorities.map((entry, i) => `${i + 1}. ${modsById[entry["id"]]["display_name"]}`);
+    alert(`Takes effect on the next boot, mods higher in the list win conflicts:\n\n${lines.join("\n")}`);
+}
+
+function showGroups(mod) {
+    var groups = mod["group"] || [];
+    if (groups.length == 0) {
+        $("#groups-info").css("display", "none");
+        return;
+    }
+
+    $("#groups").html(groups.map(group => `${group["name"]} (${group["enabled"] ? "on" : "off"})`).join(", "));
+    $("#groups-info").css("display", "block");
+}
+
+function toggleGroup(mod, groupIndex) {
+    var group = (mod["group"] || [])[groupIndex];
+    if (group == undefined) { return; }
+
+    group["enabled"] = !group["enabled"];
+    showGroups(mod);
+    window.nx.sendMessage(JSON.stringify({
+        "ToggleGroup": {
+            "id": mod["id"],
+            "group": group["name"],
+            "state": group["enabled"]
+        }
+    }));
+}
+
+// Mods with file groups get to pick one to toggle first, leaving the input empty moves on to the priority
+function editMod() {
+    var index = Number($(".is-focused").attr("data-mod-index"));
+    if (isNaN(index)) { return; }
+
+    var mod = modsById[index];
+    var groups = mod["group"] || [];
+
+    if (groups.length > 0) {
+        var lines = groups.map((group, i) => `${i + 1}. ${group["name"]} (${group["enabled"] ? "on" : "off"})`);
+        var input = prompt(`Number of the group to toggle, or nothing to set the priority:\n${lines.join("\n")}`, "");
+        if (input == null) { return; }
+
+        if (input.trim() != "") {
+            toggleGroup(mod, parseInt(input) - 1);
+            return;
+        }
+    }
+
+    setPriority();
+}
+
+function inspectMod() {
+    var index = Number($(".is-focused").attr("data-mod-index"));
+    if (isNaN(index)) { return; }
+
+    window.nx.sendMessage(JSON.stringify({
+        "InspectMod": {
+            "id": index
+        }
+    }));
+}
+
+function exportManifest() {
+    var index = Number($(".is-focused").attr("data-mod-index"));
+    if (isNaN(index)) { return; }
+
+    window.nx.sendMessage(JSON.stringify({
+        "ExportModManifest": {
+            "id": index
+        }
+    }));
+}
+
+function showManifest(manifest) {
+    var location = manifest["path"] == null ? "could not be saved to the SD card" : `saved to ${manifest["path"]}`;
+    showLog({ "name": `Files of ${manifest["name"]} (${location})`, "contents": manifest["contents"] });
+}
+
+function formatSize(size) {
+    return size >= 1024 * 1024 ? `${(size / (1024 * 1024)).toFixed(2)} MB` : `${(size / 1024).toFixed(2)} KB`;
+}
+
+// Lists the files of a mod with the size of the vanilla file they replace, flagging the ones that are larger and the ones other
+// enabled mods provide as well
+function showInspect(inspect) {
+    var conflicts = {};
+    inspect["conflicts"].forEach(conflict => { conflicts[conflict["path"]] = conflict["mods"]; });
+
+    var lines = inspect["files"].map(file => {
+        var mods = conflicts[file["path"]];
+        var conflict = mods == undefined ? "" : `  [${mods[0]} wins over ${mods.slice(1).join(", ")}]`;
+
+        if (file["original_size"] == null) {
+            return `${file["path"]}: ${formatSize(file["size"])} (not in the game)${conflict}`;
+        }
+
+        var flag = file["size"] > file["original_size"] ? "  [larger than vanilla]" : "";
+        return `${file["path"]}: ${formatSize(file["size"])} / ${formatSize(file["original_size"])} vanilla${flag}${conflict}`;
+    });
+
+    var contents = lines.length == 0
+        ? "This mod doesn't replace any file."
+        : `${lines.length} file${lines.length > 1 ? 's' : ''}, ${formatSize(inspect["total_size"])} in total.\n\n${lines.join("\n")}`;
+
+    var validation = inspect["validation"];
+    var checked = validation["known"] + validation["unknown"];
+    if (checked > 0) {
+        var summary = `${validation["known"]} of ${checked} file${checked > 1 ? 's' : ''} exist in this version of the game.`;
+        if (inspect["likely_other_version"]) {
+            summary += " Most files of this mod don't, was it built for a different version of the game?";
+        }
+        contents = `${summary}\n\n${contents}`;
+    }
+
+    showLog({ "name": `Files of ${inspect["name"]}`, "contents": contents });
+
+    var readme = inspect["readme"];
+    if (readme == null) { return; }
+
+    var section = $("<div class='readme'></div>");
+    if (readme["markdown"]) {
+        section.html(renderMarkdown(readme["contents"]));
+    } else {
+        section.addClass("plain").text(readme["contents"]);
+    }
+
+    if (readme["truncated"]) {
+        section.append($("<p class='readme-truncated'></p>").text(`${readme["name"]} is too long to be shown entirely.`));
+    }
+
+    $("#log-contents").prepend(section);
+    $("#log-contents").scrollTop(0);
+}
+
+function escapeHtml(text) {
+    return text.replace(/&/g, "&amp;").replace(/</g, "&lt;").replace(/>/g, "&gt;").replace(/"/g, "&quot;").replace(/'/g, "&#39;");
+}
+
+function renderInline(line) {
+    return escapeHtml(line)
+        .replace(/`([^`]+)`/g, "<code>$1</code>")
+        .replace(/\*\*([^*]+)\*\*/g, "<b>$1</b>")
+        .replace(/\*([^*]+)\*/g, "<i>$1</i>")
+        // Links can't be followed from the applet, so only their text is kept
+        .replace(/\[([^\]]+)\]\([^)]*\)/g, "$1");
+}
+
+// Only headings, lists, code and emphasis are supported. The readme is escaped before anything else, so the only markup that can end up
+// in the page is the one added here.
+function renderMarkdown(text) {
+    var html = [];
+    var inList = false;
+    var inCode = false;
+
+    text.split(/\r?\n/).forEach(line => {
+        if (line.trim().startsWith("```")) {
+            html.push(inCode ? "</pre>" : "<pre>");
+            inCode = !inCode;
+            return;
+        }
+
+        if (inCode) {
+            html.push(`${escapeHtml(line)}\n`);
+            return;
+        }
+
+        var item = line.match(/^\s*[-*+]\s+(.*)$/);
+        if (item == null && inList) {
+            html.push("</ul>");
+            inList = false;
+        }
+
+        var heading = line.match(/^(#{1,6})\s+(.*)$/);
+        if (heading != null) {
+            var level = Math.min(heading[1].length + 3, 6);
+            html.push(`<h${level}>${renderInline(heading[2])}</h${level}>`);
+        } else if (item != null) {
+            if (!inList) {
+                html.push("<ul>");
+                inList = true;
+            }
+            html.push(`<li>${renderInline(item[1])}</li>`);
+        } else if (line.trim() != "") {
+            html.push(`<p>${renderInline(line)}</p>`);
+        }
+    });
+
+    if (inList) { html.push("</ul>"); }
+    if (inCode) { html.push("</pre>"); }
+
+    return html.join("");
+}
+
+function showPriorityResolution(resolution) {
+    modsById[resolution["id"]]["priority"] = resolution["priority"];
+
+    var conflicts = resolution["conflicts"];
+    if (conflicts.length == 0) {
+        alert(`${modsById[resolution["id"]]["display_name"]} does not conflict with any enabled mod.`);
+        return;
+    }
+
+    var lines = conflicts.map(conflict => `${conflict["path"]}: ${conflict["mods"][0]} wins over ${conflict["mods"].slice(1).join(", ")}`);
+    alert(`Takes effect on the next boot:\n\n${lines.join("\n")}`);
+}
+
+function requestLog(which) {
+    window.nx.sendMessage(JSON.stringify({
+        "ViewLog": {
+            "which": which
+        }
+    }));
+}
+
+function showLog(log) {
+    $("#log-name").text(log["name"]);
+    // Use text() so that the log isn't interpreted as HTML
+    $("#log-contents").text(log["contents"]);
+    $("#log-viewer").css("display", "flex");
+    $("#log-contents").scrollTop($("#log-contents")[0].scrollHeight);
+    currentState = LOG_VIEW;
+}
+
+function hideLog() {
+    $("#log-viewer").css("display", "none");
+    $("#log-contents").text("");
+    currentState = SUB_MENU;
+}
+
+function toggleBypass() {
+    window.nx.sendMessage(JSON.stringify({
+        "SetBypass": {
+            "state": !bypassReplacements
+        }
+    }));
+}
+
+// Only files loaded after toggling are affected, the ones already in memory stay as they are
+function showBypass(state) {
+    bypassReplacements = state;
+    $("#bypass-btn").text(state ? "Stop bypassing replacements" : "Bypass all replacements");
+}
+
+function requestChanges() {
+    window.nx.sendMessage(JSON.stringify("GetChanges"));
+}
+
+function requestStaleEntries() {
+    window.nx.sendMessage(JSON.stringify("GetStaleEntries"));
+}
+
+// Entries for mods that were deleted, they don't load anything so removing them doesn't need a reboot
+function confirmCleanUp(count) {
+    if (count == 0) {
+        alert("Every mod enabled in this workspace is installed, there is nothing to clean up.");
+        return;
+    }
+
+    if (!confirm(`${count} mod${count > 1 ? 's' : ''} enabled in this workspace ${count > 1 ? 'are' : 'is'} no longer installed. Remove ${count > 1 ? 'them' : 'it'} from the preset?`)) { return; }
+    window.nx.sendMessage(JSON.stringify("CleanUpPreset"));
+}
+
+// Both close ARCadia without saving, a backup of the configuration is made before anything is changed
+function resetConfig() {
+    if (!confirm("Reset the whole configuration to the defaults? Every workspace and preset will be lost, but a backup will be made first.")) { return; }
+    window.nx.sendMessage(JSON.stringify("ResetConfig"));
+}
+
+function clearPreset() {
+    if (!confirm("Disable every mod of this workspace? Your other changes in this menu will be discarded, but a backup will be made first.")) { return; }
+    window.nx.sendMessage(JSON.stringify("ClearPreset"));
+}
+
+function showChanges(changes) {
+    var contents;
+
+    if (changes["first_run"]) {
+        contents = "There is no record of a previous boot yet, changes will be listed starting from the next one.";
+    } else {
+        var sections = [["Added", changes["added"]], ["Removed", changes["removed"]], ["Modified", changes["modified"]]]
+            .filter(section => section[1].length > 0)
+            .map(section => `${section[0]} (${section[1].length}):\n${section[1].map(name => `  ${name}`).join("\n")}`);
+
+        contents = sections.length == 0 ? "No mods changed since the last boot." : sections.join("\n\n");
+    }
+
+    showLog({ "name": "Changes since the last boot", "contents": contents });
+}
+
+function requestCollections() {
+    window.nx.sendMessage(JSON.stringify("GetCollections"));
+}
+
+function showCollections(collections) {
+    var list = $("#collection-list");
+    list.empty();
+
+    var names = collections.map(collection => collection["name"]);
+    var ordered = [];
+
+    // List the nested collections right below their parent
+    function visit(parent, depth) {
+        collections
+            .filter(collection => collection["parent"] == parent || (parent == null && !names.includes(collection["parent"])))
+            .filter(collection => !ordered.some(entry => entry[0] == collection))
+            .forEach(collection => {
+                ordered.push([collection, depth]);
+                visit(collection["name"], depth + 1);
+            });
+    }
+    visit(null, 0);
+
+    var create = $("<button class='submenu-btn collection-btn'>New collection</button>");
+    create.on("click", () => createCollection());
+    list.append(create);
+
+    ordered.forEach(entry => {
+        var collection = entry[0];
+        var row = $("<div class='collection-row'></div>");
+        var label = $("<h3></h3>");
+        var text = `${collection["name"]} - ${collection["all_ids"].length} mods`;
+
+        if (collection["missing"] > 0) {
+            text += `, ${collection["missing"]} missing`;
+        }
+
+        // Use text() so that collection names aren't interpreted as HTML
+        label.text(text);
+        row.css("margin-left", `${entry[1] * 40}px`);
+        row.append(label);
+
+        [
+            ["Enable", () => setCollectionState(collection, true)],
+            ["Disable", () => setCollectionState(collection, false)],
+            ["Add shown mods", () => setCollectionMods(collection, true)],
+            ["Remove shown mods", () => setCollectionMods(collection, false)],
+            ["Delete", () => deleteCollection(collection)],
+        ].forEach(action => {
+            var button = $("<button class='submenu-btn'></button>");
+            button.text(action[0]);
+            button.on("click", action[1]);
+            row.append(button);
+        });
+
+        list.append(row);
+    });
+
+    if (currentState != COLLECTION_VIEW) {
+        $("#collection-viewer").css("display", "flex");
+        create.focus();
+        currentState = COLLECTION_VIEW;
+    }
+}
+
+function hideCollections() {
+    $("#collection-viewer").css("display", "none");
+    $("#collection-list").empty();
+    currentState = SUB_MENU;
+}
+
+function createCollection() {
+    var name = prompt("Name of the new collection:");
+    if (name == null || name.trim() == "") { return; }
+
+    var parent = prompt("Name of the collection to nest it in (leave empty for none):");
+
+    window.nx.sendMessage(JSON.stringify({
+        "CreateCollection": {
+            "name": name.trim(),
+            "parent": parent == null || parent.trim() == "" ? null : parent.trim()
+        }
+    }));
+}
+
+function deleteCollection(collection) {
+    if (!confirm(`Delete the collection ${collection["name"]}? The mods in it will not be removed.`)) { return; }
+
+    window.nx.sendMessage(JSON.stringify({
+        "DeleteCollection": {
+            "name": collection["name"]
+        }
+    }));
+}
+
+function setCollectionState(collection, state) {
+    var ids = collection["all_ids"];
+    if (ids.length <= 0) { return; }
+
+    for (var i = 0; i < ids.length; i++) {
+        modsById[ids[i]]["is_disabled"] = !state;
+    }
+    refreshCurrentMods();
+    window.nx.sendMessage(JSON.stringify({
+        "ChangeMods": {
+            "state": state,
+            "ids": ids
+        }
+    }));
+}
+
+// Adds or removes the mods currently shown in the mod list, so the category filters and search can be used to pick them
+function setCollectionMods(collection, state) {
+    updateCurrentModsWCategories();
+    if (currentMods.length <= 0) { return; }
+
+    window.nx.sendMessage(JSON.stringify({
+        "SetCollectionMods": {
+            "name": collection["name"],
+            "ids": currentMods,
+            "state": state
+        }
+    }));
+}
+
+function requestWorkspaces() {
+    window.nx.sendMessage(JSON.stringify("GetWorkspaces"));
+}
+
+function showWorkspaces(workspaces) {
+    var list = $("#workspace-list");
+    list.empty();
+
+    workspaces.forEach(workspace => {
+        var button = $("<button class='submenu-btn workspace-btn'></button>");
+        var label = `${workspace["name"]}${workspace["active"] ? " (active)" : ""} - ${workspace["enabled"]} enabled mods`;
+
+        if (workspace["stale"] > 0) {
+            label += `, ${workspace["stale"]} missing`;
+        }
+
+        // Use text() so that workspace names aren't interpreted as HTML
+        button.text(label);
+        button.on("click", () => switchWorkspace(workspace["name"]));
+        list.append(button);
+    });
+
+    $("#workspace-viewer").css("display", "flex");
+    list.children().first().focus();
+    currentState = WORKSPACE_VIEW;
+}
+
+function hideWorkspaces() {
+    $("#workspace-viewer").css("display", "none");
+    $("#workspace-list").empty();
+    currentState = SUB_MENU;
+}
+
+function switchWorkspace(name) {
+    window.nx.sendMessage(JSON.stringify({
+        "SwitchWorkspace": {
+            "name": name
+        }
+    }));
+    window.location.href = "http://localhost/quit";
+}
+
 function exit() {
     window.nx.sendMessage(JSON.stringify("Closure"));
     window.location.href = "http://localhost/quit";
@@ -342,27 +961,37 @@ function updateSort() {
 
     if (sortType == "alphabetical") {
         currentMods = JSON.parse(JSON.stringify(currentMods)).sort((a, b) => {
-            if (mods[a]["folder_name"] < mods[b]["folder_name"]) { return -1; }
-            if (mods[a]["folder_name"] > mods[b]["folder_name"]) { return 1; }
+            if (modsById[a]["folder_name"] < modsById[b]["folder_name"]) { return -1; }
+            if (modsById[a]["folder_name"] > modsById[b]["folder_name"]) { return 1; }
             return 0;
         });
     } else if (sortType == "enabled") {
         currentMods = JSON.parse(JSON.stringify(currentMods)).sort((a, b) => {
-            if (!mods[a]["is_disabled"] != !mods[b]["is_disabled"]) {
-                return mods[b]["is_disabled"] ? -1 : 1;
+            if (!modsById[a]["is_disabled"] != !modsById[b]["is_disabled"]) {
+                return modsById[b]["is_disabled"] ? -1 : 1;
             } else {
-                if (mods[a]["folder_name"] < mods[b]["folder_name"]) { return -1; }
-                if (mods[a]["folder_name"] > mods[b]["folder_name"]) { return 1; }
+                if (modsById[a]["folder_name"] < modsById[b]["folder_name"]) { return -1; }
+                if (modsById[a]["folder_name"] > modsById[b]["folder_name"]) { return 1; }
             }
             return 0;
         });
+    } else if (sortType == "author") {
+        currentMods = JSON.parse(JSON.stringify(currentMods)).sort((a, b) => {
+            // Mods without an author go last
+            var authorA = (modsById[a]["author"] || "\uffff").toLowerCase();
+            var authorB = (modsById[b]["author"] || "\uffff").toLowerCase();
+            if (authorA != authorB) { return authorA < authorB ? -1 : 1; }
+            if (modsById[a]["folder_name"] < modsById[b]["folder_name"]) { return -1; }
+            if (modsById[a]["folder_name"] > modsById[b]["folder_name"]) { return 1; }
+            return 0;
+        });
     } else if (sortType == "disabled") {
         currentMods = JSON.parse(JSON.stringify(currentMods)).sort((a, b) => {
-            if (!mods[a]["is_disabled"] != !mods[b]["is_disabled"]) {
-                return mods[b]["is_disabled"] ? 1 : -1;
+            if (!modsById[a]["is_disabled"] != !modsById[b]["is_disabled"]) {
+                return modsById[b]["is_disabled"] ? 1 : -1;
             } else {
-                if (mods[a]["folder_name"] < mods[b]["folder_name"]) { return -1; }
-                if (mods[a]["folder_name"] > mods[b]["folder_name"]) { return 1; }
+                if (modsById[a]["folder_name"] < modsById[b]["folder_name"]) { return -1; }
+                if (modsById[a]["folder_name"] > modsById[b]["folder_name"]) { return 1; }
             }
             return 0;
         });
@@ -375,10 +1004,83 @@ function updateSort() {
 
 window.nx.addEventListener("message", (e) => {
     var info = JSON.parse(e.data);
+    if ("log" in info) {
+        showLog(info["log"]);
+        return;
+    }
+
+    if ("manifest" in info) {
+        showManifest(info["manifest"]);
+        return;
+    }
+
+    if ("inspect" in info) {
+        showInspect(info["inspect"]);
+        return;
+    }
+
+    if ("stale_entries" in info) {
+        confirmCleanUp(info["stale_entries"]);
+        return;
+    }
+
+    if ("preset_cleaned_up" in info) {
+        alert(`Removed ${info["preset_cleaned_up"]} stale entries from the preset.`);
+        return;
+    }
+
+    if ("bypass_replacements" in info) {
+        showBypass(info["bypass_replacements"]);
+        return;
+    }
+
+    if ("changes" in info) {
+        showChanges(info["changes"]);
+        return;
+    }
+
+    if ("collections" in info) {
+        showCollections(info["collections"]);
+        return;
+    }
+
+    if ("workspaces" in info) {
+        showWorkspaces(info["workspaces"]);
+        return;
+    }
+
+    if ("search" in info) {
+        // An answer to an older search is outdated already
+        if (info["search"]["query"] == searchQuery()) {
+            searchMatches = info["search"]["query"].length > 0 ? new Set(info["search"]["ids"]) : null;
+
+            if (currentState == MOD_MENU) {
+                showSearchedMods();
+            }
+        }
+        return;
+    }
+
+    if ("reorder" in info) {
+        showOrder(info["reorder"]);
+    }
+    if ("priority" in info) {
+        showPriorityResolution(info["priority"]);
+        return;
+    }
+
+    if ("mod_size_progress" in info) {
+        modSizeProgress = info["mod_size_progress"];
+        showModSize();
+        return;
+    }
+
     if (!("mod_size" in info))
         return;
     
     modSize = info["mod_size"];
+    modSizeProgress = null;
+    showModSize();
 });
 
 window.addEventListener("DOMContentLoaded", (e) => {
@@ -391,11 +1093,15 @@ window.addEventListener("DOMContentLoaded", (e) => {
                 "version": `${i + 3}.${i + 2}.${i}`,
                 "is_disabled": true,
                 "category": categories[i % categories.length],
+                "conflicts": [],
+                "dependencies": [],
+                "missing_dependencies": [],
                 "author": `Coolsonickirby`,
                 "description": `Hey guys! This is one of the coolest mods ever made! Mod #${i}. Hey guys! This is one of the coolest mods ever made! Mod #${i}. Hey guys! This is one of the coolest mods ever made! Mod #${i}. Hey guys! This is one of the coolest mods ever made! Mod #${i}. Hey guys! This is one of the coolest mods ever made! Mod #${i}.`,
             });
         }
 
+        indexMods();
         currentMods = mods.map(x => x["id"]);
         refreshCurrentMods();
     } else {
@@ -405,7 +1111,10 @@ window.addEventListener("DOMContentLoaded", (e) => {
             url: "mods.json",
             success: (data) => {
                 mods = data["entries"];
+                indexMods();
                 $("#workspace").html(data["workspace"]);
+                showBypass(data["bypass_replacements"]);
+                $("#sdStatus").toggle(!data["sd_available"]);
                 currentMods = mods.map(x => x["id"]);
                 refreshCurrentMods();
             }
@@ -434,9 +1143,19 @@ window.addEventListener("DOMContentLoaded", (e) => {
             }
         });
 
-        window.nx.footer.setAssign("X", "", () => {});
+        window.nx.footer.setAssign("X", "", () => {
+            if (currentState == MOD_MENU) {
+                editMod();
+            }
+        });
         window.nx.footer.setAssign("B", "", () => {
-            if (currentState == SUB_MENU) {
+            if (currentState == LOG_VIEW) {
+                hideLog();
+            } else if (currentState == WORKSPACE_VIEW) {
+                hideWorkspaces();
+            } else if (currentState == COLLECTION_VIEW) {
+                hideCollections();
+            } else if (currentState == SUB_MENU) {
                 showModMenu();
             } else {
                 exit();
@@ -447,6 +1166,16 @@ window.addEventListener("DOMContentLoaded", (e) => {
                 showSubMenu();
             }
         });
+        window.nx.footer.setAssign("ZL", "", () => {
+            if (currentState == MOD_MENU) {
+                exportManifest();
+            }
+        });
+        window.nx.footer.setAssign("ZR", "", () => {
+            if (currentState == MOD_MENU) {
+                inspectMod();
+            }
+        });
         window.nx.footer.setAssign("L", "", () => {
             if (currentState == MOD_MENU) {
                 prevPage();
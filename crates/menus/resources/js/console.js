@@ -0,0 +1,56 @@
+function exit() {
+    window.nx.sendMessage(JSON.stringify("Closure"));
+    window.location.href = "http://localhost/quit";
+}
+
+function print(text) {
+    var output = $("#console-output");
+    // Use text() so that the output isn't interpreted as HTML
+    output.text(output.text() + text + "\n");
+    output.scrollTop(output[0].scrollHeight);
+}
+
+function submit() {
+    var input = $("#console-input");
+    var line = input.val().trim();
+    input.val("");
+
+    if (line == "") { return; }
+
+    print(`> ${line}`);
+    window.nx.sendMessage(JSON.stringify({
+        "Command": {
+            "line": line
+        }
+    }));
+}
+
+window.addEventListener("DOMContentLoaded", (e) => {
+    $("#console-input").on("keydown", (e) => {
+        if (e.keyCode == 13) {
+            submit();
+        }
+    });
+
+    // The software keyboard submits the input on close, which doesn't always send an enter key
+    $("#console-input").on("change", () => submit());
+
+    window.nx.addEventListener("message", (e) => {
+        var info = JSON.parse(e.data);
+
+        if ("output" in info) {
+            print(info["output"]);
+        }
+    });
+
+    window.nx.footer.setAssign("A", "", () => {
+        $("#console-input").focus();
+    });
+    window.nx.footer.setAssign("B", "", () => {
+        exit();
+    });
+    window.nx.footer.setAssign("X", "", () => {});
+    window.nx.footer.setAssign("Y", "", () => {
+        $("#console-output").text("");
+    });
+});
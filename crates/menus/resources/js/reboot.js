@@ -0,0 +1,57 @@
+var AButtonHeld = false;
+var secondsLeft = 5;
+
+window.addEventListener("DOMContentLoaded", (e) => {
+    var buttons = document.querySelectorAll('button');
+
+    [].forEach.call(buttons, function(btn) {
+        btn.addEventListener("focus", () => {
+            btn.classList.add("is-focused");
+        });
+
+        btn.addEventListener("focusout", () => {
+            btn.classList.remove("is-focused");
+        });
+    });
+
+    // Listen to the keydown event and prevent the default
+    window.addEventListener('keydown', function(e) {
+        if (e.keyCode == UP) {
+            var target = document.querySelector(".is-focused").previousElementSibling;
+            if (target != undefined) {
+                getCurrentActiveContainer()[0].scrollTop = target.offsetTop + 50;
+                target.focus();
+            }
+        } else if (e.keyCode == DOWN) {
+            var target = document.querySelector(".is-focused").nextElementSibling;
+            if (target != undefined) {
+                getCurrentActiveContainer()[0].scrollTop = target.offsetTop - 50;
+                target.focus();
+            }
+        }
+    });
+
+    window.nx.footer.setAssign("A", "", () => {
+        $(".is-focused").last().click();
+    });
+    window.nx.footer.setAssign("B", "", () => {
+        window.location.href = "http://localhost/later";
+    });
+    window.nx.footer.setAssign("X", "", () => {});
+    window.nx.footer.setAssign("Y", "", () => {});
+
+    if ($(".is-focused").length <= 0) {
+        // Focus the cancel button so a stray press doesn't reboot right away
+        $("#list").find("button").get(1).focus();
+    }
+
+    var countdown = setInterval(() => {
+        secondsLeft -= 1;
+        $("#countdown").text(secondsLeft);
+
+        if (secondsLeft <= 0) {
+            clearInterval(countdown);
+            window.location.href = "http://localhost/reboot";
+        }
+    }, 1000);
+});
\ No newline at end of file
@@ -4,6 +4,12 @@ const WORKSPACE_CONTROL = "&#xe000 Select Option";
 var workspaces = [];
 var selected_workspace = 0;
 var active_workspace = "";
+// Workspace each workspace inherits its mods from, keyed by the inheriting one
+var parents = {};
+// Settings each workspace sets for itself, keyed by workspace and then by setting
+var overrides = {};
+// Settings a workspace is allowed to set
+var overridable = [];
 
 window.addEventListener("DOMContentLoaded", (e) => {
     if (!isNx) {
@@ -20,6 +26,9 @@ window.addEventListener("DOMContentLoaded", (e) => {
             success: (data) => {
                 workspaces = data["workspaces"];
                 active_workspace = data["active_workspace"];
+                parents = data["parents"];
+                overrides = data["overrides"];
+                overridable = data["overridable"];
                 setupWorkspaces();
             }
         });
@@ -222,6 +231,8 @@ function renameWorkspace() {
     targetName = res;
 
     workspaces[selected_workspace] = targetName;
+    relinkParents(sourceName, targetName);
+    relinkOverrides(sourceName, targetName);
 
     $("#workspace").html(workspaces[selected_workspace]);
 
@@ -253,6 +264,10 @@ function duplicateWorkspace() {
 
     workspaces.push(targetName);
 
+    if (overrides[sourceName] != undefined) {
+        overrides[targetName] = Object.assign({}, overrides[sourceName]);
+    }
+
     if (isNx) {
         window.nx.sendMessage(JSON.stringify({
             "Duplicate": {
@@ -278,12 +293,117 @@ function removeWorkspace() {
                 }));
             }
 
+            relinkParents(workspaces[selected_workspace], undefined);
+            relinkOverrides(workspaces[selected_workspace], undefined);
             workspaces.splice(selected_workspace, 1);
             changeDivFromTo('workspaceOption', 'workspaces', 0);
         }
     }
 }
 
+// Mirrors what is done on the Rust side when a workspace is renamed to `to`, or removed if it is undefined. The workspaces inheriting
+// from a removed one inherit from its own parent instead.
+function relinkParents(from, to) {
+    var ownParent = parents[from];
+    delete parents[from];
+
+    if (to != undefined && ownParent != undefined) {
+        parents[to] = ownParent;
+    }
+
+    var replacement = to != undefined ? to : ownParent;
+
+    for (var child in parents) {
+        if (parents[child] == from) {
+            if (replacement == undefined) {
+                delete parents[child];
+            } else {
+                parents[child] = replacement;
+            }
+        }
+    }
+}
+
+// Moves the settings of a workspace renamed to `to`, or drops them if it is undefined
+function relinkOverrides(from, to) {
+    if (overrides[from] != undefined && to != undefined) {
+        overrides[to] = overrides[from];
+    }
+
+    delete overrides[from];
+}
+
+// Settings are entered as key=value, and a key without a value has the workspace use the global setting again
+function setOverride() {
+    var name = workspaces[selected_workspace];
+    var current = Object.entries(overrides[name] || {}).map(([key, value]) => `${key}=${value}`).join(", ");
+    var res = prompt(`Setting to use in ${name}, as key=value. Leave the value out to use the global setting again.\nCurrently set: ${current || "nothing"}\nAvailable: ${overridable.join(", ")}`, "");
+    if (res == null || res == undefined || res.trim() == "") { return; }
+
+    var separator = res.indexOf("=");
+    var key = (separator < 0 ? res : res.substring(0, separator)).trim();
+    var value = separator < 0 ? "" : res.substring(separator + 1).trim();
+
+    if (!overridable.includes(key)) {
+        alert(`${key} can't be set per workspace!`);
+        return;
+    }
+
+    overrides[name] = overrides[name] || {};
+
+    if (value == "") {
+        delete overrides[name][key];
+    } else {
+        overrides[name][key] = value;
+    }
+
+    if (isNx) {
+        window.nx.sendMessage(JSON.stringify({
+            "SetOverride": {
+                "name": name,
+                "key": key,
+                "value": value == "" ? null : value
+            }
+        }));
+    }
+}
+
+function setParent() {
+    var name = workspaces[selected_workspace];
+    var res = prompt(`Workspace to inherit the mods from, or nothing to stop inheriting.\nThe mods enabled or disabled in ${name} take precedence.`, parents[name] || "");
+    if (res == null || res == undefined) { return; }
+
+    var parent = res.trim() == "" ? null : res.trim();
+
+    if (parent != null) {
+        if (!workspaces.includes(parent)) {
+            alert(`There is no workspace named ${parent}!`);
+            return;
+        }
+
+        // Walk up from the new parent, finding the workspace on the way means it would inherit from itself
+        for (var current = parent; current != undefined; current = parents[current]) {
+            if (current == name) {
+                alert(`${parent} already inherits from ${name}!`);
+                return;
+            }
+        }
+
+        parents[name] = parent;
+    } else {
+        delete parents[name];
+    }
+
+    if (isNx) {
+        window.nx.sendMessage(JSON.stringify({
+            "SetParent": {
+                "name": name,
+                "parent": parent
+            }
+        }));
+    }
+}
+
 function createWorkspace() {
     var res = prompt("Enter new workspace name");
     if (res == null || res == undefined) { return; }
@@ -1,10 +1,20 @@
 // #![feature(proc_macro_hygiene)]
 
-use std::{collections::HashSet, path::Path};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{Read, Seek, SeekFrom},
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{self, Receiver, Sender},
+        RwLock,
+    },
+    thread::JoinHandle,
+    time::Duration,
+};
 
-use log::{debug, error};
+use log::{debug, error, warn};
 use serde::{Deserialize, Serialize};
-use skyline_web::Webpage;
+use skyline_web::{WebSession, Webpage};
 use smash_arc::Hash40;
 
 use crate::{config, utils};
@@ -13,11 +23,17 @@ use crate::{config, utils};
 pub struct Information {
     entries: Vec<Entry>,
     workspace: String,
+    bypass_replacements: bool,
+    /// False while the SD card can't be read, in which case the game loads its own files
+    sd_available: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Default)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
 pub struct Entry {
-    id: Option<u32>,
+    /// Derived from the full path of the folder, so it stays the same when other mods are added or removed. An `id` written in info.toml
+    /// is never read, as several mods copied from the same template would end up sharing it.
+    #[serde(skip_deserializing)]
+    id: Option<u64>,
     folder_name: Option<String>,
     is_disabled: Option<bool>,
     display_name: Option<String>,
@@ -25,37 +41,806 @@ pub struct Entry {
     version: Option<String>,
     description: Option<String>,
     category: Option<String>,
+    /// Mods with a higher priority win file conflicts against the others
+    priority: Option<i32>,
+    /// Directory of the game the files of the mod are laid out relative to, instead of mirroring the full paths
+    base: Option<String>,
+    /// Optional parts of the mod that can be turned off on their own, declared as `[[group]]` tables
+    #[serde(rename = "group", default)]
+    groups: Vec<FileGroup>,
+    /// Folder names of the mods that can't be enabled at the same time as this one
+    #[serde(default)]
+    conflicts_with: Vec<String>,
+    /// Ids of the mods that can't be enabled at the same time as this one, whichever of the two declared it
+    #[serde(skip_deserializing)]
+    conflicts: Vec<u64>,
+    /// Folder names of the mods that have to be enabled for this one to work
+    #[serde(default)]
+    depends_on: Vec<String>,
+    /// Ids of the installed mods this one depends on
+    #[serde(skip_deserializing)]
+    dependencies: Vec<u64>,
+    /// Mods this one depends on that aren't installed
+    #[serde(skip_deserializing)]
+    missing_dependencies: Vec<String>,
+    /// Full path to the mod's root, which can be outside of the mods directory for mods extracted from a modpack
+    #[serde(skip)]
+    path: PathBuf,
+}
+
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct FileGroup {
+    name: String,
+    /// Patterns of the files in the group, relative to the root of the mod
+    #[serde(default)]
+    files: Vec<String>,
+    /// Filled in from the workspace, not read from info.toml
+    #[serde(skip_deserializing)]
+    enabled: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub enum ArcadiaMessage {
-    ToggleMod { id: usize, state: bool },
+    ToggleMod { id: u64, state: bool },
     ChangeAll { state: bool },
-    ChangeIndexes { state: bool, indexes: Vec<usize> },
+    ChangeMods { state: bool, ids: Vec<u64> },
     DebugPrint { message: String },
     GetModSize,
+    ViewLog { which: LogFile },
+    /// Mods whose name, folder or author contains the query
+    SearchMods { query: String },
+    SetPriority { id: u64, priority: i32 },
+    /// Enabled mods from the one that wins conflicts to the one that loses them
+    ReorderMods { ids: Vec<u64> },
+    ToggleGroup { id: u64, group: String, state: bool },
+    GetWorkspaces,
+    SwitchWorkspace { name: String },
+    GetChanges,
+    InspectMod { id: u64 },
+    ExportModManifest { id: u64 },
+    SetBypass { state: bool },
+    GetStaleEntries,
+    CleanUpPreset,
+    ResetConfig,
+    ClearPreset,
+    GetCollections,
+    CreateCollection { name: String, parent: Option<String> },
+    DeleteCollection { name: String },
+    SetCollectionMods { name: String, ids: Vec<u64>, state: bool },
     Closure,
 }
 
-pub fn get_mods(presets: &HashSet<Hash40>) -> Vec<Entry> {
-    let mut id: u32 = 0;
+#[derive(Debug, Serialize)]
+struct CollectionSummary {
+    name: String,
+    parent: Option<String>,
+    /// Installed mods directly in the collection
+    ids: Vec<u64>,
+    /// Installed mods in the collection and the ones nested in it, which are toggled when toggling the whole collection
+    all_ids: Vec<u64>,
+    /// Mods of the collection that are no longer installed
+    missing: usize,
+}
+
+fn get_collection_summaries(entries: &[Entry]) -> Vec<CollectionSummary> {
+    let collections = ::config::collections::get_list();
+
+    let ids: HashMap<&str, u64> = entries.iter().filter_map(|entry| Some((entry.folder_name.as_deref()?, entry.id?))).collect();
+
+    let installed_ids = |mods: &HashSet<String>| {
+        let mut installed: Vec<u64> = mods.iter().filter_map(|folder_name| ids.get(folder_name.as_str()).copied()).collect();
+        installed.sort_unstable();
+        installed
+    };
+
+    let mut summaries: Vec<CollectionSummary> = collections
+        .iter()
+        .map(|(name, collection)| {
+            let direct = installed_ids(&collection.mods);
+
+            CollectionSummary {
+                name: name.clone(),
+                parent: collection.parent.clone(),
+                missing: collection.mods.len() - direct.len(),
+                ids: direct,
+                all_ids: installed_ids(&::config::collections::get_mods_recursive(&collections, name)),
+            }
+        })
+        .collect();
+
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    summaries
+}
+
+/// Mods that were added, removed or modified on the SD card between the previous boot and this one
+#[derive(Debug, Default, Serialize)]
+struct ModChanges {
+    /// No snapshot was taken on the previous boot, so there is nothing to compare against
+    first_run: bool,
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+fn get_mod_changes() -> ModChanges {
+    let (previous, current) = match (::config::snapshots::get_previous(), ::config::snapshots::get_current()) {
+        (Some(previous), Some(current)) => (previous, current),
+        _ => {
+            return ModChanges {
+                first_run: true,
+                ..Default::default()
+            }
+        },
+    };
+
+    // Folder names are enough to tell the mods apart on the page
+    let name = |path: &String| Path::new(path).file_name().and_then(|name| name.to_str()).unwrap_or(path).to_string();
+
+    let mut changes = ModChanges::default();
+
+    for (path, modified) in current.iter() {
+        match previous.get(path) {
+            None => changes.added.push(name(path)),
+            Some(previous_modified) if previous_modified != modified => changes.modified.push(name(path)),
+            _ => {},
+        }
+    }
+
+    changes.removed = previous.keys().filter(|path| !current.contains_key(*path)).map(name).collect();
+
+    changes.added.sort();
+    changes.removed.sort();
+    changes.modified.sort();
+    changes
+}
+
+/// Files written to the SD card that can be viewed from ARCadia
+#[derive(Debug, Deserialize)]
+pub enum LogFile {
+    Latest,
+    Conflicts,
+    ConflictReport,
+}
+
+#[derive(Debug, Serialize)]
+struct LogContents {
+    name: String,
+    contents: String,
+}
+
+/// Maximum amount of bytes sent to the page when viewing a log, so that huge files don't exhaust the browser's memory
+const MAX_LOG_SIZE: u64 = 0x10000;
+
+impl LogFile {
+    fn path(&self) -> Option<PathBuf> {
+        match self {
+            // Log files are named after the time they were created at, so the latest one is the most recently modified
+            LogFile::Latest => std::fs::read_dir(utils::paths::logs())
+                .ok()?
+                .filter_map(|entry| entry.ok())
+                .filter(|entry| entry.path().is_file())
+                .max_by_key(|entry| entry.metadata().and_then(|metadata| metadata.modified()).ok())
+                .map(|entry| entry.path()),
+            LogFile::Conflicts => Some(PathBuf::from("sd:/ultimate/arcropolis/conflicts.json")),
+            LogFile::ConflictReport => Some(PathBuf::from("sd:/ultimate/arcropolis/conflict_report.json")),
+        }
+    }
+}
+
+/// Reads the end of the file, up to [`MAX_LOG_SIZE`] bytes
+fn read_log_tail(path: &Path) -> std::io::Result<String> {
+    let mut file = std::fs::File::open(path)?;
+    let len = file.metadata()?.len();
+
+    let truncated = len > MAX_LOG_SIZE;
+
+    if truncated {
+        file.seek(SeekFrom::Start(len - MAX_LOG_SIZE))?;
+    }
+
+    let mut bytes = Vec::with_capacity(len.min(MAX_LOG_SIZE) as usize);
+    file.take(MAX_LOG_SIZE).read_to_end(&mut bytes)?;
+
+    let contents = String::from_utf8_lossy(&bytes);
+
+    if truncated {
+        // Skip the partial line we most likely started in the middle of
+        let start = contents.find('\n').map_or(0, |idx| idx + 1);
+        Ok(format!("[...]\n{}", &contents[start..]))
+    } else {
+        Ok(contents.into_owned())
+    }
+}
+
+fn view_log(which: &LogFile) -> LogContents {
+    let path = match which.path() {
+        Some(path) => path,
+        None => {
+            return LogContents {
+                name: format!("{:?}", which),
+                contents: String::from("No log file could be found."),
+            }
+        },
+    };
+
+    let contents = match read_log_tail(&path) {
+        Ok(contents) if contents.is_empty() => String::from("This file is empty."),
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => String::from("This file does not exist yet."),
+        Err(e) => {
+            warn!("Failed to read '{}' for the log viewer. Reason: {}", path.display(), e);
+            format!("Failed to read this file: {}", e)
+        },
+    };
+
+    LogContents {
+        name: path.display().to_string(),
+        contents,
+    }
+}
+
+/// How a file provided by several enabled mods is resolved, with the mods ordered from winner to losers
+#[derive(Debug, Serialize)]
+struct ConflictResolution {
+    path: String,
+    mods: Vec<String>,
+}
+
+/// Maximum amount of conflicts sent back after a priority change, the complete list can be found in the conflict report
+const MAX_RESOLUTIONS: usize = 100;
+
+/// Collects the paths of every file in a mod relative to its root. Files sitting at the root of the mod (info.toml, preview, plugins...)
+/// are not part of the game's filesystem and thus can't conflict, so they are skipped.
+fn collect_mod_files(root: &Path, current: &Path, files: &mut Vec<String>) {
+    let entries = match std::fs::read_dir(current) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+
+    for entry in entries.filter_map(|entry| entry.ok()) {
+        let path = entry.path();
+
+        if entry.file_name().to_str().map_or(true, |name| name.starts_with('.')) {
+            continue;
+        }
+
+        if path.is_dir() {
+            collect_mod_files(root, &path, files);
+        } else if current != root {
+            if let Some(local) = path.strip_prefix(root).ok().and_then(|local| local.to_str()) {
+                files.push(local.to_lowercase().replace('\\', "/"));
+            }
+        }
+    }
+}
+
+/// Gets the path in the game of a file of a mod, which is relative to the base directory of the mod if it declares one
+fn game_path(base: Option<&str>, local: &str) -> String {
+    match base.map(|base| base.trim_matches('/').to_lowercase()).filter(|base| !base.is_empty()) {
+        Some(base) => format!("{}/{}", base, local),
+        None => local.to_string(),
+    }
+}
+
+/// Gets the path in the game of a file that replaces a file of the game whole, matched the way discovery does it: regional variants stand
+/// for the same file, and files for another region than `region` are never loaded. None for those and for patches, which are merged
+/// with the files of the other mods rather than conflicting with them.
+fn replaced_path(base: Option<&str>, local: &str, region: &str) -> Option<String> {
+    let name = local.rsplit('/').next().unwrap_or(local);
+
+    // Entries of bgm_property.bin are merged like patches
+    if is_patch_file(local) || name == "bgm_property.bin" || ::config::is_out_of_region(name, region) {
+        return None;
+    }
+
+    Some(::config::strip_regional_suffix(&game_path(base, local)))
+}
+
+/// Finds the files that the mod at `index` shares with the other enabled mods and sorts out which mod wins each of them
+fn resolve_conflicts(entries: &[Entry], presets: &HashSet<Hash40>, index: usize) -> Vec<ConflictResolution> {
+    let mut enabled: Vec<&Entry> = entries
+        .iter()
+        .filter(|entry| entry.path.to_str().map_or(false, |path| presets.contains(&Hash40::from(path))))
+        .collect();
+
+    // Same order as discovery, where the first mod to provide a file wins
+    ::config::conflict_strategy().sort_mods(&mut enabled, |entry| entry.folder_name.clone().unwrap_or_default(), |entry| entry.priority.unwrap_or(0));
+    ::config::sort_mods_by_source(&mut enabled, |entry| entry.path.to_string_lossy().to_string(), ::config::rom_overrides_sd());
+
+    let mut own_files = Vec::new();
+    collect_mod_files(&entries[index].path, &entries[index].path, &mut own_files);
+
+    let region = ::config::region().to_string();
+    let base = entries[index].base.as_deref();
+    let mut providers: HashMap<String, Vec<String>> =
+        own_files.iter().filter_map(|file| Some((replaced_path(base, file, &region)?, Vec::new()))).collect();
+
+    for entry in enabled {
+        let mut files = Vec::new();
+        collect_mod_files(&entry.path, &entry.path, &mut files);
+
+        // A mod providing several regional variants of a file only counts once
+        let replaced: HashSet<String> = files.iter().filter_map(|file| replaced_path(entry.base.as_deref(), file, &region)).collect();
+
+        for path in replaced {
+            if let Some(mods) = providers.get_mut(&path) {
+                mods.push(entry.folder_name.clone().unwrap_or_default());
+            }
+        }
+    }
+
+    let mut resolutions: Vec<ConflictResolution> = providers
+        .into_iter()
+        .filter(|(_, mods)| mods.len() > 1)
+        .map(|(path, mods)| ConflictResolution { path, mods })
+        .collect();
+
+    resolutions.sort_by(|a, b| a.path.cmp(&b.path));
+    resolutions.truncate(MAX_RESOLUTIONS);
+    resolutions
+}
+
+/// What the game knows about a file of a mod
+#[derive(Debug)]
+pub struct ResolvedFile {
+    pub hash: u64,
+    /// None if the path of the file isn't in the hash list
+    pub game_path: Option<String>,
+    /// Size of the file as the game shipped it. None if the game doesn't have the file, or when the sizes can't be read yet.
+    pub original_size: Option<u64>,
+    /// Whether the installed version of the game has the file. None when the data.arc can't be read yet.
+    pub in_game: Option<bool>,
+}
+
+/// Looks up a file from its path relative to a mod folder
+pub type FileResolver = Box<dyn Fn(&str) -> ResolvedFile + Send + Sync>;
+
+static FILE_RESOLVER: RwLock<Option<FileResolver>> = RwLock::new(None);
+
+/// Sets what looks up the files when inspecting a mod, since the hashes and the data.arc are only read outside of the menus
+pub fn set_file_resolver(resolver: impl Fn(&str) -> ResolvedFile + Send + Sync + 'static) {
+    *FILE_RESOLVER.write().unwrap() = Some(Box::new(resolver));
+}
+
+/// Applies a preset change to the running game, given the mod folders that were enabled and disabled. Returns false if a reboot is
+/// needed instead.
+pub type PresetReloader = Box<dyn Fn(&[PathBuf], &[PathBuf]) -> bool + Send + Sync>;
+
+static PRESET_RELOADER: RwLock<Option<PresetReloader>> = RwLock::new(None);
+
+/// Sets what tries to apply a preset change without rebooting, since only the filesystem knows what the mods replace
+pub fn set_preset_reloader(reloader: impl Fn(&[PathBuf], &[PathBuf]) -> bool + Send + Sync + 'static) {
+    *PRESET_RELOADER.write().unwrap() = Some(Box::new(reloader));
+}
+
+/// Tries to apply the mods that were toggled in a preset without a reboot
+fn reload_preset(entries: &[Entry], old: &HashSet<Hash40>, new: &HashSet<Hash40>) -> bool {
+    let reloader = PRESET_RELOADER.read().unwrap();
+
+    let reloader = match reloader.as_ref() {
+        Some(reloader) => reloader,
+        None => return false,
+    };
+
+    let toggled = |from: &HashSet<Hash40>, to: &HashSet<Hash40>| -> Vec<PathBuf> {
+        entries
+            .iter()
+            .filter(|entry| entry.path.to_str().map(Hash40::from).map_or(false, |hash| !from.contains(&hash) && to.contains(&hash)))
+            .map(|entry| entry.path.clone())
+            .collect()
+    };
+
+    reloader(&toggled(old, new), &toggled(new, old))
+}
+
+/// Extensions of the files that are merged into a game file instead of replacing one, so their paths aren't expected to exist in the game
+const PATCH_EXTENSIONS: &[&str] = &[
+    "prcx", "prcxml", "stdatx", "stdatxml", "stprmx", "stprmxml", "xmsbt", "patch3audio", "motdiff", "yml", "bsdiff", "template", "music",
+];
+
+fn is_patch_file(file: &str) -> bool {
+    PATCH_EXTENSIONS.iter().any(|extension| file.ends_with(&format!(".{}", extension)))
+}
+
+/// How many files of a mod exist in the installed version of the game
+#[derive(Debug, Default, Serialize)]
+pub struct ModValidation {
+    /// Files that replace a file of the game
+    pub known: usize,
+    /// Files the game doesn't have, which do nothing unless the mod adds them
+    pub unknown: usize,
+    /// Set for mods with a config.json, which can legitimately add new files to the game
+    pub adds_files: bool,
+}
+
+impl ModValidation {
+    /// Most files of a mod made for another version of the game point to paths this version doesn't have
+    pub fn is_likely_other_version(&self) -> bool {
+        !self.adds_files && self.unknown > self.known
+    }
+}
+
+/// Checks the files of a mod against the installed version of the game. Patch files are left out, as are all files while the data.arc
+/// can't be read yet. `base` is the directory the files are relative to, if the mod declares one.
+pub fn validate_mod(folder: &Path, base: Option<&str>) -> ModValidation {
+    let mut files = Vec::new();
+    collect_mod_files(folder, folder, &mut files);
+
+    let resolver = FILE_RESOLVER.read().unwrap();
+
+    let mut validation = ModValidation {
+        adds_files: folder.join("config.json").exists(),
+        ..Default::default()
+    };
+
+    for file in files {
+        if is_patch_file(&file) {
+            continue;
+        }
+
+        match resolver.as_ref().and_then(|resolver| resolver(&game_path(base, &file)).in_game) {
+            Some(true) => validation.known += 1,
+            Some(false) => validation.unknown += 1,
+            None => {},
+        }
+    }
+
+    validation
+}
+
+/// A file of a mod next to the one it replaces, so that users can tell when a replacement is larger than the vanilla file
+#[derive(Debug, Serialize)]
+struct InspectedFile {
+    path: String,
+    size: u64,
+    /// None for files the game doesn't have, or when the vanilla sizes can't be read yet
+    original_size: Option<u64>,
+}
+
+fn inspect_mod_files(root: &Path, base: Option<&str>) -> Vec<InspectedFile> {
+    let mut files = Vec::new();
+    collect_mod_files(root, root, &mut files);
+    files.sort();
+
+    let resolver = FILE_RESOLVER.read().unwrap();
+
+    files
+        .into_iter()
+        .map(|local| {
+            let path = game_path(base, &local);
+
+            InspectedFile {
+                size: std::fs::metadata(root.join(&local)).map_or(0, |metadata| metadata.len()),
+                original_size: resolver.as_ref().and_then(|resolver| resolver(&path).original_size),
+                path,
+            }
+        })
+        .collect()
+}
+
+/// Longest part of a readme shown in the inspect panel, in bytes
+const MAX_README_LENGTH: u64 = 0x4000;
+
+/// Notes shipped at the root of a mod, such as install instructions and credits
+#[derive(Debug, Serialize)]
+struct Readme {
+    name: String,
+    /// Rendered as basic markdown by the page instead of plain text
+    markdown: bool,
+    contents: String,
+    /// Set when only the start of the file is shown
+    truncated: bool,
+}
+
+/// Reads `readme.txt` or `readme.md` from the root of a mod, whatever their case. None if the mod doesn't have one.
+fn read_mod_readme(root: &Path) -> Option<Readme> {
+    let (path, name) = std::fs::read_dir(root).ok()?.filter_map(|entry| entry.ok()).find_map(|entry| {
+        let name = entry.file_name().to_str()?.to_string();
+        let lowercase = name.to_lowercase();
+        (lowercase == "readme.txt" || lowercase == "readme.md").then(|| (entry.path(), name))
+    })?;
+
+    let mut data = Vec::new();
+
+    if let Err(e) = std::fs::File::open(&path).and_then(|file| file.take(MAX_README_LENGTH).read_to_end(&mut data)) {
+        warn!("Failed to read '{}'. Reason: {}", path.display(), e);
+        return None;
+    }
+
+    Some(Readme {
+        markdown: name.to_lowercase().ends_with(".md"),
+        truncated: std::fs::metadata(&path).map_or(false, |metadata| metadata.len() > MAX_README_LENGTH),
+        // Cutting the file short can split a character in two, which is better shown as a placeholder than not shown at all
+        contents: String::from_utf8_lossy(&data).into_owned(),
+        name,
+    })
+}
+
+/// Lists the game files a mod replaces, one per line, with the ones missing from the hash list written as their hash
+fn mod_manifest(name: &str, root: &Path, base: Option<&str>) -> String {
+    let mut files = Vec::new();
+    collect_mod_files(root, root, &mut files);
+
+    let resolver = FILE_RESOLVER.read().unwrap();
+
+    let mut lines: Vec<String> = files
+        .into_iter()
+        .map(|local| game_path(base, &local))
+        .map(|path| match resolver.as_ref().map(|resolver| resolver(&path)) {
+            Some(resolved) => resolved.game_path.unwrap_or_else(|| format!("{:#x}", resolved.hash)),
+            None => path,
+        })
+        .collect();
+
+    // Regional variants of a file resolve to the same game path
+    lines.sort();
+    lines.dedup();
+
+    format!("{}\n{} file(s)\n\n{}\n", name, lines.len(), lines.join("\n"))
+}
+
+/// Writes the manifest of a mod next to the other ones and returns where it went
+fn export_mod_manifest(folder_name: &str, manifest: &str) -> std::io::Result<PathBuf> {
+    let path = PathBuf::from(utils::paths::manifests().as_str()).join(format!("{}.txt", folder_name));
+    std::fs::create_dir_all(utils::paths::manifests())?;
+    std::fs::write(&path, manifest)?;
+    Ok(path)
+}
+
+/// Runs the handlers that can take a while (walking the SD card, network requests) away from the message loop so that the page stays
+/// responsive. Their responses are posted back to the page by the message loop once they are ready.
+struct BackgroundTasks {
+    sender: Sender<String>,
+    receiver: Receiver<String>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl BackgroundTasks {
+    fn new() -> Self {
+        let (sender, receiver) = mpsc::channel();
+
+        Self {
+            sender,
+            receiver,
+            handles: Vec::new(),
+        }
+    }
+
+    /// Runs the task on its own thread. The string it returns is sent to the page as is.
+    fn spawn<F: FnOnce() -> String + Send + 'static>(&mut self, task: F) {
+        self.spawn_reporting(move |_| task());
+    }
+
+    /// Same as `spawn`, but the task is also given a way to send responses before it is done, to report its progress
+    fn spawn_reporting<F: FnOnce(&dyn Fn(String)) -> String + Send + 'static>(&mut self, task: F) {
+        let sender = self.sender.clone();
+
+        self.handles.retain(|handle| !handle.is_finished());
+        self.handles.push(std::thread::spawn(move || {
+            // The receiving end is only gone if the menu was closed, in which case nobody cares about the result anymore
+            let report = |response: String| {
+                let _ = sender.send(response);
+            };
+            let response = task(&report);
+            report(response);
+        }));
+    }
+
+    /// Waits up to `timeout` for a task to post a response, then sends every response that is ready to the page. This is what paces the
+    /// message loop, so a finished task is sent right away instead of after a fixed sleep.
+    fn send_finished(&self, session: &WebSession, timeout: Duration) {
+        if let Ok(response) = self.receiver.recv_timeout(timeout) {
+            session.send(&response);
+        }
+
+        for response in self.receiver.try_iter() {
+            session.send(&response);
+        }
+    }
+
+    /// Waits for every task still in flight
+    fn join(&mut self) {
+        for handle in self.handles.drain(..) {
+            if handle.join().is_err() {
+                error!("A background task of ARCadia panicked.");
+            }
+        }
+    }
+}
+
+impl Drop for BackgroundTasks {
+    // The message loop can also be left on an error, no task should outlive the menu however it is closed
+    fn drop(&mut self) {
+        self.join();
+    }
+}
+
+/// How long the message loop waits on the background tasks before checking for a message from the page again
+const MESSAGE_POLL_INTERVAL: Duration = Duration::from_millis(16);
+
+/// How many mods are measured between two progress updates sent to the page, so it isn't flooded on huge mod sets
+const MOD_SIZE_REPORT_INTERVAL: usize = 25;
+
+/// Sums up the size of every file in a mod
+fn mod_size(path: &Path) -> u64 {
+    std::fs::read_dir(path)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| match entry.metadata() {
+            Ok(metadata) if metadata.is_dir() => mod_size(&entry.path()),
+            Ok(metadata) => metadata.len(),
+            Err(_) => 0,
+        })
+        .sum()
+}
+
+/// Extensions of the previews a mod can have, in the order they are looked for
+const PREVIEW_EXTENSIONS: &[&str] = &["webp", "png", "jpg", "jpeg"];
+
+/// Checks the magic of an image against its extension, which catches corrupted files and other formats renamed
+fn has_image_header(header: &[u8], extension: &str) -> bool {
+    match extension {
+        "webp" => header.len() >= 12 && &header[0..4] == b"RIFF" && &header[8..12] == b"WEBP",
+        "png" => header.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "jpg" | "jpeg" => header.starts_with(&[0xFF, 0xD8, 0xFF]),
+        _ => false,
+    }
+}
+
+/// Reads the preview of a mod, going through the formats in order until one has a valid header, without reading the whole of the
+/// invalid ones. Returns the placeholder if the mod has previews but none of them can be used, and None if it has none.
+fn read_preview(mod_path: &Path) -> Option<Vec<u8>> {
+    let mut found = false;
+
+    for extension in PREVIEW_EXTENSIONS {
+        let path = mod_path.join("preview").with_extension(extension);
+
+        if !path.exists() {
+            continue;
+        }
+
+        found = true;
+
+        let mut header = Vec::with_capacity(12);
+
+        if let Err(e) = std::fs::File::open(&path).and_then(|file| file.take(12).read_to_end(&mut header)) {
+            warn!("Failed to read '{}'. Reason: {}", path.display(), e);
+            continue;
+        }
+
+        if !has_image_header(&header, extension) {
+            warn!("'{}' is not a valid {} image, skipping it.", path.display(), extension.to_uppercase());
+            continue;
+        }
+
+        match std::fs::read(&path) {
+            Ok(image) => return Some(image),
+            Err(e) => warn!("Failed to read '{}'. Reason: {}", path.display(), e),
+        }
+    }
+
+    if found {
+        warn!("No valid preview was found in '{}', using the placeholder instead.", mod_path.display());
+        Some(crate::files::MISSING_WEBP.to_vec())
+    } else {
+        None
+    }
+}
+
+/// Explains where an info.toml failed to parse: the offending line with a marker under the column, followed by a hint if the line
+/// looks like one of the usual mistakes
+fn describe_toml_error(contents: &str, error: &toml::de::Error) -> String {
+    let (line_index, column) = match error.line_col() {
+        Some(position) => position,
+        None => return error.to_string(),
+    };
+
+    let line = contents.lines().nth(line_index).unwrap_or_default();
+
+    let mut description = format!("{}\n\nLine {}:\n{}\n{}^", error, line_index + 1, line, " ".repeat(column));
+
+    // Anything that can't start a number, a boolean, an array or a table, and isn't quoted
+    let is_unquoted_string = line.split_once('=').map_or(false, |(_, value)| {
+        let value = value.trim();
+        !value.is_empty()
+            && !value.starts_with(|c: char| matches!(c, '"' | '\'' | '[' | '{' | '+' | '-') || c.is_ascii_digit())
+            && value != "true"
+            && value != "false"
+    });
+
+    let hint = if line.contains(|c: char| matches!(c, '\u{2018}' | '\u{2019}' | '\u{201C}' | '\u{201D}')) {
+        Some("This line contains curly quotes, which are usually inserted by word processors. Replace them with straight quotes (\").")
+    } else if line.starts_with('\t') {
+        Some("This line is indented with a tab. Remove the indentation, keys don't need any.")
+    } else if is_unquoted_string {
+        Some("Text values must be surrounded by quotes, for example: author = \"Your name\".")
+    } else {
+        None
+    };
+
+    if let Some(hint) = hint {
+        description.push_str("\n\nHint: ");
+        description.push_str(hint);
+    }
+
+    description
+}
+
+/// Gives the priorities to a new order of mods, from the one that wins conflicts to the one that loses them. The priorities the mods
+/// already had are handed out again from the highest to the lowest, so their place relative to the mods left out of the reorder doesn't
+/// change. Mods that would end up tied are bumped above the next one instead.
+fn reorder_priorities(current: &[i32]) -> Vec<i32> {
+    let mut priorities = current.to_vec();
+    priorities.sort_unstable_by(|a, b| b.cmp(a));
+
+    for index in (0..priorities.len().saturating_sub(1)).rev() {
+        priorities[index] = priorities[index].max(priorities[index + 1] + 1);
+    }
+
+    priorities
+}
+
+/// Gets the id of a mod from the full path of its folder, the same key as the presets. It doesn't depend on the other mods installed or
+/// on the order the SD lists them in, and mods with the same folder name in different directories (such as in a modpack) still get
+/// their own id.
+fn stable_mod_id(path: &str) -> u64 {
+    // Hash40 fits in 40 bits, well within what the page can represent as a number. This is also the key of the priority overrides.
+    ::config::priorities::mod_key(path)
+}
+
+/// Reads the info.toml of a mod. A missing file reads the same as an empty one, both use the defaults from the folder name, but a file
+/// that exists and can't be read is an error worth telling the user about.
+fn read_info_toml(path: &Path) -> std::io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(String::new()),
+        result => result,
+    }
+}
+
+pub fn get_mods(presets: &HashSet<Hash40>, disabled_groups: &HashSet<Hash40>, priority_overrides: &HashMap<u64, i32>) -> Vec<Entry> {
+    // Every info.toml that failed to parse, reported in a single dialog once all the mods are read
+    let mut invalid_infos: Vec<String> = Vec::new();
     let use_folder_name = ::config::use_folder_name();
-    std::fs::read_dir(utils::paths::mods())
-        .unwrap()
-        .enumerate()
-        .filter_map(|(_i, path)| {
-            let path_to_be_used = path.unwrap().path();
+
+    // Mods extracted from modpack bundles live in one directory per bundle, next to the regular mods
+    let modpacks = std::fs::read_dir(utils::paths::modpack_cache())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|path| path.is_dir());
+
+    let mut entries: Vec<Entry> = std::iter::once(utils::paths::mods())
+        .chain(utils::paths::extra_mods())
+        .map(|directory| PathBuf::from(directory.as_str()))
+        .chain(modpacks)
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|path| {
+            let path_to_be_used = path.ok()?.path();
 
             if path_to_be_used.is_file() {
                 return None;
             }
 
-            let disabled = !presets.contains(&Hash40::from(path_to_be_used.to_str().unwrap()));
+            // Presets and ids are keyed by the path as a string, so a folder whose name isn't UTF-8 can't be toggled anyway
+            let (path_str, folder_name) = match (path_to_be_used.to_str(), path_to_be_used.file_name().and_then(|name| name.to_str())) {
+                (Some(path_str), Some(folder_name)) => (path_str.to_string(), folder_name.to_string()),
+                _ => {
+                    warn!("Skipping '{}' because its name is not valid UTF-8.", path_to_be_used.display());
+                    return None;
+                },
+            };
 
-            let folder_name = Path::new(&path_to_be_used).file_name().unwrap().to_os_string().into_string().unwrap();
+            let disabled = !presets.contains(&Hash40::from(path_str.as_str()));
 
             let info_path = format!("{}/info.toml", path_to_be_used.display());
 
+            let id = stable_mod_id(&path_str);
+
+            // A priority set from the menu takes precedence over the one in info.toml
+            let priority_override = priority_overrides.get(&id).copied();
+
             let default_entry = Entry {
                 id: Some(id),
                 folder_name: Some(folder_name.clone()),
@@ -63,10 +848,17 @@ pub fn get_mods(presets: &HashSet<Hash40>) -> Vec<Entry> {
                 version: Some("???".to_string()),
                 // description: Some("".to_string()),
                 category: Some("Miscellaneous".to_string()),
+                priority: Some(priority_override.unwrap_or(0)),
+                path: path_to_be_used.clone(),
                 ..Default::default()
             };
 
-            let mod_info = match toml::from_str::<Entry>(&std::fs::read_to_string(info_path).unwrap_or_default()) {
+            let info_contents = read_info_toml(Path::new(&info_path)).unwrap_or_else(|e| {
+                warn!("Failed to read '{}', falling back to the folder name. Reason: {}", info_path, e);
+                String::new()
+            });
+
+            let mod_info = match toml::from_str::<Entry>(&info_contents) {
                 Ok(res) => Entry {
                     id: Some(id),
                     folder_name: Some(folder_name.clone()),
@@ -82,23 +874,191 @@ pub fn get_mods(presets: &HashSet<Hash40>) -> Vec<Entry> {
                         }
                     }),
                     description: Some(res.description.unwrap_or_default().replace('\n', "<br />")),
+                    priority: Some(priority_override.or(res.priority).unwrap_or(0)),
+                    base: res.base,
+                    groups: res
+                        .groups
+                        .into_iter()
+                        .map(|group| FileGroup {
+                            enabled: !disabled_groups.contains(&::config::groups::group_hash(&path_str, &group.name)),
+                            ..group
+                        })
+                        .collect(),
+                    conflicts_with: res.conflicts_with,
+                    conflicts: Vec::new(),
+                    path: path_to_be_used.clone(),
                 },
                 Err(e) => {
-                    skyline_web::dialog_ok::DialogOk::ok(format!("The following info.toml is not valid: \n\n* '{}'\n\nError: {}", folder_name, e,));
+                    invalid_infos.push(format!("* '{}'\n\n{}", folder_name, describe_toml_error(&info_contents, &e)));
                     default_entry
                 },
             };
 
-            id += 1;
-
             Some(mod_info)
         })
+        .collect();
+
+    if !invalid_infos.is_empty() {
+        skyline_web::dialog_ok::DialogOk::ok(format!("The following info.toml files are not valid:\n\n{}", invalid_infos.join("\n\n")));
+    }
+
+    link_conflicts(&mut entries);
+    link_dependencies(&mut entries);
+
+    entries
+}
+
+/// Fills in the mods each mod conflicts with from the `conflicts_with` lists of their info.toml. A conflict only needs to be declared by
+/// one of the two mods, as a mod can't know about every mod released after it. Folder names are compared case-insensitively.
+fn link_conflicts(entries: &mut [Entry]) {
+    let ids: HashMap<String, u64> = entries
+        .iter()
+        .filter_map(|entry| Some((entry.folder_name.as_ref()?.to_lowercase(), entry.id?)))
+        .collect();
+
+    let mut pairs: Vec<(u64, u64)> = Vec::new();
+
+    for entry in entries.iter() {
+        let id = match entry.id {
+            Some(id) => id,
+            None => continue,
+        };
+
+        for other in entry.conflicts_with.iter().filter_map(|name| ids.get(&name.to_lowercase())) {
+            if *other != id {
+                pairs.push((id, *other));
+                pairs.push((*other, id));
+            }
+        }
+    }
+
+    for entry in entries.iter_mut() {
+        let mut conflicts: Vec<u64> = pairs.iter().filter(|(id, _)| Some(*id) == entry.id).map(|(_, other)| *other).collect();
+        conflicts.sort_unstable();
+        conflicts.dedup();
+        entry.conflicts = conflicts;
+    }
+}
+
+/// Fills in the mods each mod depends on from the `depends_on` list of its info.toml, the same way as the conflicts
+fn link_dependencies(entries: &mut [Entry]) {
+    let ids: HashMap<String, u64> = entries
+        .iter()
+        .filter_map(|entry| Some((entry.folder_name.as_ref()?.to_lowercase(), entry.id?)))
+        .collect();
+
+    for entry in entries.iter_mut() {
+        let mut dependencies = Vec::new();
+        let mut missing = Vec::new();
+
+        for name in entry.depends_on.iter() {
+            match ids.get(&name.to_lowercase()) {
+                Some(id) if Some(*id) != entry.id => dependencies.push(*id),
+                Some(_) => {},
+                None => missing.push(name.clone()),
+            }
+        }
+
+        dependencies.sort_unstable();
+        dependencies.dedup();
+
+        entry.dependencies = dependencies;
+        entry.missing_dependencies = missing;
+    }
+}
+
+/// Finds the mods whose name, folder or author contains `query`, whatever its case. Every mod matches an empty query.
+fn search_mods(entries: &[Entry], query: &str) -> Vec<u64> {
+    let query = query.trim().to_lowercase();
+
+    entries
+        .iter()
+        .filter(|entry| {
+            query.is_empty()
+                || [&entry.display_name, &entry.folder_name, &entry.author]
+                    .iter()
+                    .any(|field| field.as_ref().map_or(false, |field| field.to_lowercase().contains(&query)))
+        })
+        .filter_map(|entry| entry.id)
         .collect()
 }
 
+/// Escapes text coming from mods before it is shown in a dialog, which renders HTML
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#39;")
+}
+
+/// Checks that the enabled mods of a preset can work together. Lists the mods that conflict with each other and the mods whose
+/// dependencies are disabled or not installed otherwise, with their names escaped for a dialog.
+fn check_preset(entries: &[Entry], presets: &HashSet<Hash40>) -> Result<(), Vec<String>> {
+    let is_enabled = |entry: &Entry| entry.path.to_str().map_or(false, |path| presets.contains(&Hash40::from(path)));
+    let names: HashMap<u64, &Entry> = entries.iter().filter_map(|entry| Some((entry.id?, entry))).collect();
+    let name = |entry: &Entry| escape_html(&entry.display_name.clone().or_else(|| entry.folder_name.clone()).unwrap_or_default());
+
+    let mut problems = Vec::new();
+
+    for entry in entries.iter().filter(|entry| is_enabled(entry)) {
+        for other in entry.conflicts.iter().filter_map(|id| names.get(id)) {
+            // Each pair is listed by both of its mods, only report it once
+            if is_enabled(other) && entry.id < other.id {
+                problems.push(format!("{} can't be used along with {}", name(entry), name(other)));
+            }
+        }
+
+        for other in entry.dependencies.iter().filter_map(|id| names.get(id)) {
+            if !is_enabled(other) {
+                problems.push(format!("{} needs {}, which is disabled", name(entry), name(other)));
+            }
+        }
+
+        for missing in entry.missing_dependencies.iter() {
+            problems.push(format!("{} needs {}, which is not installed", name(entry), escape_html(missing)));
+        }
+    }
+
+    if problems.is_empty() {
+        Ok(())
+    } else {
+        Err(problems)
+    }
+}
+
+/// Removes the previews written by the previous session. Anything that doesn't look like the preview folder is left alone, so a wrong
+/// path can never delete files that don't belong to ARCropolis. Returns whether the folder can be used.
+fn clear_preview_cache(path: &camino::Utf8Path) -> bool {
+    let is_preview_cache = path.file_name() == Some(utils::paths::PREVIEW_CACHE_NAME)
+        && path.parent().map_or(false, |parent| parent.ends_with("manual_html/html-document/contents.htdocs"));
+
+    if !is_preview_cache {
+        error!("Refusing to clear '{}' since it is not the preview cache of ARCadia.", path);
+        return false;
+    }
+
+    match std::fs::remove_dir_all(path) {
+        Ok(()) => true,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => true,
+        Err(e) => {
+            error!("Failed to clear the preview cache at '{}'. Reason: {}", path, e);
+            true
+        },
+    }
+}
+
 pub fn show_arcadia(workspace: Option<String>) {
+    open_arcadia(workspace, false)
+}
+
+/// `workspace_switched` is set when a previous ARCadia session changed something that requires a reboot before switching workspaces,
+/// so that the reboot prompt isn't lost along the way
+fn open_arcadia(workspace: Option<String>, workspace_switched: bool) {
     let umm_path = utils::paths::mods();
 
+    // Anything saved now would mix the mods of the overridden directory into the preset of the regular one
+    if ::config::mods_path_override().is_some() {
+        skyline_web::dialog_ok::DialogOk::ok("The mods directory is overridden for this boot, mods can't be managed until the next one.");
+        return;
+    }
+
     if !umm_path.exists() {
         skyline_web::dialog_ok::DialogOk::ok("It seems the directory specified in your configuration does not exist.");
         return;
@@ -106,31 +1066,45 @@ pub fn show_arcadia(workspace: Option<String>) {
     let workspace_name: String =
         workspace.unwrap_or_else(|| ::config::workspaces::get_active_workspace_name().unwrap_or_else(|_| String::from("Default")));
 
-    let presets = ::config::presets::get_preset(&workspace_name).unwrap();
+    let mut presets = ::config::presets::get_preset_or_reset(&workspace_name);
     let mut new_presets = presets.clone();
 
-    let mods: Information = Information {
-        entries: get_mods(&presets),
+    let disabled_groups = ::config::groups::get_disabled(&workspace_name);
+    let mut new_disabled_groups = disabled_groups.clone();
+
+    let mut mods: Information = Information {
+        entries: get_mods(&presets, &disabled_groups, &::config::priorities::get_overrides(&workspace_name)),
         workspace: workspace_name.clone(),
+        bypass_replacements: ::config::replacements_bypassed(),
+        sd_available: ::config::sd_available(),
     };
 
     // region Setup Preview Images
     let mut images: Vec<(String, Vec<u8>)> = Vec::new();
     for item in &mods.entries {
-        let path = &umm_path.join(item.folder_name.as_ref().unwrap()).join("preview.webp");
-
-        if path.exists() {
-            images.push((format!("img/{}", item.id.unwrap()), std::fs::read(path).unwrap()));
-        };
+        if let Some(image) = read_preview(&item.path) {
+            images.push((format!("{}/{}", utils::paths::PREVIEW_CACHE_NAME, item.id.unwrap()), image));
+        }
     }
 
-    let img_cache = "sd:/atmosphere/contents/01006A800016E000/manual_html/html-document/contents.htdocs/img";
+    let img_cache = utils::paths::preview_cache();
 
-    if std::fs::metadata(img_cache).is_ok() {
-        let _ = std::fs::remove_dir_all(img_cache).map_err(|err| error!("Error occured in ARCadia-rs when trying to delete cache: {}", err));
-    };
+    if clear_preview_cache(&img_cache) {
+        if let Err(e) = std::fs::create_dir_all(&img_cache) {
+            error!("Failed to create the preview cache at '{}'. Reason: {}", img_cache, e);
+        }
+    }
 
-    std::fs::create_dir_all(img_cache).unwrap();
+    // The previews are written to the SD when the page opens, a truncated one is worse than the placeholder
+    let images_size: u64 = images.iter().map(|(_, image)| image.len() as u64).sum();
+
+    if !utils::storage::has_room_for(images_size) {
+        warn!("Not enough free space on the SD card to cache the mod previews ({:#x} bytes), skipping them.", images_size);
+        images.clear();
+        skyline_web::dialog_ok::DialogOk::ok(
+            "Your SD card is almost full, so the mod previews will not be shown.<br>Consider freeing some space on your SD card.",
+        );
+    }
 
     println!("Opening ARCadia...");
 
@@ -139,8 +1113,8 @@ pub fn show_arcadia(workspace: Option<String>) {
         .file("index.html", &crate::files::ARCADIA_HTML_TEXT)
         .file("arcadia.js", &crate::files::ARCADIA_JS_TEXT)
         .file("common.js", &crate::files::COMMON_JAVASCRIPT_TEXT)
-        .file("arcadia.css", &crate::files::ARCADIA_CSS_TEXT)
-        .file("common.css", &crate::files::COMMON_CSS_TEXT)
+        .file("arcadia.css", &crate::files::themed("arcadia.css", crate::files::ARCADIA_CSS_TEXT))
+        .file("common.css", &crate::files::themed("common.css", crate::files::COMMON_CSS_TEXT))
         .file("pagination.min.js", &crate::files::PAGINATION_JS)
         .file("jquery.marquee.min.js", &crate::files::MARQUEE_JS)
         .file("check.svg", &crate::files::CHECK_SVG)
@@ -152,11 +1126,44 @@ pub fn show_arcadia(workspace: Option<String>) {
         .open_session(skyline_web::Visibility::Default)
         .unwrap();
 
-    while let Ok(message) = session.recv_json::<ArcadiaMessage>() {
+    // Where each mod is in the list, as the page refers to them by id
+    let positions: HashMap<u64, usize> = mods.entries.iter().enumerate().filter_map(|(index, entry)| Some((entry.id?, index))).collect();
+    // The installed mods were already listed when opening the page, so telling which entries of a preset are stale is only a matter of
+    // looking them up
+    let installed: HashSet<Hash40> = mods.entries.iter().filter_map(|entry| entry.path.to_str().map(Hash40::from)).collect();
+
+    let mut tasks = BackgroundTasks::new();
+    let mut switch_to: Option<String> = None;
+
+    loop {
+        let message = match session.try_recv() {
+            Some(message) => message,
+            None => {
+                tasks.send_finished(&session, MESSAGE_POLL_INTERVAL);
+                continue;
+            },
+        };
+
+        let message = match serde_json::from_str::<ArcadiaMessage>(&message) {
+            Ok(message) => message,
+            Err(e) => {
+                error!("Received an invalid message from ARCadia. Reason: {}", e);
+                break;
+            },
+        };
+
         match message {
             ArcadiaMessage::ToggleMod { id, state } => {
-                let path = format!("{}/{}", umm_path, mods.entries[id].folder_name.as_ref().unwrap());
-                let hash = Hash40::from(path.as_str());
+                let index = match positions.get(&id) {
+                    Some(index) => *index,
+                    None => {
+                        warn!("Tried to toggle an unknown mod with id {:#x}", id);
+                        continue;
+                    },
+                };
+
+                let path = mods.entries[index].path.display();
+                let hash = Hash40::from(mods.entries[index].path.to_str().unwrap());
                 debug!("Setting {} to {}", path, state);
 
                 if state {
@@ -174,17 +1181,16 @@ pub fn show_arcadia(workspace: Option<String>) {
                     new_presets.clear();
                 } else {
                     for item in mods.entries.iter() {
-                        let path = format!("{}/{}", umm_path, item.folder_name.as_ref().unwrap());
-                        let hash = Hash40::from(path.as_str());
+                        let hash = Hash40::from(item.path.to_str().unwrap());
 
                         new_presets.insert(hash);
                     }
                 }
             },
-            ArcadiaMessage::ChangeIndexes { state, indexes } => {
-                for idx in indexes {
-                    let path = format!("{}/{}", umm_path, mods.entries[idx].folder_name.as_ref().unwrap());
-                    let hash = Hash40::from(path.as_str());
+            ArcadiaMessage::ChangeMods { state, ids } => {
+                for idx in ids.iter().filter_map(|id| positions.get(id).copied()) {
+                    let path = mods.entries[idx].path.display();
+                    let hash = Hash40::from(mods.entries[idx].path.to_str().unwrap());
                     debug!("Setting {} to {}", path, state);
 
                     if state {
@@ -198,10 +1204,286 @@ pub fn show_arcadia(workspace: Option<String>) {
                 println!("session says: {}", message);
             },
             ArcadiaMessage::GetModSize => {
-                // let size = crate::GLOBAL_FILESYSTEM.try_read().map_or(0, |lock| lock.get_sum_size().unwrap_or(0));
-                session.send(format!("{{ \"mod_size\": {} }}", 69420).as_str());
+                let enabled: Vec<PathBuf> = mods
+                    .entries
+                    .iter()
+                    .filter(|entry| entry.path.to_str().map_or(false, |path| new_presets.contains(&Hash40::from(path))))
+                    .map(|entry| entry.path.clone())
+                    .collect();
+
+                tasks.spawn_reporting(move |report| {
+                    let mut size = 0;
+
+                    for (done, path) in enabled.iter().enumerate() {
+                        size += mod_size(path);
+
+                        if (done + 1) % MOD_SIZE_REPORT_INTERVAL == 0 {
+                            report(serde_json::json!({ "mod_size_progress": { "done": done + 1, "total": enabled.len() } }).to_string());
+                        }
+                    }
+
+                    format!("{{ \"mod_size\": {} }}", size)
+                });
+            },
+            ArcadiaMessage::ViewLog { which } => {
+                debug!("Viewing log {:?}", which);
+                let log = serde_json::json!({ "log": view_log(&which) });
+                session.send(&log.to_string());
+            },
+            ArcadiaMessage::SearchMods { query } => {
+                let ids = search_mods(&mods.entries, &query);
+                let response = serde_json::json!({ "search": { "query": query, "ids": ids } });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::SetPriority { id, priority } => {
+                let index = match positions.get(&id) {
+                    Some(index) => *index,
+                    None => {
+                        warn!("Tried to set the priority of an unknown mod with id {:#x}", id);
+                        continue;
+                    },
+                };
+
+                let folder_name = mods.entries[index].folder_name.clone().unwrap_or_default();
+                debug!("Setting the priority of {} to {}", folder_name, priority);
+
+                if let Err(e) = ::config::priorities::set_override(&workspace_name, id, priority) {
+                    error!("Failed to save the priority of {}. Reason: {}", folder_name, e);
+                }
+
+                mods.entries[index].priority = Some(priority);
+
+                let entries = mods.entries.clone();
+                let presets = new_presets.clone();
+
+                tasks.spawn(move || {
+                    let response = serde_json::json!({
+                        "priority": {
+                            "id": id,
+                            "priority": priority,
+                            "conflicts": resolve_conflicts(&entries, &presets, index),
+                        }
+                    });
+                    response.to_string()
+                });
+            },
+            ArcadiaMessage::ReorderMods { ids } => {
+                let order: Vec<usize> = ids.iter().filter_map(|id| positions.get(id).copied()).collect();
+                let current: Vec<i32> = order.iter().map(|index| mods.entries[*index].priority.unwrap_or(0)).collect();
+
+                let priorities: Vec<(usize, i32)> = order.iter().copied().zip(reorder_priorities(&current)).collect();
+
+                let overrides = priorities.iter().filter_map(|(index, priority)| Some((mods.entries[*index].id?, *priority)));
+
+                if let Err(e) = ::config::priorities::set_overrides(&workspace_name, overrides) {
+                    error!("Failed to save the order of the mods. Reason: {}", e);
+                }
+
+                for (index, priority) in priorities.iter() {
+                    mods.entries[*index].priority = Some(*priority);
+                }
+
+                let response = serde_json::json!({
+                    "reorder": priorities
+                        .iter()
+                        .map(|(index, priority)| serde_json::json!({ "id": mods.entries[*index].id, "priority": priority }))
+                        .collect::<Vec<_>>()
+                });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::ToggleGroup { id, group, state } => {
+                let index = match positions.get(&id) {
+                    Some(index) => *index,
+                    None => {
+                        warn!("Tried to toggle a group of an unknown mod with id {:#x}", id);
+                        continue;
+                    },
+                };
+
+                let hash = ::config::groups::group_hash(mods.entries[index].path.to_str().unwrap(), &group);
+                debug!("Setting group {} of {} to {}", group, mods.entries[index].path.display(), state);
+
+                if state {
+                    new_disabled_groups.remove(&hash);
+                } else {
+                    new_disabled_groups.insert(hash);
+                }
+
+                if let Some(entry_group) = mods.entries[index].groups.iter_mut().find(|entry_group| entry_group.name == group) {
+                    entry_group.enabled = state;
+                }
+            },
+            ArcadiaMessage::GetWorkspaces => {
+                let response = serde_json::json!({ "workspaces": crate::workspaces::get_workspace_summaries(&installed) });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::GetChanges => {
+                let response = serde_json::json!({ "changes": get_mod_changes() });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::InspectMod { id } => {
+                let index = match positions.get(&id) {
+                    Some(index) => *index,
+                    None => {
+                        warn!("Tried to inspect an unknown mod with id {:#x}", id);
+                        continue;
+                    },
+                };
+
+                let entry = &mods.entries[index];
+                let root = entry.path.clone();
+                let base = entry.base.clone();
+                let name = entry.display_name.clone().or_else(|| entry.folder_name.clone()).unwrap_or_default();
+                let entries = mods.entries.clone();
+                let presets = new_presets.clone();
+
+                tasks.spawn(move || {
+                    let validation = validate_mod(&root, base.as_deref());
+                    let files = inspect_mod_files(&root, base.as_deref());
+                    let response = serde_json::json!({
+                        "inspect": {
+                            "name": name,
+                            "total_size": files.iter().map(|file| file.size).sum::<u64>(),
+                            "files": files,
+                            "conflicts": resolve_conflicts(&entries, &presets, index),
+                            "readme": read_mod_readme(&root),
+                            "likely_other_version": validation.is_likely_other_version(),
+                            "validation": validation,
+                        }
+                    });
+                    response.to_string()
+                });
+            },
+            ArcadiaMessage::ExportModManifest { id } => {
+                let entry = match positions.get(&id) {
+                    Some(index) => &mods.entries[*index],
+                    None => {
+                        warn!("Tried to export the manifest of an unknown mod with id {:#x}", id);
+                        continue;
+                    },
+                };
+
+                let root = entry.path.clone();
+                let base = entry.base.clone();
+                let folder_name = entry.folder_name.clone().unwrap_or_default();
+                let name = entry.display_name.clone().unwrap_or_else(|| folder_name.clone());
+
+                tasks.spawn(move || {
+                    let manifest = mod_manifest(&name, &root, base.as_deref());
+
+                    // The manifest is still shown when it can't be saved, it can be copied from the page instead
+                    let path = match export_mod_manifest(&folder_name, &manifest) {
+                        Ok(path) => Some(path.display().to_string()),
+                        Err(e) => {
+                            error!("Failed to save the manifest of {}. Reason: {}", folder_name, e);
+                            None
+                        },
+                    };
+
+                    let response = serde_json::json!({
+                        "manifest": {
+                            "name": name,
+                            "path": path,
+                            "contents": manifest,
+                        }
+                    });
+                    response.to_string()
+                });
+            },
+            ArcadiaMessage::SetBypass { state } => {
+                ::config::set_replacements_bypassed(state);
+                let response = serde_json::json!({ "bypass_replacements": state });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::GetCollections => {
+                let response = serde_json::json!({ "collections": get_collection_summaries(&mods.entries) });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::CreateCollection { name, parent } => {
+                if let Err(e) = ::config::collections::create_collection(name.clone(), parent) {
+                    error!("Failed to create collection {}. Reason: {}", name, e);
+                }
+
+                let response = serde_json::json!({ "collections": get_collection_summaries(&mods.entries) });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::DeleteCollection { name } => {
+                if let Err(e) = ::config::collections::delete_collection(&name) {
+                    error!("Failed to delete collection {}. Reason: {}", name, e);
+                }
+
+                let response = serde_json::json!({ "collections": get_collection_summaries(&mods.entries) });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::SetCollectionMods { name, ids, state } => {
+                let folder_names: Vec<&str> = ids
+                    .iter()
+                    .filter_map(|id| mods.entries.get(*positions.get(id)?)?.folder_name.as_deref())
+                    .collect();
+
+                if let Err(e) = ::config::collections::set_mods_state(&name, &folder_names, state) {
+                    error!("Failed to update collection {}. Reason: {}", name, e);
+                }
+
+                let response = serde_json::json!({ "collections": get_collection_summaries(&mods.entries) });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::SwitchWorkspace { name } => {
+                debug!("Switching to workspace {}", name);
+                switch_to = Some(name);
+                tasks.join();
+                session.exit();
+                session.wait_for_exit();
+                break;
+            },
+            ArcadiaMessage::GetStaleEntries => {
+                let stale = match ::config::presets::find_stale_entries(&workspace_name, &installed) {
+                    Ok(stale) => stale.len(),
+                    Err(e) => {
+                        error!("Failed to look for stale entries in the preset of workspace {}. Reason: {}", workspace_name, e);
+                        0
+                    },
+                };
+
+                let response = serde_json::json!({ "stale_entries": stale });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::CleanUpPreset => {
+                let removed = match ::config::presets::clean_up_preset(&workspace_name, &installed) {
+                    Ok(removed) => removed,
+                    Err(e) => {
+                        error!("Failed to clean up the preset of workspace {}. Reason: {}", workspace_name, e);
+                        0
+                    },
+                };
+
+                // Otherwise they'd be written back when saving, stale entries don't load anything so this isn't a change to the preset
+                presets.retain(|hash| installed.contains(hash));
+                new_presets.retain(|hash| installed.contains(hash));
+
+                let response = serde_json::json!({ "preset_cleaned_up": removed });
+                session.send(&response.to_string());
+            },
+            ArcadiaMessage::ResetConfig | ArcadiaMessage::ClearPreset => {
+                tasks.join();
+                session.exit();
+                session.wait_for_exit();
+
+                // The changes made in this session are dropped, they would otherwise be written over the fresh configuration
+                let success = if matches!(message, ArcadiaMessage::ResetConfig) {
+                    crate::recovery::reset_config()
+                } else {
+                    crate::recovery::clear_preset(&workspace_name)
+                };
+
+                if success && skyline_web::dialog::Dialog::yes_no("Would you like to reboot the game to reload your mods?") {
+                    crate::reboot_with_countdown();
+                }
+
+                return;
             },
             ArcadiaMessage::Closure => {
+                tasks.join();
                 session.exit();
                 session.wait_for_exit();
                 break;
@@ -209,15 +1491,251 @@ pub fn show_arcadia(workspace: Option<String>) {
         }
     }
 
-    let active_workspace = ::config::workspaces::get_active_workspace_name().unwrap();
-    ::config::presets::replace_preset(&workspace_name, &new_presets).unwrap();
+    let active_workspace = ::config::workspaces::get_active_workspace_name().unwrap_or_else(|_| String::from("Default"));
 
+    // A preset whose mods can't work together is never saved, the previous one stays in use
     if new_presets != presets {
+        if let Err(problems) = check_preset(&mods.entries, &new_presets) {
+            skyline_web::dialog_ok::DialogOk::ok(format!(
+                "Your changes to workspace {} were not saved, as some of the enabled mods can't work together:<br><br>{}",
+                escape_html(&workspace_name),
+                problems.join("<br>")
+            ));
+            return;
+        }
+    }
+
+    if let Err(e) = ::config::presets::replace_preset(&workspace_name, &new_presets) {
+        error!("Failed to save the preset for workspace {}. Reason: {}", workspace_name, e);
+        skyline_web::dialog_ok::DialogOk::ok(format!("Failed to save your changes to workspace {}.", workspace_name));
+        return;
+    }
+
+    if new_disabled_groups != disabled_groups {
+        if let Err(e) = ::config::groups::set_disabled(&workspace_name, &new_disabled_groups) {
+            error!("Failed to save the file groups for workspace {}. Reason: {}", workspace_name, e);
+        }
+    }
+
+    // Editing a preset only matters right now if it's the one being used
+    let preset_changed = (new_presets != presets || new_disabled_groups != disabled_groups) && active_workspace == workspace_name;
+
+    if let Some(name) = switch_to {
+        if let Err(e) = ::config::workspaces::set_active_workspace(name.clone()) {
+            error!("Failed to switch to workspace {}. Reason: {}", name, e);
+            skyline_web::dialog_ok::DialogOk::ok(format!("Failed to switch to workspace {}.", name));
+            return;
+        }
+
+        let pending_reboot = workspace_switched || preset_changed || name != active_workspace;
+        return open_arcadia(Some(name), pending_reboot);
+    }
+
+    // The preset is already saved at this point, so backing out only delays when the changes are applied
+    if workspace_switched {
+        if skyline_web::dialog::Dialog::yes_no(format!("Your active workspace has successfully been changed to {}!<br>Your changes will take effect on the next boot.<br>Would you like to reboot the game to reload your mods?", workspace_name)) {
+            crate::reboot_with_countdown();
+        }
+    } else if preset_changed && new_disabled_groups == disabled_groups && reload_preset(&mods.entries, &presets, &new_presets) {
+        skyline_web::dialog_ok::DialogOk::ok("Your preset has successfully been updated and applied!<br>Only params were changed, so no reboot is needed.");
+    } else if preset_changed {
         // Acquire the filesystem so we can check if it's already finished or not (for boot-time mod manager)
         // if let Some(_filesystem) = crate::GLOBAL_FILESYSTEM.try_read() {
-            if active_workspace.eq(&workspace_name) && skyline_web::dialog::Dialog::yes_no("Your preset has successfully been updated!<br>Your changes will take effect on the next boot.<br>Would you like to reboot the game to reload your mods?") {
-                unsafe { skyline::nn::oe::RequestToRelaunchApplication() };
+            if skyline_web::dialog::Dialog::yes_no("Your preset has successfully been updated!<br>Your changes will take effect on the next boot.<br>Would you like to reboot the game to reload your mods?") {
+                crate::reboot_with_countdown();
             }
         // }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mods_sharing_a_folder_name_keep_their_own_id() {
+        let regular = stable_mod_id("sd:/ultimate/mods/Skins");
+        let modpack = stable_mod_id("sd:/ultimate/modpacks/pack/Skins");
+
+        assert_ne!(regular, modpack);
+        // Listing them the other way around doesn't matter
+        assert_eq!(stable_mod_id("sd:/ultimate/modpacks/pack/Skins"), modpack);
+        assert_eq!(stable_mod_id("sd:/ultimate/mods/Skins"), regular);
+    }
+
+    #[test]
+    fn previews_fall_back_to_the_next_valid_format() {
+        let mod_path = std::env::temp_dir().join("arcadia_previews_fall_back_to_the_next_valid_format");
+        let _ = std::fs::remove_dir_all(&mod_path);
+        std::fs::create_dir_all(&mod_path).unwrap();
+
+        assert_eq!(read_preview(&mod_path), None);
+
+        // A PNG renamed to .webp is skipped for the actual PNG
+        let png = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR".to_vec();
+        std::fs::write(mod_path.join("preview.webp"), &png).unwrap();
+        std::fs::write(mod_path.join("preview.png"), &png).unwrap();
+        assert_eq!(read_preview(&mod_path), Some(png));
+
+        std::fs::write(mod_path.join("preview.png"), b"not an image").unwrap();
+        assert_eq!(read_preview(&mod_path).as_deref(), Some(crate::files::MISSING_WEBP.as_ref()));
+
+        std::fs::remove_dir_all(&mod_path).unwrap();
+    }
+
+    #[test]
+    fn background_tasks_are_joined_when_the_menu_goes_away() {
+        let finished = std::sync::Arc::new(std::sync::atomic::AtomicBool::new(false));
+
+        let mut tasks = BackgroundTasks::new();
+        let task_finished = finished.clone();
+        tasks.spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            task_finished.store(true, std::sync::atomic::Ordering::SeqCst);
+            String::new()
+        });
+        drop(tasks);
+
+        assert!(finished.load(std::sync::atomic::Ordering::SeqCst));
+    }
+
+    #[test]
+    fn empty_info_tomls_are_told_apart_from_unreadable_ones() {
+        let mod_path = std::env::temp_dir().join("arcadia_empty_info_tomls_are_told_apart_from_unreadable_ones");
+        let _ = std::fs::remove_dir_all(&mod_path);
+        std::fs::create_dir_all(&mod_path).unwrap();
+        let info_path = mod_path.join("info.toml");
+
+        assert_eq!(read_info_toml(&info_path).unwrap(), "");
+
+        std::fs::write(&info_path, "").unwrap();
+        assert_eq!(read_info_toml(&info_path).unwrap(), "");
+        // An empty file is a valid info.toml where everything is left to the defaults
+        assert!(toml::from_str::<Entry>("").is_ok());
+
+        // Something that exists but can't be read as a file
+        std::fs::remove_file(&info_path).unwrap();
+        std::fs::create_dir(&info_path).unwrap();
+        assert!(read_info_toml(&info_path).is_err());
+
+        std::fs::remove_dir_all(&mod_path).unwrap();
+    }
+
+    #[test]
+    fn declared_mod_ids_are_ignored() {
+        // Two mods copied from the same template still get told apart by their folder
+        let first = toml::from_str::<Entry>("id = 0").unwrap();
+        let second = toml::from_str::<Entry>("id = 0").unwrap();
+
+        assert_eq!(first.id, None);
+        assert_eq!(second.id, None);
+        assert_ne!(stable_mod_id("sd:/ultimate/mods/First"), stable_mod_id("sd:/ultimate/mods/Second"));
+    }
+
+    #[test]
+    fn reordering_reuses_the_existing_priorities() {
+        assert_eq!(reorder_priorities(&[3, 10, 5]), [10, 5, 3]);
+        assert_eq!(reorder_priorities(&[0, 0, 0]), [2, 1, 0]);
+        assert_eq!(reorder_priorities(&[1, 4, 4]), [5, 4, 1]);
+        assert!(reorder_priorities(&[]).is_empty());
+    }
+
+    fn entry(folder_name: &str) -> Entry {
+        let path = format!("sd:/ultimate/mods/{}", folder_name);
+
+        Entry {
+            id: Some(stable_mod_id(&path)),
+            folder_name: Some(folder_name.to_string()),
+            path: PathBuf::from(path),
+            ..Default::default()
+        }
+    }
+
+    fn preset(entries: &[&Entry]) -> HashSet<Hash40> {
+        entries.iter().map(|entry| Hash40::from(entry.path.to_str().unwrap())).collect()
+    }
+
+    #[test]
+    fn conflicting_presets_are_refused() {
+        let mut first = entry("<b>First</b>");
+        let mut second = entry("Second");
+        first.conflicts.push(second.id.unwrap());
+        second.conflicts.push(first.id.unwrap());
+
+        let entries = [first.clone(), second.clone()];
+
+        assert!(check_preset(&entries, &preset(&[&first])).is_ok());
+
+        let problems = check_preset(&entries, &preset(&[&first, &second])).unwrap_err();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("&lt;b&gt;First&lt;/b&gt;"));
+    }
+
+    #[test]
+    fn presets_missing_dependencies_are_refused() {
+        let dependency = entry("Dependency");
+        let mut dependent = entry("Dependent");
+        dependent.dependencies.push(dependency.id.unwrap());
+        dependent.missing_dependencies.push(String::from("Uninstalled & gone"));
+
+        let entries = [dependency.clone(), dependent.clone()];
+        let problems = check_preset(&entries, &preset(&[&dependent])).unwrap_err();
+
+        assert_eq!(problems, ["Dependent needs Dependency, which is disabled", "Dependent needs Uninstalled &amp; gone, which is not installed"]);
+        assert_eq!(check_preset(&entries, &preset(&[&dependency, &dependent])).unwrap_err().len(), 1);
+    }
+
+    #[test]
+    fn search_matches_names_folders_and_authors() {
+        let mut skins = entry("Skins");
+        skins.display_name = Some(String::from("Alt Costumes"));
+        let mut stage = entry("Battlefield");
+        stage.author = Some(String::from("SkinMaker"));
+        let other = entry("Music");
+
+        let entries = [skins.clone(), stage.clone(), other.clone()];
+
+        assert_eq!(search_mods(&entries, " skin "), [skins.id.unwrap(), stage.id.unwrap()]);
+        assert_eq!(search_mods(&entries, "COSTUME"), [skins.id.unwrap()]);
+        assert_eq!(search_mods(&entries, ""), [skins.id.unwrap(), stage.id.unwrap(), other.id.unwrap()]);
+        assert!(search_mods(&entries, "nothing").is_empty());
+    }
+
+    #[test]
+    fn conflicts_are_linked_both_ways() {
+        let mut moveset = entry("Moveset");
+        moveset.conflicts_with = vec![String::from("OTHER MOVESET"), String::from("Not Installed"), String::from("Moveset")];
+        let other = entry("Other Moveset");
+        let skins = entry("Skins");
+
+        let mut entries = [moveset.clone(), other.clone(), skins.clone()];
+        link_conflicts(&mut entries);
+
+        // Only one of the two mods declared the conflict, enabling either of them prompts to disable the other
+        assert_eq!(entries[0].conflicts, [other.id.unwrap()]);
+        assert_eq!(entries[1].conflicts, [moveset.id.unwrap()]);
+        assert!(entries[2].conflicts.is_empty());
+    }
+
+    #[test]
+    fn only_replaced_files_can_conflict() {
+        assert_eq!(replaced_path(None, "ui/param/database/ui_chara_db.prc", "us_en").as_deref(), Some("ui/param/database/ui_chara_db.prc"));
+        // Merged with the files of the other mods
+        assert_eq!(replaced_path(None, "ui/param/database/ui_chara_db.prcxml", "us_en"), None);
+        assert_eq!(replaced_path(None, "ui/message/msg_name.xmsbt", "us_en"), None);
+
+        // Regional variants stand for the file they replace, unless they are for another region
+        assert_eq!(replaced_path(None, "ui/message/msg_name+us_en.msbt", "us_en").as_deref(), Some("ui/message/msg_name.msbt"));
+        assert_eq!(replaced_path(None, "ui/message/msg_name+region+.msbt", "us_en").as_deref(), Some("ui/message/msg_name.msbt"));
+        assert_eq!(replaced_path(None, "ui/message/msg_name+jp_ja.msbt", "us_en"), None);
+
+        let based = replaced_path(Some("fighter/mario"), "model/body/c00/model.numdlb", "us_en");
+        assert_eq!(based.as_deref(), Some("fighter/mario/model/body/c00/model.numdlb"));
+    }
+
+    #[test]
+    fn ids_match_the_preset_keys() {
+        let path = "sd:/ultimate/mods/Skins";
+        assert_eq!(stable_mod_id(path), Hash40::from(path).0);
+    }
+}
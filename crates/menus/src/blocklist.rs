@@ -0,0 +1,7 @@
+/// Tells the user a mod was blocked for being incompatible, and asks whether to load it anyway
+pub fn confirm_blocked_mod(folder_name: &str, reason: &str) -> bool {
+    skyline_web::dialog::Dialog::yes_no(format!(
+        "The mod '{}' is known to cause problems and has been disabled.<br><br>{}<br><br>Would you like to load it anyway? This will not be asked again.",
+        folder_name, reason
+    ))
+}
@@ -0,0 +1,52 @@
+use std::sync::RwLock;
+
+use serde::Deserialize;
+use skyline_web::Webpage;
+
+/// Runs a line typed in the console and returns what should be printed back
+pub type ConsoleHandler = Box<dyn Fn(&str) -> String + Send + Sync>;
+
+static CONSOLE_HANDLER: RwLock<Option<ConsoleHandler>> = RwLock::new(None);
+
+#[derive(Debug, Deserialize)]
+pub enum ConsoleMessage {
+    Command { line: String },
+    Closure,
+}
+
+/// Sets what runs the commands typed in the console, since the state they query lives outside of the menus
+pub fn set_console_handler(handler: impl Fn(&str) -> String + Send + Sync + 'static) {
+    *CONSOLE_HANDLER.write().unwrap() = Some(Box::new(handler));
+}
+
+pub fn show_console() {
+    if CONSOLE_HANDLER.read().unwrap().is_none() {
+        skyline_web::dialog_ok::DialogOk::ok("The console is not available right now.");
+        return;
+    }
+
+    let session = Webpage::new()
+        .htdocs_dir("contents")
+        .file("index.html", &crate::files::CONSOLE_HTML_TEXT)
+        .file("menu.css", &crate::files::MENU_CSS_TEXT)
+        .file("console.js", &crate::files::CONSOLE_JAVASCRIPT_TEXT)
+        .file("common.js", &crate::files::COMMON_JAVASCRIPT_TEXT)
+        .background(skyline_web::Background::Default)
+        .boot_display(skyline_web::BootDisplay::Default)
+        .open_session(skyline_web::Visibility::Default)
+        .unwrap();
+
+    while let Ok(message) = session.recv_json::<ConsoleMessage>() {
+        match message {
+            ConsoleMessage::Command { line } => {
+                let output = CONSOLE_HANDLER.read().unwrap().as_ref().map_or_else(String::new, |handler| handler(&line));
+                session.send(&serde_json::json!({ "output": output }).to_string());
+            },
+            ConsoleMessage::Closure => {
+                session.exit();
+                session.wait_for_exit();
+                break;
+            },
+        }
+    }
+}
@@ -1,3 +1,5 @@
+use log::warn;
+
 pub static COMMON_JAVASCRIPT_TEXT: &str = include_str!("../../resources/js/common.js");
 pub static COMMON_CSS_TEXT: &str = include_str!("../../resources/css/common.css");
 
@@ -21,7 +23,35 @@ pub static WORKSPACES_HTML_TEXT: &str = include_str!("../../resources/templates/
 pub static WORKSPACES_CSS_TEXT: &str = include_str!("../../resources/css/workspaces.css");
 pub static WORKSPACES_JAVASCRIPT_TEXT: &str = include_str!("../../resources/js/workspaces.js");
 
+pub static REBOOT_HTML_TEXT: &str = include_str!("../../resources/templates/reboot.html");
+pub static REBOOT_JAVASCRIPT_TEXT: &str = include_str!("../../resources/js/reboot.js");
+
+pub static CONSOLE_HTML_TEXT: &str = include_str!("../../resources/templates/console.html");
+pub static CONSOLE_JAVASCRIPT_TEXT: &str = include_str!("../../resources/js/console.js");
+
 pub static CHECK_SVG: &[u8] = include_bytes!("../../resources/img/check.svg");
 pub static MARQUEE_JS: &str = include_str!("../../resources/js/jquery.marquee.min.js");
 pub static PAGINATION_JS: &str = include_str!("../../resources/js/pagination.min.js");
 pub static MISSING_WEBP: &[u8] = include_bytes!("../../resources/img/missing.webp");
+
+/// Gets the user's version of a stylesheet from the theme folder, or the embedded one if there is none.
+/// Overrides that can't be read or are empty are ignored, so a broken theme never leaves the menu unstyled.
+pub fn themed(name: &str, default: &'static str) -> String {
+    let path = crate::utils::paths::theme().join(name);
+
+    if !path.exists() {
+        return default.to_string();
+    }
+
+    match std::fs::read_to_string(&path) {
+        Ok(contents) if !contents.trim().is_empty() => contents,
+        Ok(_) => {
+            warn!("The theme override '{}' is empty, using the default one instead.", path);
+            default.to_string()
+        },
+        Err(e) => {
+            warn!("Failed to read the theme override '{}', using the default one instead. Reason: {}", path, e);
+            default.to_string()
+        },
+    }
+}
@@ -10,6 +10,15 @@ pub mod changelog;
 pub use changelog::*;
 pub mod files;
 pub use files::*;
+pub mod reboot;
+pub use reboot::*;
+pub mod console;
+pub use console::*;
+pub mod blocklist;
+pub use blocklist::*;
+pub mod recovery;
+pub mod summary;
+pub use summary::*;
 use skyline_web::Webpage;
 
 mod utils;
@@ -40,6 +49,9 @@ pub fn show_main_menu() {
             "http://localhost/config" => {
                 show_config_editor(&mut ::config::GLOBAL_CONFIG.lock().unwrap());
             },
+            "http://localhost/console" => {
+                show_console();
+            },
             _ => {},
         },
     }
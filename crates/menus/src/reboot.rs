@@ -0,0 +1,21 @@
+use skyline_web::Webpage;
+
+/// Gives the user a few seconds to back out before relaunching the game.
+/// Cancelling leaves everything as is, so whatever was saved beforehand is applied on the next boot instead.
+pub fn reboot_with_countdown() {
+    let response = Webpage::new()
+        .htdocs_dir("contents")
+        .file("index.html", &crate::files::REBOOT_HTML_TEXT)
+        .file("menu.css", &crate::files::MENU_CSS_TEXT)
+        .file("reboot.js", &crate::files::REBOOT_JAVASCRIPT_TEXT)
+        .file("common.js", &crate::files::COMMON_JAVASCRIPT_TEXT)
+        .background(skyline_web::Background::Default)
+        .boot_display(skyline_web::BootDisplay::Default)
+        .open()
+        .unwrap();
+
+    match response.get_last_url().unwrap() {
+        "http://localhost/reboot" => unsafe { skyline::nn::oe::RequestToRelaunchApplication() },
+        _ => skyline_web::dialog_ok::DialogOk::ok("The reboot has been cancelled.<br>Your changes will take effect on the next boot."),
+    }
+}
@@ -0,0 +1,57 @@
+use log::error;
+
+/// Backs up then resets the whole configuration to the defaults. Tells the user where the backup went, or why it failed.
+pub fn reset_config() -> bool {
+    match ::config::recovery::reset_config() {
+        Ok(backup) => {
+            skyline_web::dialog_ok::DialogOk::ok(format!(
+                "Your configuration has been reset to the defaults.<br>A backup of the previous one has been saved to {}.",
+                backup.display()
+            ));
+            true
+        },
+        Err(e) => {
+            error!("Failed to reset the configuration. Reason: {}", e);
+            skyline_web::dialog_ok::DialogOk::ok(format!("Failed to reset your configuration, nothing was changed.<br>{}", e));
+            false
+        },
+    }
+}
+
+/// Backs up the configuration then disables every mod of a workspace. Tells the user where the backup went, or why it failed.
+pub fn clear_preset(workspace_name: &str) -> bool {
+    match ::config::recovery::clear_preset(workspace_name) {
+        Ok(backup) => {
+            skyline_web::dialog_ok::DialogOk::ok(format!(
+                "Every mod of workspace {} has been disabled.<br>A backup of your previous configuration has been saved to {}.",
+                workspace_name,
+                backup.display()
+            ));
+            true
+        },
+        Err(e) => {
+            error!("Failed to clear the preset of workspace {}. Reason: {}", workspace_name, e);
+            skyline_web::dialog_ok::DialogOk::ok(format!("Failed to clear the preset of workspace {}.<br>{}", workspace_name, e));
+            false
+        },
+    }
+}
+
+/// Offered at boot for when a broken configuration or mod keeps the game from getting to the menus
+pub fn show_safe_mode() {
+    if skyline_web::dialog::Dialog::no_yes(
+        "ARCropolis safe mode<br><br>Would you like to reset your configuration to the defaults?<br>Every workspace and preset will be lost, but a backup will be made first.",
+    ) {
+        reset_config();
+        return;
+    }
+
+    let workspace_name = ::config::workspaces::get_active_workspace_name().unwrap_or_else(|_| String::from("Default"));
+
+    if skyline_web::dialog::Dialog::no_yes(format!(
+        "Would you like to disable every mod of your active workspace ({}) instead?<br>A backup of your configuration will be made first.",
+        workspace_name
+    )) {
+        clear_preset(&workspace_name);
+    }
+}
@@ -0,0 +1,4 @@
+/// Tells the user what happened while the game was booting, such as how many mods were loaded
+pub fn show_boot_summary(summary: &str) {
+    skyline_web::dialog_ok::DialogOk::ok(format!("ARCropolis<br><br>{}", summary));
+}
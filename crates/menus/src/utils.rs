@@ -64,22 +64,86 @@ pub mod paths {
     }
 
     pub fn mods() -> Utf8PathBuf {
-        Utf8PathBuf::from("sd:/ultimate/mods")
+        match ::config::mods_path_override() {
+            Some(path) => Utf8PathBuf::from(path),
+            None => Utf8PathBuf::from("sd:/ultimate/mods"),
+        }
+    }
+
+    /// The additional mods directories from the configuration that exist on the SD
+    pub fn extra_mods() -> Vec<Utf8PathBuf> {
+        ::config::extra_mods_paths().into_iter().map(Utf8PathBuf::from).filter(|path| path.is_dir()).collect()
+    }
+
+    pub fn modpack_cache() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/modpacks")
     }
 
     pub fn config() -> Utf8PathBuf {
         Utf8PathBuf::from("sd:/ultimate/arcropolis/config")
     }
 
+    /// Folder where users can drop stylesheets to use instead of the ones embedded in the menus
+    pub fn theme() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/theme")
+    }
+
     pub fn logs() -> Utf8PathBuf {
         Utf8PathBuf::from("sd:/ultimate/arcropolis/logs")
     }
 
+    /// Lists of the files replaced by a mod, exported from ARCadia to be shared
+    pub fn manifests() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/manifests")
+    }
+
+    /// Name of the folder of the web pages that holds the mod previews, the only one ARCropolis ever clears
+    pub static PREVIEW_CACHE_NAME: &str = "arcadia-previews";
+
+    /// Folder the web applet serves the menu pages from, which is where the files given to the pages are written
+    pub fn web_htdocs() -> Utf8PathBuf {
+        Utf8PathBuf::from(::config::web_contents_base().unwrap_or_else(|| String::from("sd:/atmosphere/contents")))
+            .join(format!("{:016X}", skyline::info::get_program_id()))
+            .join("manual_html/html-document/contents.htdocs")
+    }
+
+    pub fn preview_cache() -> Utf8PathBuf {
+        web_htdocs().join(PREVIEW_CACHE_NAME)
+    }
+
     pub fn cache() -> Utf8PathBuf {
         Utf8PathBuf::from("sd:/ultimate/arcropolis/cache").join(get_game_version().to_string())
     }
 }
 
+pub mod storage {
+    /// Room left on top of the data being written, so the SD isn't filled to the last byte
+    const SAFETY_MARGIN: u64 = 0x100000;
+
+    extern "C" {
+        #[link_name = "_ZN2nn2fs16GetFreeSpaceSizeEPlPKc"]
+        fn get_free_space_size(out_size: *mut i64, path: *const u8) -> u32;
+    }
+
+    /// Queries how many bytes are left on the SD card
+    pub fn sd_free_space() -> Option<u64> {
+        let mut size = 0;
+
+        match unsafe { get_free_space_size(&mut size, "sd:/\0".as_ptr()) } {
+            0 => Some(size as u64),
+            result => {
+                log::warn!("Failed to query the free space of the SD card. Result: {:#x}", result);
+                None
+            },
+        }
+    }
+
+    /// Checks if `size` bytes can be written to the SD card. If the free space can't be queried, the write is allowed, as it would be without this check.
+    pub fn has_room_for(size: u64) -> bool {
+        sd_free_space().map_or(true, |free| free >= size + SAFETY_MARGIN)
+    }
+}
+
 pub mod save {
     use super::*;
     use smash_arc::Region;
@@ -1,6 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
 use ::config::GLOBAL_CONFIG;
+use log::error;
 use serde::{Deserialize, Serialize};
 use skyline_web::Webpage;
 use smash_arc::Hash40;
@@ -11,6 +12,12 @@ use crate::config;
 pub struct Information {
     workspaces: Vec<String>,
     active_workspace: String,
+    /// Workspace each workspace inherits its mods from, if any
+    parents: HashMap<String, String>,
+    /// Settings each workspace sets for itself, layered over the global configuration while it is active
+    overrides: HashMap<String, HashMap<String, String>>,
+    /// Settings a workspace is allowed to set
+    overridable: Vec<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,18 +28,68 @@ pub enum WorkspacesMessage {
     Rename { source_name: String, target_name: String },
     Remove { name: String },
     Duplicate { source_name: String, target_name: String },
+    SetParent { name: String, parent: Option<String> },
+    /// Sets a setting for a workspace, or has it use the global one again without a value
+    SetOverride { name: String, key: String, value: Option<String> },
     ClosureRequest,
 }
 
+#[derive(Serialize, Debug)]
+pub struct WorkspaceSummary {
+    pub name: String,
+    /// Amount of installed mods enabled in this workspace
+    pub enabled: usize,
+    /// Amount of mods enabled in this workspace that are no longer installed
+    pub stale: usize,
+    pub active: bool,
+}
+
+/// Lists every workspace along with how many mods it enables. `installed` holds the hashes of the installed mod folders, so that the mods
+/// only have to be listed once no matter how many workspaces there are.
+pub fn get_workspace_summaries(installed: &HashSet<Hash40>) -> Vec<WorkspaceSummary> {
+    let active_workspace = ::config::workspaces::get_active_workspace_name().unwrap_or_else(|_| "Default".to_string());
+
+    let mut summaries: Vec<WorkspaceSummary> = ::config::workspaces::get_list()
+        .unwrap_or_default()
+        .into_keys()
+        .map(|name| {
+            let preset = ::config::presets::get_preset(&name).unwrap_or_default();
+            let enabled = preset.iter().filter(|hash| installed.contains(hash)).count();
+
+            WorkspaceSummary {
+                active: name == active_workspace,
+                stale: preset.len() - enabled,
+                enabled,
+                name,
+            }
+        })
+        .collect();
+
+    summaries.sort_by(|a, b| a.name.cmp(&b.name));
+    summaries
+}
+
 pub fn show_workspaces() {
+    open_workspaces(false)
+}
+
+/// Lets the user pick the workspace to load while the mods are being discovered, which doesn't need a reboot to take effect
+pub fn show_workspaces_on_boot() {
+    open_workspaces(true)
+}
+
+fn open_workspaces(on_boot: bool) {
     let mut storage = GLOBAL_CONFIG.lock().unwrap();
-    let mut active_workspace: String = storage.get_field("workspace").unwrap_or_else(|_| "Default".to_string());
+    let mut active_workspace: String = ::config::workspaces::read_active_workspace_name(&*storage).unwrap_or_else(|_| "Default".to_string());
     let prev_set_workspace: String = active_workspace.clone();
     let mut workspace_list: HashMap<String, String> = storage.get_field_json("workspace_list").unwrap_or_default();
 
     let info: Information = Information {
         workspaces: workspace_list.iter().map(|(k, _v)| k.clone()).collect(),
         active_workspace: active_workspace.clone(),
+        parents: ::config::workspaces::read_parents(&*storage),
+        overrides: ::config::overrides::read_all(&*storage),
+        overridable: ::config::overrides::KEYS.iter().map(|key| key.to_string()).collect(),
     };
 
     let mut workspace_to_edit: Option<String> = None;
@@ -60,7 +117,7 @@ pub fn show_workspaces() {
             },
             WorkspacesMessage::SetActive { name } => {
                 active_workspace = name.clone();
-                storage.set_field("workspace", name).unwrap();
+                ::config::workspaces::write_active_workspace_name(&mut *storage, name).unwrap();
             },
             WorkspacesMessage::Edit { name } => {
                 session.wait_for_exit();
@@ -72,23 +129,72 @@ pub fn show_workspaces() {
             WorkspacesMessage::Rename { source_name, target_name } => {
                 let preset_name = workspace_list[&source_name].clone();
                 workspace_list.remove(&source_name);
-                workspace_list.insert(target_name, preset_name);
+                workspace_list.insert(target_name.clone(), preset_name);
                 storage.set_field_json("workspace_list", &workspace_list).unwrap_or_default();
+
+                if let Err(e) = ::config::workspaces::relink_parents(&mut *storage, &source_name, Some(&target_name)) {
+                    error!("Failed to update the inheritance of workspace {}. Reason: {}", source_name, e);
+                }
+
+                if let Err(e) = ::config::overrides::relink(&mut *storage, &source_name, Some(&target_name)) {
+                    error!("Failed to move the settings of workspace {}. Reason: {}", source_name, e);
+                }
+
+                if let Err(e) = ::config::priorities::relink(&mut *storage, &source_name, Some(&target_name)) {
+                    error!("Failed to move the mod priorities of workspace {}. Reason: {}", source_name, e);
+                }
             },
             WorkspacesMessage::Remove { name } => {
                 workspace_list.remove(&name);
                 storage.set_field_json("workspace_list", &workspace_list).unwrap_or_default();
+
+                if let Err(e) = ::config::workspaces::relink_parents(&mut *storage, &name, None) {
+                    error!("Failed to update the inheritance of workspace {}. Reason: {}", name, e);
+                }
+
+                if let Err(e) = ::config::overrides::relink(&mut *storage, &name, None) {
+                    error!("Failed to remove the settings of workspace {}. Reason: {}", name, e);
+                }
+
+                if let Err(e) = ::config::priorities::relink(&mut *storage, &name, None) {
+                    error!("Failed to remove the mod priorities of workspace {}. Reason: {}", name, e);
+                }
             },
             WorkspacesMessage::Duplicate { source_name, target_name } => {
-                let source_preset_name = &workspace_list[&source_name];
                 let target_preset_name = format!("{}_preset{}", target_name, workspace_list.len() + 1);
 
-                let presets: HashSet<Hash40> = storage.get_field_json(source_preset_name).unwrap_or_default();
+                // The duplicate stands on its own, so it gets the mods the source inherits as well
+                let presets: HashSet<Hash40> = ::config::presets::read_preset(&*storage, &source_name).unwrap_or_default();
+
+                if let Err(e) = ::config::overrides::copy(&mut *storage, &source_name, &target_name) {
+                    error!("Failed to copy the settings of workspace {}. Reason: {}", source_name, e);
+                }
+
+                if let Err(e) = ::config::priorities::copy(&mut *storage, &source_name, &target_name) {
+                    error!("Failed to copy the mod priorities of workspace {}. Reason: {}", source_name, e);
+                }
 
                 workspace_list.insert(target_name, target_preset_name.clone());
                 storage.set_field_json(target_preset_name, &presets).unwrap();
                 storage.set_field_json("workspace_list", &workspace_list).unwrap_or_default();
             },
+            WorkspacesMessage::SetParent { name, parent } => {
+                // Also checked by the page, but a cycle would leave the workspaces without a preset to start from
+                if let Err(e) = ::config::workspaces::write_parent(&mut *storage, &name, parent.as_deref()) {
+                    error!("Failed to set the parent of workspace {}. Reason: {}", name, e);
+                }
+            },
+            WorkspacesMessage::SetOverride { name, key, value } => {
+                // Also checked by the page, the others aren't read through the overrides
+                if !::config::overrides::KEYS.contains(&key.as_str()) {
+                    error!("Workspace {} tried to override '{}', which can't be set per workspace.", name, key);
+                    continue;
+                }
+
+                if let Err(e) = ::config::overrides::write(&mut *storage, &name, &key, value.as_deref()) {
+                    error!("Failed to save the settings of workspace {}. Reason: {}", name, e);
+                }
+            },
             WorkspacesMessage::ClosureRequest => {
                 session.wait_for_exit();
                 session.exit();
@@ -100,7 +206,7 @@ pub fn show_workspaces() {
 
     if !workspace_list.contains_key(&active_workspace) {
         active_workspace = "Default".to_string();
-        storage.set_field("workspace", active_workspace.clone()).unwrap();
+        ::config::workspaces::write_active_workspace_name(&mut *storage, active_workspace.clone()).unwrap();
     }
 
     drop(storage);
@@ -110,7 +216,7 @@ pub fn show_workspaces() {
         crate::arcadia::show_arcadia(Some(s))
     }
 
-    if active_workspace.ne(&prev_set_workspace) {
+    if active_workspace.ne(&prev_set_workspace) && !on_boot {
         // if let Some(_filesystem) = crate::GLOBAL_FILESYSTEM.try_read() {
             if skyline_web::dialog::Dialog::yes_no(format!("Your active workspace has successfully been changed to {}!<br>Your changes will take effect on the next boot.<br>Would you like to reboot the game to reload your mods?", active_workspace)) {
                 unsafe { skyline::nn::oe::RequestToRelaunchApplication() };
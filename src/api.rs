@@ -3,6 +3,7 @@ pub mod event;
 pub mod file;
 pub mod utils;
 pub mod lua;
+pub mod replace;
 
 pub use callback::*;
 
@@ -20,7 +21,7 @@ pub struct ApiVersion {
 /// Do your changes only add new APIs in a backwards compatible way: Minor bump
 ///
 /// Are your changes only internal? No version bump
-static API_VERSION: ApiVersion = ApiVersion { major: 1, minor: 9 };
+static API_VERSION: ApiVersion = ApiVersion { major: 1, minor: 11 };
 
 #[no_mangle]
 pub extern "C" fn arcrop_api_version() -> &'static ApiVersion {
@@ -41,11 +42,13 @@ pub extern "C" fn arcrop_require_api_version(major: u32, minor: u32) {
 fn show_arcrop_update_prompt() -> ! {
     skyline::error::show_error(69, "ARCropolis API error, press Details.\0", "Your ARCropolis version is older than one of your plugins supports, an update is required");
 
+    crate::stop_background_threads();
     unsafe { skyline::nn::oe::ExitApplication() }
 }
 
 fn show_plugin_update_prompt() -> ! {
     skyline::error::show_error(69, "ARCropolis API error, press Details.\0", "Your ARCropolis version is too new for one of your plugins, it must be updated to support this API version");
 
+    crate::stop_background_threads();
     unsafe { skyline::nn::oe::ExitApplication() }
 }
@@ -1,4 +1,4 @@
-use std::collections::{HashMap, HashSet};
+use std::collections::HashSet;
 
 use owo_colors::OwoColorize;
 use smash_arc::*;
@@ -55,15 +55,49 @@ pub extern "C" fn arcrop_is_file_loaded(hash: Hash40) -> bool {
     if !resource::initialized() {
         false
     } else {
-        let arc = resource::arc();
-        let filesystem_info = resource::filesystem_info();
-        match arc.get_file_path_index_from_hash(hash) {
-            Ok(file_path_index) => filesystem_info.get_loaded_filepaths()[file_path_index.0 as usize].is_loaded == 1,
+        match resource::arc().get_file_path_index_from_hash(hash) {
+            Ok(file_path_index) => resource::is_loaded(file_path_index.0 as usize),
             _ => false,
         }
     }
 }
 
+/// Gets the hash of the file path at an index of the loaded filepath table, as found in the structures the game hands to hooks
+#[no_mangle]
+pub extern "C" fn arcrop_get_file_path_hash(file_path_index: u32, out_hash: &mut Hash40) -> bool {
+    debug!("arcrop_get_file_path_hash -> Received index {:#x}", file_path_index);
+
+    match resource::get_hash_from_t1_index(file_path_index as usize) {
+        Some(hash) => {
+            *out_hash = hash;
+            true
+        },
+        None => false,
+    }
+}
+
+/// Gets the load state of the data of a file, as the value of the game's `LoadState` (0: unused, 1: unloaded, 2: unknown, 3: loaded)
+#[no_mangle]
+pub extern "C" fn arcrop_get_file_load_state(hash: Hash40, out_state: &mut u8) -> bool {
+    debug!("arcrop_get_file_load_state -> Received hash {} ({:#x})", hashes::find(hash).green(), hash.0);
+
+    if !resource::initialized() {
+        return false;
+    }
+
+    let Ok(file_info) = resource::arc().get_file_info_from_hash(hash) else {
+        return false;
+    };
+
+    match resource::table2().get(usize::from(file_info.file_info_indice_index)) {
+        Some(loaded_data) => {
+            *out_state = loaded_data.state as u8;
+            true
+        },
+        None => false,
+    }
+}
+
 #[no_mangle]
 pub extern "C" fn arcrop_is_mod_enabled(hash: Hash40) -> bool {
     debug!("arcrop_is_mod_enabled -> Received hash {} ({:#x})", hashes::find(hash).green(), hash.0);
@@ -89,7 +123,7 @@ pub extern "C" fn arcrop_is_mod_enabled(hash: Hash40) -> bool {
                         .map(|name| !name.starts_with('.'))
                         .unwrap_or(false)
                     {
-                        Some(Hash40::from(path.to_str().unwrap()))
+                        path.to_str().map(Hash40::from)
                     } else {
                         None
                     }
@@ -99,10 +133,8 @@ pub extern "C" fn arcrop_is_mod_enabled(hash: Hash40) -> bool {
             })
             .collect()
     } else {
-        let workspace_name: String = storage.get_field("workspace").unwrap_or_else(|_| "Default".to_string());
-        let workspace_list: HashMap<String, String> = storage.get_field_json("workspace_list").unwrap_or_default();
-        let preset_name = &workspace_list[&workspace_name];
-        storage.get_field_json(preset_name).unwrap_or_default()
+        let workspace_name: String = config::workspaces::read_active_workspace_name(&*storage).unwrap_or_else(|_| "Default".to_string());
+        config::presets::read_preset(&*storage, &workspace_name).unwrap_or_default()
     };
 
     preset.contains(&hash)
@@ -0,0 +1,201 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, LazyLock, RwLock},
+};
+
+use owo_colors::OwoColorize;
+use smash_arc::Hash40;
+
+use crate::{hashes, resource::TableEntry};
+
+/// Information about a replacement that just landed in the game's loaded data table
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+pub struct TableEntryInfo {
+    pub hash: Hash40,
+    pub filepath_index: u32,
+    pub file_info_indice_index: u32,
+    /// Pointer to the game's buffer holding the replaced data
+    pub data: *const u8,
+    /// Amount of bytes written by ARCropolis
+    pub size: usize,
+    /// Size of the buffer allocated by the game
+    pub buffer_size: usize,
+}
+
+impl TableEntryInfo {
+    /// Describes a replacement of `size` bytes that was written to the buffer of the entry
+    pub fn new(hash: Hash40, filepath_index: u32, entry: &TableEntry, size: usize) -> Self {
+        Self {
+            hash,
+            filepath_index,
+            file_info_indice_index: entry.index() as u32,
+            data: entry.data(),
+            size,
+            buffer_size: entry.size(),
+        }
+    }
+}
+
+/// Transforms the bytes of a file right before they are copied into the game's buffer.
+/// The vector can grow up to the size of the game's buffer, which is given as the second argument.
+pub type PreReplaceFn = Arc<dyn Fn(&mut Vec<u8>, usize) + Send + Sync>;
+/// Called once a replacement has been copied into the game's buffer
+pub type PostReplaceFn = Arc<dyn Fn(&TableEntryInfo) + Send + Sync>;
+
+/// C-facing pre-replace callback. Receives the data, its current length (which can be updated) and the maximum length allowed.
+pub type PreReplaceCallbackFn = extern "C" fn(Hash40, *mut u8, &mut usize, usize);
+/// C-facing post-replace callback
+pub type PostReplaceCallbackFn = extern "C" fn(&TableEntryInfo);
+
+static PRE_REPLACE_CALLBACKS: LazyLock<RwLock<HashMap<Hash40, Vec<PreReplaceFn>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+static POST_REPLACE_CALLBACKS: LazyLock<RwLock<HashMap<Hash40, Vec<PostReplaceFn>>>> = LazyLock::new(|| RwLock::new(HashMap::new()));
+
+/// Registers a transform to run on the data of a file before it is handed to the game.
+/// Transforms registered for the same file run in registration order, each one receiving the output of the previous one.
+///
+/// Callbacks run from the resource loading thread while the filesystem lock is held, which means they must not call
+/// anything that locks it again (such as `arcrop_load_file` or [`install_replacement`]) and should return quickly,
+/// since the game is stalled until they do. Registering more callbacks from a callback is fine, they run from the next replacement on.
+pub fn register_pre_replace(hash: Hash40, callback: impl Fn(&mut Vec<u8>) + Send + Sync + 'static) {
+    register_pre_replace_bounded(hash, move |data, _| callback(data));
+}
+
+/// Same as [`register_pre_replace`], for callbacks that need to know how large the data can grow
+fn register_pre_replace_bounded(hash: Hash40, callback: impl Fn(&mut Vec<u8>, usize) + Send + Sync + 'static) {
+    PRE_REPLACE_CALLBACKS.write().unwrap().entry(hash).or_default().push(Arc::new(callback));
+}
+
+/// Registers a callback to run once a replacement for a file has been written into the game's buffer.
+///
+/// The same constraints as [`register_pre_replace`] apply: the filesystem lock is held while this runs.
+pub fn register_post_replace(hash: Hash40, callback: impl Fn(&TableEntryInfo) + Send + Sync + 'static) {
+    POST_REPLACE_CALLBACKS.write().unwrap().entry(hash).or_default().push(Arc::new(callback));
+}
+
+/// Installs the data of a file right away, to be used the next time the game loads it. This is the imperative counterpart of providing
+/// the file from a mod, for data that is downloaded or generated at runtime. Returns false if the data couldn't be installed, for
+/// example when it would go over the preload budget.
+///
+/// This locks the filesystem, so it must not be called from a callback.
+pub fn install_replacement(hash: Hash40, data: Vec<u8>) -> bool {
+    unsafe { crate::GLOBAL_FILESYSTEM.write().unwrap().install_replacement(hash, data) }
+}
+
+pub fn has_pre_replace(hash: Hash40) -> bool {
+    PRE_REPLACE_CALLBACKS.read().unwrap().contains_key(&hash)
+}
+
+/// Runs every transform registered for the file. `max_size` is the size of the game's buffer.
+pub fn run_pre_replace(hash: Hash40, data: &mut Vec<u8>, max_size: usize) {
+    // The registry is unlocked before running anything so the callbacks can register more of them
+    let callbacks = PRE_REPLACE_CALLBACKS.read().unwrap().get(&hash).cloned().unwrap_or_default();
+
+    if callbacks.is_empty() {
+        return;
+    }
+
+    data.reserve(max_size.saturating_sub(data.len()));
+
+    for callback in callbacks.iter() {
+        callback(data, max_size);
+    }
+}
+
+pub fn run_post_replace(info: &TableEntryInfo) {
+    let callbacks = POST_REPLACE_CALLBACKS.read().unwrap().get(&info.hash).cloned().unwrap_or_default();
+
+    for callback in callbacks.iter() {
+        callback(info);
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn arcrop_register_pre_replace(hash: Hash40, cb: PreReplaceCallbackFn) {
+    debug!(
+        "arcrop_register_pre_replace -> Hash received: {} ({:#x})",
+        hashes::find(hash).green(),
+        hash.0
+    );
+
+    register_pre_replace_bounded(hash, move |data, max_size| {
+        // The allocation can be larger than asked for, but the data must still fit the game's buffer
+        let max_len = data.capacity().min(max_size).max(data.len());
+        let mut len = data.len();
+
+        cb(hash, data.as_mut_ptr(), &mut len, max_len);
+
+        // The callback is trusted to have initialized everything up to the length it reports, but never past what it was given
+        unsafe { data.set_len(len.min(max_len)) };
+    });
+}
+
+/// Copies `length` bytes from `data`, the caller keeps ownership of the buffer
+#[no_mangle]
+pub extern "C" fn arcrop_install_replacement(hash: Hash40, data: *const u8, length: usize) -> bool {
+    debug!(
+        "arcrop_install_replacement -> Hash received: {} ({:#x}), Data len: {:#x}",
+        hashes::find(hash).green(),
+        hash.0,
+        length
+    );
+
+    if data.is_null() {
+        return false;
+    }
+
+    install_replacement(hash, unsafe { std::slice::from_raw_parts(data, length) }.to_vec())
+}
+
+#[no_mangle]
+pub extern "C" fn arcrop_register_post_replace(hash: Hash40, cb: PostReplaceCallbackFn) {
+    debug!(
+        "arcrop_register_post_replace -> Hash received: {} ({:#x})",
+        hashes::find(hash).green(),
+        hash.0
+    );
+
+    register_post_replace(hash, move |info| cb(info));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn callbacks_can_register_callbacks() {
+        let hash = Hash40::from("test/callbacks_can_register_callbacks");
+
+        register_pre_replace(hash, move |data| {
+            data.push(1);
+            register_pre_replace(hash, |data| data.push(2));
+        });
+
+        let mut data = Vec::new();
+        run_pre_replace(hash, &mut data, 0x10);
+        assert_eq!(data, [1]);
+
+        let mut data = Vec::new();
+        run_pre_replace(hash, &mut data, 0x10);
+        assert_eq!(data, [1, 2]);
+    }
+
+    extern "C" fn fill_everything(_: Hash40, data: *mut u8, len: &mut usize, max_len: usize) {
+        unsafe { std::ptr::write_bytes(data.add(*len), 0xFF, max_len - *len) };
+        *len = max_len;
+    }
+
+    #[test]
+    fn c_callbacks_are_bounded_by_the_game_buffer() {
+        let hash = Hash40::from("test/c_callbacks_are_bounded_by_the_game_buffer");
+        arcrop_register_pre_replace(hash, fill_everything);
+
+        // Reserving from a larger allocation must not let the callback go past the buffer of the game
+        let mut data = Vec::with_capacity(0x100);
+        data.extend_from_slice(&[0, 1, 2, 3]);
+        run_pre_replace(hash, &mut data, 0x10);
+
+        assert_eq!(data.len(), 0x10);
+        assert_eq!(&data[..4], [0, 1, 2, 3]);
+    }
+}
@@ -21,3 +21,11 @@ pub extern "C" fn arcrop_show_main_menu() {
     #[cfg(feature = "ui")]
     menus::show_main_menu();
 }
+
+/// Fills `out` with how far along the discovery of the mods is, so a plugin can display it during boot. Returns true while discovery is
+/// still running.
+#[no_mangle]
+pub extern "C" fn arcrop_get_discovery_progress(out: &mut crate::fs::progress::DiscoveryProgress) -> bool {
+    *out = crate::fs::progress::discovery_progress();
+    out.in_progress
+}
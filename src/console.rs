@@ -0,0 +1,97 @@
+//! Commands for the in-game console, a lighter way than the mod manager to check what ARCropolis is doing.
+//!
+//! | Command            | Description                                                       |
+//! |--------------------|-------------------------------------------------------------------|
+//! | `help`             | Lists the commands                                                |
+//! | `hash <path>`      | Hashes a game path the same way mod files are                     |
+//! | `modded <file>`    | Tells if a file is replaced, and by which file of which mod       |
+//! | `resolve <file>`   | Gives the path to the file on the SD that replaces a game file    |
+//! | `stats`            | Shows how many files are replaced and how much space they take    |
+//! | `bypass [on\|off]` | Loads the game's own files instead of the mods, or stops doing so |
+//!
+//! Files can be given either as a game path (`fighter/mario/model/body/c00/model.numdlb`) or as a hash (`0x1234567890`).
+
+use smash_arc::Hash40;
+
+use crate::{hashes, PathExtension, GLOBAL_FILESYSTEM};
+
+static HELP: &str = "help: lists the commands
+hash <path>: hashes a game path
+modded <path or hash>: tells if a file is replaced
+resolve <path or hash>: gives the path on the SD of the file replacing a game file
+stats: shows how many files are replaced
+bypass [on|off]: loads the game's own files instead of the mods, only for files loaded from now on";
+
+fn parse_file(argument: &str) -> Result<Hash40, String> {
+    if let Some(hex) = argument.strip_prefix("0x") {
+        u64::from_str_radix(hex, 16).map(Hash40).map_err(|_| format!("'{}' is not a valid hash.", argument))
+    } else {
+        std::path::Path::new(argument).smash_hash().map_err(|_| format!("'{}' is not a valid path.", argument))
+    }
+}
+
+fn describe(hash: Hash40) -> String {
+    format!("'{}' ({:#x})", hashes::find(hash), hash.0)
+}
+
+fn bypass(argument: &str) -> String {
+    let state = match argument {
+        "" => !config::replacements_bypassed(),
+        "on" => true,
+        "off" => false,
+        _ => return format!("Expected 'on' or 'off', got '{}'.", argument),
+    };
+
+    config::set_replacements_bypassed(state);
+
+    if state {
+        "Replacements bypassed. Files loaded from now on come from the game, reload the scene to see the difference.".to_string()
+    } else {
+        "Replacements enabled. Files loaded from now on come from the mods again.".to_string()
+    }
+}
+
+/// Runs a line typed in the console and returns what to print back
+pub fn handle_command(line: &str) -> String {
+    let (command, argument) = line.split_once(' ').map_or((line, ""), |(command, argument)| (command, argument.trim()));
+
+    // The console can be opened while the filesystem is being built, don't block the menu until it's done
+    let filesystem = match command {
+        "help" => return HELP.to_string(),
+        "hash" => return parse_file(argument).map_or_else(|e| e, |hash| format!("{:#x}", hash.0)),
+        "bypass" => return bypass(argument),
+        "modded" | "resolve" | "stats" => match unsafe { GLOBAL_FILESYSTEM.try_read() } {
+            Ok(filesystem) => filesystem,
+            Err(_) => return "The filesystem is busy, try again once the game is done loading.".to_string(),
+        },
+        _ => return format!("Unknown command '{}'. Type \"help\" to list the available commands.", command),
+    };
+
+    let (count, size) = match (filesystem.get_file_count(), filesystem.get_sum_size()) {
+        (Some(count), Some(size)) => (count, size),
+        _ => return "The filesystem is not initialized yet.".to_string(),
+    };
+
+    match command {
+        "stats" => format!("{} files replaced, {:.2} MB in total.", count, size as f64 / (1024.0 * 1024.0)),
+        _ => {
+            let hash = match parse_file(argument) {
+                Ok(hash) => hash,
+                Err(e) => return e,
+            };
+
+            match (command, filesystem.local_hash(hash)) {
+                (_, None) => format!("{} is not modded.", describe(hash)),
+                ("modded", Some(local)) => format!("{} is modded by '{}'.", describe(hash), local.display()),
+                (_, Some(local)) if config::replacements_bypassed() => {
+                    format!("{} is modded by '{}', but replacements are bypassed.", describe(hash), local.display())
+                },
+                (_, Some(local)) => match filesystem.hash(hash) {
+                    Some(path) => format!("{} resolves to '{}'.", describe(hash), path.display()),
+                    // Patched and API-provided files don't exist on the SD as-is
+                    None => format!("{} is generated by ARCropolis from '{}'.", describe(hash), local.display()),
+                },
+            }
+        },
+    }
+}
@@ -2,10 +2,13 @@ use std::{
     cell::UnsafeCell,
     collections::{HashMap, HashSet},
     fmt,
-    io::Write,
+    io::{self, Read, Write},
     ops::Deref,
     path::{Path, PathBuf},
-    sync::atomic::{AtomicBool, Ordering},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        Mutex,
+    },
 };
 
 use arc_config::{Config as ModConfig, ToExternal, ToSmashArc};
@@ -22,18 +25,189 @@ use crate::{
     resource, PathExtension,
 };
 
+pub mod blocklist;
+#[cfg(feature = "online")]
+pub mod dev_server;
 mod discover;
+pub mod limits;
+#[cfg(feature = "modpack")]
+pub mod modpack;
+pub mod music;
+pub mod progress;
+pub mod rescan;
+pub mod sd_card;
+pub mod template;
+mod ui_database;
+pub mod unmatched;
 mod utils;
 pub use discover::*;
 pub mod loaders;
 pub use loaders::*;
 
 static DEFAULT_CONFIG: &str = include_str!("../resources/override.json");
+/// Maximum amount of memory used to hold preloaded files until the game requests them, along with the data installed through the API
+static PRELOAD_BUDGET: usize = 0x200_0000;
+/// Size of the reads used to stream replacement files into the game's buffers
+static STREAM_CHUNK_SIZE: usize = 0x10_0000;
 static IS_INIT: AtomicBool = AtomicBool::new(false);
 // pub type ApiLoader = StandardLoader; // temporary until an actual ApiLoader is implemented
 
 pub type ArcropolisOrbit = Orbit<ArcLoader, StandardLoader, ApiLoader>;
 
+/// Whether the game currently holds the data of a file in its resource tables
+fn is_file_loaded(hash: Hash40) -> bool {
+    resource::arc()
+        .get_file_path_index_from_hash(hash)
+        .map_or(false, |index| resource::is_loaded(index.0 as usize))
+}
+
+/// Reads a file into the start of the buffer a chunk at a time, so it never has to be held in memory a second time
+fn stream_into(full_path: &Path, buffer: &mut [u8]) -> io::Result<usize> {
+    let mut file = std::fs::File::open(full_path)?;
+    let size = file.metadata()?.len() as usize;
+
+    if size > buffer.len() {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the file is larger than the provided buffer ({:#x} > {:#x})", size, buffer.len()),
+        ));
+    }
+
+    for chunk in buffer[..size].chunks_mut(STREAM_CHUNK_SIZE) {
+        file.read_exact(chunk)?;
+    }
+
+    Ok(size)
+}
+
+/// Deepest a file of the game is nested, used to stop walking up its folders
+const MAX_FOLDER_DEPTH: usize = 32;
+
+/// Lazy namespaces are scanned one at a time, so that a thread requesting a file of a namespace being scanned waits for it to be done
+static LAZY_SCAN: Mutex<()> = Mutex::new(());
+/// Time spent scanning lazy namespaces since boot, to compare against the time they saved during discovery
+static LAZY_SCAN_TIME: AtomicU64 = AtomicU64::new(0);
+
+/// What happened to a file found after boot when mapping it
+enum LateFile {
+    Added,
+    /// Another mod already provides it, or it can't be read
+    Skipped,
+    NotInGame,
+    /// Larger than the file it replaces, while the arc can't be patched
+    TooLarge,
+    /// Larger than what the game can handle for its kind of file
+    OverLimit(limits::OversizedFile),
+}
+
+/// Lists the files found in `directory`, which belongs to the mod at `root`, as their path in the game relative to `base` and their
+/// path on the SD. Only the SD is read, so it doesn't need the filesystem to be locked.
+fn scan_late_files(root: &Path, base: &Path, directory: PathBuf) -> Vec<(PathBuf, PathBuf)> {
+    let region = config::region();
+    let mut files = Vec::new();
+
+    let mut directories = vec![directory];
+
+    while let Some(directory) = directories.pop() {
+        let read_dir = match std::fs::read_dir(&directory) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!("Unable to read '{}' while scanning for files. Reason: {:?}", directory.display(), e);
+                }
+                continue;
+            },
+        };
+
+        for full_path in read_dir.filter_map(|entry| entry.ok().map(|entry| entry.path())) {
+            let name = full_path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+
+            let is_out_of_region = crate::is_out_of_region(name, &region.to_string());
+
+            if name.starts_with('.') || is_out_of_region {
+                continue;
+            }
+
+            if full_path.is_dir() {
+                directories.push(full_path);
+                continue;
+            }
+
+            // Same as during discovery, files at the root of a mod (info.toml, preview...) are not part of the game
+            if full_path.parent() == Some(root) {
+                continue;
+            }
+
+            let local = base.join(full_path.strip_prefix(root).unwrap());
+            files.push((local, full_path));
+        }
+    }
+
+    files
+}
+
+/// Scans the lazy namespace the requested file belongs to, the first time a file from it is requested.
+///
+/// The SD is read without holding the filesystem, so the other loaders keep going in the meantime. The files are mapped the same way
+/// the ones of mods added after boot are: they don't get unshared, and the ones that are larger than the files they replace are left for
+/// the next boot, since the game already sized its buffers.
+pub fn populate_lazy_namespace(hash: Hash40) {
+    let pending = || unsafe { crate::GLOBAL_FILESYSTEM.read().unwrap().pending_lazy_namespace(hash) };
+
+    if pending().is_none() {
+        return;
+    }
+
+    let _scan = LAZY_SCAN.lock().unwrap();
+
+    // Another thread may have scanned it while this one was waiting
+    let (namespace, roots) = match pending() {
+        Some(pending) => pending,
+        None => return,
+    };
+
+    let now = std::time::Instant::now();
+    let files: Vec<(PathBuf, PathBuf)> = roots.iter().flat_map(|root| scan_late_files(root, Path::new(""), root.join(&namespace))).collect();
+
+    let (added, too_large) = unsafe { crate::GLOBAL_FILESYSTEM.write().unwrap().map_lazy_namespace(&namespace, files) };
+
+    if !too_large.is_empty() {
+        record_oversized_lazy_files(&too_large);
+    }
+
+    let elapsed = now.elapsed().as_millis() as u64;
+    let total = LAZY_SCAN_TIME.fetch_add(elapsed, Ordering::SeqCst) + elapsed;
+
+    info!(
+        "Scanned lazy namespace '{}' in {}ms, {} file(s) added. {}ms were spent scanning lazy namespaces since boot.",
+        namespace, elapsed, added, total
+    );
+}
+
+/// Remembers the files of a lazy namespace that are too large to be used this boot, so that their size gets patched on the next one
+fn record_oversized_lazy_files(files: &[PathBuf]) {
+    let record = crate::utils::paths::lazy_oversized_files();
+    let mut contents = std::fs::read_to_string(&record).unwrap_or_default();
+
+    for full_path in files {
+        warn!(
+            "File '{}' is larger than the file it replaces and will only be used after a reboot.",
+            full_path.display()
+        );
+
+        let line = full_path.display().to_string();
+
+        if !contents.lines().any(|existing| existing == line) {
+            contents.push_str(&line);
+            contents.push('\n');
+        }
+    }
+
+    if let Err(e) = std::fs::write(&record, contents) {
+        warn!("Failed to write '{}'. Reason: {:?}", record, e);
+    }
+}
+
 pub struct FilesystemUninitializedError;
 
 impl fmt::Debug for FilesystemUninitializedError {
@@ -52,13 +226,32 @@ pub struct CachedFilesystem {
     current_nus3bank_id: u32,
     nus3banks: HashMap<Hash40, u32>,
     total_size: usize,
+    /// Data of the files that mods asked to preload, keyed by their local path and handed over on the first request
+    preloaded: Mutex<HashMap<PathBuf, Vec<u8>>>,
+    /// Data installed through the API, keyed by local path. Unlike preloaded files, it is handed over every time the game loads the file.
+    installed: HashMap<PathBuf, Vec<u8>>,
+    /// Mod folders present when the filesystem was built or last rescanned
+    known_roots: HashSet<PathBuf>,
+    /// Files that aren't loaded through the discovered tree (mods added after boot, lazy namespaces, paired effect files), keyed by their
+    /// local path, pointing to the file on the SD
+    late_files: HashMap<PathBuf, PathBuf>,
+    /// Lazy namespaces that haven't been requested by the game yet
+    pending_namespaces: HashSet<String>,
+    /// Mod folders to scan when a lazy namespace is requested, from the highest priority to the lowest
+    lazy_roots: Vec<PathBuf>,
+    /// Decompressed size of the modded files before ARCropolis touched the arc, None for the files that mods add to the game
+    vanilla_sizes: HashMap<Hash40, Option<u64>>,
+    /// Sizes the game can't handle for some kinds of files, which late files are checked against as well
+    size_limits: limits::SizeLimits,
+    /// Mods where no file matches a path of the game, which change nothing
+    ineffective_mods: Vec<PathBuf>,
 }
 
 impl CachedFilesystem {
     /// Load all configs that were found during discovery and join them into a singular config
-    fn load_remaining_configs(current: &mut ModConfig, launchpad: &LaunchPad<StandardLoader>) {
-        for (root, local) in launchpad.collected_paths().iter() {
-            let full_path = root.join(local);
+    fn load_remaining_configs(current: &mut ModConfig, collected: &[ModFile]) {
+        for file in collected.iter() {
+            let full_path = &file.full_path;
             if !full_path.exists() {
                 warn!("Collected path at {} does not exist.", full_path.display());
                 continue;
@@ -81,9 +274,10 @@ impl CachedFilesystem {
     }
 
     /// Get a list of all PRC patch files and add them to the virtual tree
-    fn initialize_prc_patches(launchpad: &LaunchPad<StandardLoader>, api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+    fn initialize_prc_patches(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
         let mut set = HashSet::new();
-        for (root, path) in launchpad.collected_paths().iter() {
+        for file in collected.iter() {
+            let path = &file.local;
             // The collected paths gives us everything so we only want these extensions
             if path.has_extension("prcx")
                 || path.has_extension("prcxml")
@@ -92,7 +286,7 @@ impl CachedFilesystem {
                 || path.has_extension("stprmx")
                 || path.has_extension("stprmxml")
             {
-                if let Some(hash) = utils::add_prc_patch(api_tree, root, path) {
+                if let Some(hash) = utils::add_prc_patch(api_tree, &file.full_path, path) {
                     set.insert(hash);
                 }
             }
@@ -101,12 +295,13 @@ impl CachedFilesystem {
     }
 
     /// Get a list of all MSBT patch files and add them to the virtual tree
-    fn initialize_msbt_patches(launchpad: &LaunchPad<StandardLoader>, api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+    fn initialize_msbt_patches(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
         let mut set = HashSet::new();
-        for (root, path) in launchpad.collected_paths().iter() {
+        for file in collected.iter() {
+            let path = &file.local;
             // The collected paths gives us everything so we only want these extensions
             if path.has_extension("xmsbt") {
-                if let Some(hash) = utils::add_msbt_patch(api_tree, root, path) {
+                if let Some(hash) = utils::add_msbt_patch(api_tree, &file.full_path, path) {
                     set.insert(hash);
                 }
             }
@@ -115,12 +310,13 @@ impl CachedFilesystem {
     }
 
     /// Get a list of all nus3audio patch files and add them to the virtual tree
-    fn initialize_nus3audio_patches(launchpad: &LaunchPad<StandardLoader>, api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+    fn initialize_nus3audio_patches(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
         let mut set = HashSet::new();
-        for (root, path) in launchpad.collected_paths().iter() {
+        for file in collected.iter() {
+            let path = &file.local;
             // The collected paths gives us everything so we only want these extensions
             if path.has_extension("patch3audio") {
-                if let Some(hash) = utils::add_nus3audio_patch(api_tree, root, path) {
+                if let Some(hash) = utils::add_nus3audio_patch(api_tree, &file.full_path, path) {
                     set.insert(hash);
                 }
             }
@@ -129,12 +325,13 @@ impl CachedFilesystem {
     }
 
     /// Get a list of all motion list patch files and add them to the virtual tree
-    fn initialize_motionlist_patches(launchpad: &LaunchPad<StandardLoader>, api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+    fn initialize_motionlist_patches(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
         let mut set = HashSet::new();
-        for (root, path) in launchpad.collected_paths().iter() {
+        for file in collected.iter() {
+            let path = &file.local;
             // The collected paths gives us everything so we only want these extensions
             if path.has_extension("motdiff") || path.ends_with("motion_list.yml") {
-                if let Some(hash) = utils::add_motionlist_patch(api_tree, root, path) {
+                if let Some(hash) = utils::add_motionlist_patch(api_tree, &file.full_path, path) {
                     set.insert(hash);
                 }
             }
@@ -143,12 +340,58 @@ impl CachedFilesystem {
     }
 
     /// Get a list of all bgm_property files and add them to the virtual tree
-    fn initialize_bgm_property_patches(launchpad: &LaunchPad<StandardLoader>, api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+    fn initialize_bgm_property_patches(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
         let mut set = HashSet::new();
-        for (root, path) in launchpad.collected_paths().iter() {
+        for file in collected.iter() {
+            let path = &file.local;
             // The collected paths gives us everything so we only want these extensions
             if path.file_name() == Path::new("bgm_property.bin").file_name() {
-                if let Some(hash) = utils::add_bgm_property_patch(api_tree, root, path) {
+                if let Some(hash) = utils::add_bgm_property_patch(api_tree, &file.full_path, path) {
+                    set.insert(hash);
+                }
+            }
+        }
+        set
+    }
+
+    /// Get a list of all the tracks that mods add and route the files they are added to through the virtual tree
+    fn initialize_music_tracks(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+        for file in collected.iter() {
+            let path = &file.local;
+            if !path.has_extension("music") {
+                continue;
+            }
+
+            match music::load_track(&file.full_path, path) {
+                Ok(track) => api_tree.loader.insert_music_track(track),
+                Err(e) => error!("Failed to load track '{}'. Reason: {}", file.full_path.display(), e),
+            }
+        }
+
+        if !api_tree.loader.has_music_tracks() {
+            return HashSet::new();
+        }
+
+        // Files already patched by a mod go through the loader either way
+        let mut set = HashSet::new();
+
+        for (root, local) in [("api:/patch-prc", music::UI_BGM_DB), ("api:/patch-bgm_property", music::BGM_PROPERTY)] {
+            if api_tree.loader.is_plain(Hash40::from(local)) {
+                set.extend(utils::add_file_to_api_tree(api_tree, root, local, ApiCallback::None));
+            }
+        }
+
+        set
+    }
+
+    /// Get a list of all binary diff files and add them to the virtual tree
+    fn initialize_binary_patches(collected: &[ModFile], api_tree: &mut Tree<ApiLoader>) -> HashSet<Hash40> {
+        let mut set = HashSet::new();
+        for file in collected.iter() {
+            let path = &file.local;
+            // The collected paths gives us everything so we only want these extensions
+            if path.has_extension("bsdiff") {
+                if let Some(hash) = utils::add_binary_patch(api_tree, &file.full_path, path) {
                     set.insert(hash);
                 }
             }
@@ -191,6 +434,120 @@ impl CachedFilesystem {
         // individual files
         let (mut hashed_sizes, mut hashed_paths) = utils::make_hash_maps(launchpad.tree());
 
+        let based = discover::take_based_files();
+
+        // Mods laid out under a base come last in the discovery order, so they lose the files they share with the other mods
+        let mut based_late_files = HashMap::new();
+
+        for file in based.files {
+            let hash = match file.local.smash_hash() {
+                Ok(hash) => hash,
+                Err(_) => continue,
+            };
+
+            if let Some(kept) = hashed_paths.get(&hash) {
+                warn!("File '{}' was rejected for file '{}' during discovery.", file.full_path.display(), kept.display());
+                continue;
+            }
+
+            let size = match std::fs::metadata(&file.full_path) {
+                Ok(metadata) => metadata.len() as usize,
+                Err(_) => continue,
+            };
+
+            // Most likely a wrong base rather than a file the mod means to add
+            if !file.local.is_stream() && arc.get_file_path_index_from_hash(hash).is_err() {
+                warn!("'{}' maps to a file which does not exist in the game.", file.full_path.display());
+            }
+
+            hashed_sizes.insert(hash, size);
+            hashed_paths.insert(hash, file.local.clone());
+            based_late_files.insert(file.local, file.full_path);
+        }
+
+        // Patches and other set aside files, of the discovered mods first and of the mods laid out under a base after them
+        let collected: Vec<ModFile> = launchpad
+            .collected_paths()
+            .iter()
+            .map(|(root, local)| ModFile::new(root, local))
+            .chain(based.collected)
+            .collect();
+
+        // Files of the groups that were disabled from the mod manager. The tree already settled conflicts by then, so a disabled file is
+        // left to the game rather than to the next mod providing it.
+        for local in utils::find_disabled_group_files(launchpad.tree(), &discover::take_disabled_group_patterns()) {
+            if let Ok(hash) = local.smash_hash() {
+                debug!("Skipping '{}' since its file group is disabled", local.display());
+                hashed_sizes.remove(&hash);
+                hashed_paths.remove(&hash);
+            }
+        }
+
+        // The game is left with its own file rather than one it would corrupt its memory with
+        let size_limits = config::size_limits();
+        let oversized = limits::find_oversized_files(&size_limits, &hashed_sizes, &hashed_paths);
+
+        for file in oversized.iter() {
+            hashed_sizes.remove(&file.hash);
+            hashed_paths.remove(&file.hash);
+        }
+
+        limits::report(&oversized);
+
+        // Effect companions that have to be taken from another mod than the one that won them
+        let effect_overrides = utils::pair_effect_files(launchpad.tree());
+
+        for (local, full_path) in effect_overrides.iter() {
+            if let (Ok(hash), Ok(metadata)) = (local.smash_hash(), std::fs::metadata(full_path)) {
+                hashed_sizes.insert(hash, metadata.len() as usize);
+            }
+        }
+
+        // Files generated from templates are read from their base file, unless a mod provides them directly
+        let mut late_files = effect_overrides;
+        late_files.extend(based_late_files);
+
+        for ModFile { root, full_path, local } in collected.iter() {
+            if !local.has_extension("template") {
+                continue;
+            }
+
+            let generated = match template::load_template(root, full_path) {
+                Ok(generated) => generated,
+                Err(e) => {
+                    error!("Failed to load template '{}'. Reason: {}", full_path.display(), e);
+                    continue;
+                },
+            };
+
+            for file in generated {
+                if hashed_paths.contains_key(&file.hash) {
+                    warn!(
+                        "'{}' is already provided by a mod, ignoring the template '{}' for it.",
+                        file.local.display(),
+                        full_path.display()
+                    );
+                    continue;
+                }
+
+                file.register_transforms();
+                hashed_sizes.insert(file.hash, file.size);
+                hashed_paths.insert(file.hash, file.local.clone());
+                late_files.insert(file.local, file.base);
+            }
+        }
+
+        // Warn about mods that don't replace anything before their paths become known hashes below
+        let ineffective_mods = utils::find_ineffective_mods(&launchpad, arc);
+
+        if !ineffective_mods.is_empty() {
+            warn!(
+                "Boot summary: {} mod(s) contain no file matching a game path and will not change anything. Check that their files are named correctly and made for this version of the game:\n{}",
+                ineffective_mods.len(),
+                ineffective_mods.iter().map(|root| format!("- {}", root.display())).collect::<Vec<_>>().join("\n")
+            );
+        }
+
         // Add the discovered paths to the global hashes, so that when a file is loading that *we have discovered* we can guarantee
         // that we are printing the real path in the logger.
         for (_hash, path) in hashed_paths.iter() {
@@ -209,7 +566,7 @@ impl CachedFilesystem {
         };
 
         // Load all of the user configs into the main config
-        Self::load_remaining_configs(&mut config, &launchpad);
+        Self::load_remaining_configs(&mut config, &collected);
 
         // Collect all of the NUS3BANK dependencies that audio files have in order to be unshared
         // Note that we pass the unshare blacklist because if the NUS3AUDIO files are blacklisted then we shouldn't unshare the
@@ -218,13 +575,16 @@ impl CachedFilesystem {
 
         // Create the API file tree and start adding things to it
         let mut api_tree = Tree::new(ApiLoader::default());
+        api_tree.loader.set_merge_order(discover::take_merge_order());
 
         // Set up the API tree with all of the patch files
-        let mut hashes = Self::initialize_prc_patches(&launchpad, &mut api_tree);
-        hashes.extend(Self::initialize_msbt_patches(&launchpad, &mut api_tree));
-        hashes.extend(Self::initialize_nus3audio_patches(&launchpad, &mut api_tree));
-        hashes.extend(Self::initialize_motionlist_patches(&launchpad, &mut api_tree));
-        hashes.extend(Self::initialize_bgm_property_patches(&launchpad, &mut api_tree));
+        let mut hashes = Self::initialize_prc_patches(&collected, &mut api_tree);
+        hashes.extend(Self::initialize_msbt_patches(&collected, &mut api_tree));
+        hashes.extend(Self::initialize_nus3audio_patches(&collected, &mut api_tree));
+        hashes.extend(Self::initialize_motionlist_patches(&collected, &mut api_tree));
+        hashes.extend(Self::initialize_bgm_property_patches(&collected, &mut api_tree));
+        hashes.extend(Self::initialize_binary_patches(&collected, &mut api_tree));
+        hashes.extend(Self::initialize_music_tracks(&collected, &mut api_tree));
 
         // Add the hash files and set the new size to 10x the original files
         for hash in hashes {
@@ -262,8 +622,10 @@ impl CachedFilesystem {
         // Set the global flag that we are initialized (referenced by API)
         IS_INIT.store(true, Ordering::SeqCst);
 
+        let preload_list = utils::get_preload_list(launchpad.tree());
+
         // Construct a CachedFilesystem
-        CachedFilesystem {
+        let mut filesystem = CachedFilesystem {
             loader: launchpad.launch(ArcLoader(arc), api_tree),
             config,
             hash_lookup: hashed_paths,
@@ -273,7 +635,63 @@ impl CachedFilesystem {
             current_nus3bank_id: 7420,
             nus3banks: HashMap::new(),
             total_size: 0,
+            preloaded: Mutex::new(HashMap::new()),
+            installed: HashMap::new(),
+            known_roots: discover::list_mod_roots(),
+            late_files,
+            pending_namespaces: config::lazy_namespaces().into_iter().collect(),
+            lazy_roots: discover::take_lazy_roots(),
+            size_limits,
+            vanilla_sizes: HashMap::new(),
+            ineffective_mods,
+        };
+
+        filesystem.map_oversized_lazy_files();
+        filesystem.preload_files(&preload_list);
+
+        filesystem
+    }
+
+    /// Reads the requested files ahead of time so that they are ready when the game asks for them, until the preload budget runs out
+    fn preload_files(&self, locals: &[PathBuf]) {
+        // Not filled in place, since loading goes through the preloaded files
+        let mut preloaded = HashMap::new();
+        let mut used = 0;
+
+        for local in locals {
+            let hash = match local.smash_hash() {
+                Ok(hash) => hash,
+                Err(_) => continue,
+            };
+
+            let path = match self.hash_lookup.get(&hash) {
+                Some(path) => path,
+                None => {
+                    warn!("Cannot preload '{}' because no mod provides it.", local.display());
+                    continue;
+                },
+            };
+
+            if preloaded.contains_key(path) {
+                continue;
+            }
+
+            if let Some(data) = self.load(hash) {
+                if used + data.len() > PRELOAD_BUDGET {
+                    warn!("Skipping the preload of '{}' because the preload budget has been exhausted.", local.display());
+                    continue;
+                }
+
+                used += data.len();
+                preloaded.insert(path.clone(), data);
+            }
+        }
+
+        if !preloaded.is_empty() {
+            info!("Preloaded {} file(s) for a total of {:#x} bytes.", preloaded.len(), used);
         }
+
+        *self.preloaded.lock().unwrap() = preloaded;
     }
 
     /// Patches a file in the LoadedArc
@@ -312,14 +730,108 @@ impl CachedFilesystem {
         }
     }
 
+    /// Remembers the size of a file as the game shipped it, before it gets patched or added to the arc
+    fn record_vanilla_size(&mut self, hash: Hash40) {
+        self.vanilla_sizes.entry(hash).or_insert_with(|| {
+            resource::arc()
+                .get_file_data_from_hash(hash, config::region())
+                .ok()
+                .map(|data| data.decomp_size as u64)
+        });
+    }
+
+    /// Decompressed size of a file in the vanilla data.arc, or None if the game doesn't have the file
+    pub fn original_size(&self, hash: Hash40) -> Option<u64> {
+        match self.vanilla_sizes.get(&hash) {
+            Some(size) => *size,
+            // Files that no mod touches are still the way the game shipped them
+            None => resource::arc()
+                .get_file_data_from_hash(hash, config::region())
+                .ok()
+                .map(|data| data.decomp_size as u64),
+        }
+    }
+
     // Search the provided hash for a PathBuf in the hash lookup
     pub fn local_hash(&self, hash: Hash40) -> Option<&PathBuf> {
         self.hash_lookup.get(&hash)
     }
 
     // Get the "actual path" for a file hash
+    // Returns None while replacements are bypassed or the SD card can't be read, so the game loads its own file instead. Files that are
+    // already loaded stay modded until the game loads them again.
     pub fn hash(&self, hash: Hash40) -> Option<PathBuf> {
-        self.local_hash(hash).and_then(|x| self.loader.query_actual_path(x))
+        if config::replacements_bypassed() || !config::sd_available() {
+            return None;
+        }
+
+        self.local_hash(hash).and_then(|x| {
+            self.loader
+                .query_actual_path(x)
+                .or_else(|| self.late_files.get(x).cloned())
+                // Installed from memory, there is no file on the SD to point to
+                .or_else(|| self.installed.contains_key(x).then(|| x.clone()))
+        })
+    }
+
+    /// Hands `data` to the game every time it loads the file, instead of what mods provide for it or the vanilla file. The data is
+    /// kept in memory for the rest of the session and counts towards the preload budget. Returns false if it couldn't be installed.
+    pub fn install_replacement(&mut self, hash: Hash40, data: Vec<u8>) -> bool {
+        let local = self.hash_lookup.get(&hash).cloned().unwrap_or_else(|| get_path_from_hash(hash));
+
+        // The stream hook hands the game a path to read from, which data in memory doesn't have
+        if local.is_stream() {
+            warn!("Cannot install a replacement for stream file '{}' ({:#x}).", hashes::find(hash), hash.0);
+            return false;
+        }
+
+        // Files can't be added to the arc once the game is running
+        if self.original_size(hash).is_none() {
+            warn!("Cannot install a replacement for '{}' ({:#x}) as the game doesn't have this file.", hashes::find(hash), hash.0);
+            return false;
+        }
+
+        // The current size, which is larger than the vanilla one if a mod already grew the file
+        let decomp_size = match resource::arc().get_file_data_from_hash(hash, config::region()) {
+            Ok(file_data) => file_data.decomp_size as usize,
+            Err(_) => return false,
+        };
+
+        {
+            let preloaded = self.preloaded.lock().unwrap();
+            // Installing over a file frees the share of the budget its previous data had
+            let used: usize = preloaded
+                .iter()
+                .chain(self.installed.iter())
+                .filter(|(path, _)| **path != local)
+                .map(|(_, data)| data.len())
+                .sum();
+
+            if used + data.len() > PRELOAD_BUDGET {
+                warn!(
+                    "Cannot install a replacement of {:#x} bytes for '{}' ({:#x}) because the preload budget has been exhausted.",
+                    data.len(),
+                    hashes::find(hash),
+                    hash.0
+                );
+                return false;
+            }
+        }
+
+        self.record_vanilla_size(hash);
+
+        let cached_size = if data.len() <= decomp_size {
+            decomp_size
+        } else {
+            self.patch_file(hash, data.len()).unwrap_or(decomp_size)
+        };
+
+        self.hash_lookup.insert(hash, local.clone());
+        self.hash_size_cache.insert(hash, cached_size);
+        self.preloaded.lock().unwrap().remove(&local);
+        self.installed.insert(local, data);
+
+        true
     }
 
     // Load the file data from the Orbits filesystem
@@ -335,6 +847,24 @@ impl CachedFilesystem {
             return None;
         };
 
+        if let Some(data) = self.installed.get(path) {
+            return Some(data.clone());
+        }
+
+        if let Some(data) = self.preloaded.lock().unwrap().remove(path) {
+            return Some(data);
+        }
+
+        if let Some(full_path) = self.late_files.get(path) {
+            return match std::fs::read(full_path) {
+                Ok(data) => Some(data),
+                Err(e) => {
+                    error!("Failed to load data for {}. Reason: {:?}", full_path.display(), e);
+                    None
+                },
+            };
+        }
+
         match self.loader.load(path) {
             Ok(data) => Some(data),
             Err(Error::Virtual(ApiLoaderError::NoVirtFile)) => {
@@ -356,6 +886,16 @@ impl CachedFilesystem {
 
     // Load the file data from the Orbits filesystem into a pre-allocated buffer
     pub fn load_into(&self, hash: Hash40, mut buffer: &mut [u8]) -> Option<usize> {
+        if let Some(full_path) = self.streamable_path(hash) {
+            return match stream_into(&full_path, buffer) {
+                Ok(size) => Some(size),
+                Err(e) => {
+                    error!("Failed to stream data for {}. Reason: {:?}", full_path.display(), e);
+                    None
+                },
+            };
+        }
+
         if let Some(data) = self.load(hash) {
             if buffer.len() < data.len() {
                 error!(
@@ -373,6 +913,22 @@ impl CachedFilesystem {
         }
     }
 
+    /// The file on the SD to read for a replacement, if its contents go to the game untouched. Anything coming from memory, a callback
+    /// or a patch has to go through [`load`](Self::load) instead.
+    fn streamable_path(&self, hash: Hash40) -> Option<PathBuf> {
+        let local = self.hash_lookup.get(&hash)?;
+
+        if local.is_stream()
+            || self.installed.contains_key(local)
+            || self.preloaded.lock().unwrap().contains_key(local)
+            || !self.loader.virt().loader.is_plain(hash)
+        {
+            return None;
+        }
+
+        self.late_files.get(local).cloned().or_else(|| self.loader.query_actual_path(local))
+    }
+
     // Sets the incoming file to be loaded
     pub fn set_incoming(&mut self, hash: Option<Hash40>) {
         if let Some(hash) = self.incoming_load.take() {
@@ -440,8 +996,392 @@ impl CachedFilesystem {
             .collect();
     }
 
+    /// Picks up the mods added to the mods directory since boot and returns how many files they replace.
+    ///
+    /// Only plain file replacements can be applied this late: files that are larger than the vanilla ones, patch files, plugins and
+    /// anything else that needs to be handled while booting are left for the next boot. Files that are already replaced by another mod are kept as is.
+    pub fn rescan_new_mods(&mut self) -> usize {
+        let new_roots = discover::find_new_mod_roots(&self.known_roots);
+        let mut replaced = 0;
+
+        for root in new_roots {
+            info!("Found new mod '{}' while rescanning.", root.display());
+            let base = discover::mod_base(&root).unwrap_or_default();
+            replaced += self.add_late_files(&root, &base, root.clone(), false);
+        }
+
+        // Disabled mods are remembered too, so they don't get reported again on the next rescan
+        self.known_roots = discover::list_mod_roots();
+
+        replaced
+    }
+
+    /// Applies a preset change without a reboot when the mods that were enabled or disabled only contain params. Returns false without
+    /// changing anything if the mods contain other files, or if the game already loaded one of the params since it would keep its copy.
+    /// The params are checked once the change is applied, and false is returned as well if one of them didn't change as expected.
+    pub fn reload_params(&mut self, enabled: &[PathBuf], disabled: &[PathBuf]) -> bool {
+        let mut affected = Vec::new();
+        let mut toggled = Vec::new();
+        // Full params of the disabled mods that the game currently gets from them
+        let mut removed = Vec::new();
+
+        for (root, is_enabled) in enabled.iter().map(|root| (root, true)).chain(disabled.iter().map(|root| (root, false))) {
+            if discover::mod_base(root).is_some() {
+                info!("'{}' declares a base directory, a reboot is needed to apply the preset.", root.display());
+                return false;
+            }
+
+            let params = match utils::find_param_files(root) {
+                Some(params) => params,
+                None => {
+                    info!("'{}' does not only contain params, a reboot is needed to apply the preset.", root.display());
+                    return false;
+                },
+            };
+
+            for local in params {
+                if !local.has_extension("prc") {
+                    match utils::param_patch_base(&local).smash_hash() {
+                        Ok(hash) => {
+                            affected.push(hash);
+                            toggled.push(utils::ToggledParam { hash, root: root.clone(), is_patch: true, enabled: is_enabled });
+                        },
+                        Err(_) => return false,
+                    }
+                    continue;
+                }
+
+                let hash = match local.smash_hash() {
+                    Ok(hash) => hash,
+                    Err(_) => return false,
+                };
+
+                // Patches read the file they apply to from the discovered mods, which can't be changed after boot
+                if self.loader.virt().loader.has_prc_patches(hash) {
+                    info!("'{}' is also patched, a reboot is needed to apply the preset.", local.display());
+                    return false;
+                }
+
+                let current = self.local_hash(hash).and_then(|x| self.loader.query_actual_path(x).or_else(|| self.late_files.get(x).cloned()));
+
+                if is_enabled && current.is_some() {
+                    info!("'{}' is already provided by another mod, a reboot is needed to apply the preset.", local.display());
+                    return false;
+                }
+
+                if !is_enabled && current.map_or(false, |path| path.starts_with(root)) {
+                    removed.push((hash, local));
+                }
+
+                affected.push(hash);
+                toggled.push(utils::ToggledParam { hash, root: root.clone(), is_patch: false, enabled: is_enabled });
+            }
+        }
+
+        if let Some(hash) = affected.iter().find(|hash| is_file_loaded(**hash)) {
+            info!("'{}' ({:#x}) is already loaded by the game, a reboot is needed to apply the preset.", hashes::find(*hash), hash.0);
+            return false;
+        }
+
+        for (hash, local) in removed {
+            self.hash_lookup.remove(&hash);
+            self.late_files.remove(&local);
+        }
+
+        for root in disabled {
+            self.loader.virt_mut().loader.remove_prc_patches(root);
+        }
+
+        for root in enabled {
+            // Only picks up the full params, patches aren't files of the game
+            self.add_late_files(root, Path::new(""), root.clone(), true);
+
+            for local in utils::find_param_files(root).unwrap_or_default().iter().filter(|local| !local.has_extension("prc")) {
+                let hash = match utils::add_prc_patch(self.loader.virt_mut(), root.join(local), local) {
+                    Some(hash) => hash,
+                    None => continue,
+                };
+
+                self.record_vanilla_size(hash);
+                self.hash_lookup.entry(hash).or_insert_with(|| get_path_from_hash(hash));
+
+                // Same room as the patched params get at boot
+                if !self.hash_size_cache.contains_key(&hash) {
+                    if let Some(size) = self.original_size(hash) {
+                        let size = size as usize;
+                        let cached_size = self.patch_file(hash, size * 10).unwrap_or(size);
+                        self.hash_size_cache.insert(hash, cached_size);
+                    }
+                }
+            }
+        }
+
+        let misapplied = utils::misapplied_params(
+            &toggled,
+            |hash| self.local_hash(hash).and_then(|x| self.loader.query_actual_path(x).or_else(|| self.late_files.get(x).cloned())),
+            |hash| self.loader.virt().loader.prc_patches(hash).to_vec(),
+        );
+
+        if let Some(hash) = misapplied.first() {
+            error!(
+                "'{}' ({:#x}) did not change as expected after applying the preset, a reboot is needed.",
+                hashes::find(*hash),
+                hash.0
+            );
+            return false;
+        }
+
+        info!("Applied the preset without a reboot, {} param(s) changed.", affected.len());
+
+        true
+    }
+
+    /// Maps the files found in `directory`, which belongs to the mod at `root`, unless another mod already provides them. Their paths
+    /// in the game are relative to `base`, which is empty unless the mod declares one. Files larger than the ones they replace are only
+    /// kept if `grow` is set, in which case their size is patched in the arc.
+    fn add_late_files(&mut self, root: &Path, base: &Path, directory: PathBuf, grow: bool) -> usize {
+        let mut added = 0;
+
+        for (local, full_path) in scan_late_files(root, base, directory) {
+            match self.map_late_file(local, full_path.clone(), grow) {
+                LateFile::Added => added += 1,
+                LateFile::Skipped => {},
+                // Most likely a wrong base rather than a file the mod means to add
+                LateFile::NotInGame if !base.as_os_str().is_empty() => {
+                    warn!("'{}' maps to a file which does not exist in the game.", full_path.display());
+                },
+                LateFile::NotInGame => {},
+                LateFile::TooLarge => {
+                    warn!(
+                        "File '{}' is larger than the file it replaces and will only be used after a reboot.",
+                        full_path.display()
+                    );
+                },
+                LateFile::OverLimit(file) => error!("{}, it will not be replaced.", file.describe()),
+            }
+        }
+
+        added
+    }
+
+    /// Maps a file found after boot unless another mod already provides it. A file larger than the one it replaces is only kept if
+    /// `grow` is set, in which case its size is patched in the arc.
+    fn map_late_file(&mut self, local: PathBuf, full_path: PathBuf, grow: bool) -> LateFile {
+        let hash = match local.smash_hash() {
+            Ok(hash) => hash,
+            Err(_) => return LateFile::Skipped,
+        };
+
+        if self.hash_lookup.contains_key(&hash) {
+            return LateFile::Skipped;
+        }
+
+        // Streamed files (stream;/) are read by the game straight from the path the stream hook gives it, so they don't have an
+        // entry in the arc to check the size of or to grow
+        if local.is_stream() {
+            if let Some(string) = local.to_str() {
+                hashes::add(string);
+            }
+
+            self.hash_lookup.insert(hash, local.clone());
+            self.late_files.insert(local, full_path);
+            return LateFile::Added;
+        }
+
+        let decomp_size = match resource::arc().get_file_data_from_hash(hash, config::region()) {
+            Ok(data) => data.decomp_size as usize,
+            Err(_) => return LateFile::NotInGame,
+        };
+
+        let size = match std::fs::metadata(&full_path) {
+            Ok(metadata) => metadata.len() as usize,
+            Err(_) => return LateFile::Skipped,
+        };
+
+        if let Some(file) = limits::check_file(&self.size_limits, hash, &local, size) {
+            return LateFile::OverLimit(file);
+        }
+
+        self.record_vanilla_size(hash);
+
+        let cached_size = if size <= decomp_size {
+            decomp_size
+        } else if grow {
+            self.patch_file(hash, size).unwrap_or(decomp_size)
+        } else {
+            return LateFile::TooLarge;
+        };
+
+        if let Some(string) = local.to_str() {
+            hashes::add(string);
+        }
+
+        self.hash_lookup.insert(hash, local.clone());
+        self.hash_size_cache.insert(hash, cached_size);
+        self.late_files.insert(local, full_path);
+        LateFile::Added
+    }
+
+    /// Finds the pending lazy namespace a file of the game belongs to, along with the mod folders to scan for it. The folders of the file
+    /// are walked up in the search section rather than read from the hash list, which doesn't know every file of the game.
+    fn pending_lazy_namespace(&self, hash: Hash40) -> Option<(String, Vec<PathBuf>)> {
+        if self.pending_namespaces.is_empty() {
+            return None;
+        }
+
+        let search = resource::search();
+        let mut current = hash;
+
+        for _ in 0..MAX_FOLDER_DEPTH {
+            let parent = search.get_path_list_entry_from_hash(current).ok()?.parent.hash40();
+
+            if let Some(namespace) = self.pending_namespaces.iter().find(|namespace| Hash40::from(namespace.as_str()) == parent) {
+                return Some((namespace.clone(), self.lazy_roots.clone()));
+            }
+
+            // The root folder is its own parent
+            if parent == current {
+                return None;
+            }
+
+            current = parent;
+        }
+
+        None
+    }
+
+    /// Maps the files scanned for a lazy namespace, which happens while the game is running, so the files that are larger than the ones
+    /// they replace are left for the next boot. Returns how many files were added and the ones that were too large.
+    fn map_lazy_namespace(&mut self, namespace: &str, files: Vec<(PathBuf, PathBuf)>) -> (usize, Vec<PathBuf>) {
+        let mut added = 0;
+        let mut too_large = Vec::new();
+
+        for (local, full_path) in files {
+            match self.map_late_file(local, full_path.clone(), false) {
+                LateFile::Added => added += 1,
+                LateFile::TooLarge => too_large.push(full_path),
+                LateFile::OverLimit(file) => error!("{}, it will not be replaced.", file.describe()),
+                LateFile::Skipped | LateFile::NotInGame => {},
+            }
+        }
+
+        self.pending_namespaces.remove(namespace);
+
+        (added, too_large)
+    }
+
+    /// Maps the files of lazy namespaces that were too large to be used when their namespace was scanned on the previous boots. Their
+    /// size can still be patched at this point, since the game hasn't loaded anything yet.
+    fn map_oversized_lazy_files(&mut self) {
+        let record = crate::utils::paths::lazy_oversized_files();
+
+        let contents = match std::fs::read_to_string(&record) {
+            Ok(contents) => contents,
+            Err(_) => return,
+        };
+
+        let mut kept = Vec::new();
+
+        for line in contents.lines() {
+            let full_path = PathBuf::from(line);
+
+            // The mod was removed or disabled since
+            let root = match self.lazy_roots.iter().find(|root| full_path.starts_with(root)) {
+                Some(root) => root.clone(),
+                None => continue,
+            };
+
+            if !full_path.is_file() {
+                continue;
+            }
+
+            let local = full_path.strip_prefix(&root).unwrap().to_path_buf();
+
+            if let LateFile::Added = self.map_late_file(local, full_path, true) {
+                kept.push(line);
+            }
+        }
+
+        let result = if kept.is_empty() {
+            std::fs::remove_file(&record)
+        } else {
+            std::fs::write(&record, kept.join("\n"))
+        };
+
+        if let Err(e) = result {
+            warn!("Failed to update '{}'. Reason: {:?}", record, e);
+        }
+    }
+
+    /// Reads every file that can be requested by the game once, the same way the hooks would, and writes a pass/fail report to the SD.
+    /// Returns whether every file passed.
+    pub fn run_integrity_check(&self) -> bool {
+        let arc = resource::arc();
+        let region = config::region();
+
+        let mut entries: Vec<(&Hash40, &PathBuf)> = self.hash_lookup.iter().collect();
+        entries.sort_by(|(_, a), (_, b)| a.cmp(b));
+
+        let mut report = Vec::with_capacity(entries.len());
+        let mut failures = 0;
+
+        for (hash, path) in entries {
+            let was_preloaded = self.preloaded.lock().unwrap().contains_key(path);
+
+            let result = match self.load(*hash) {
+                Some(data) => {
+                    // The game allocates its buffer from the patched size, so anything bigger would fail to be loaded by the hooks
+                    let result = match arc.get_file_data_from_hash(*hash, region) {
+                        Ok(file_data) if data.len() > file_data.decomp_size as usize => Err(format!(
+                            "file size {:#x} is larger than the game buffer ({:#x})",
+                            data.len(),
+                            file_data.decomp_size
+                        )),
+                        _ => Ok(()),
+                    };
+
+                    // Reading went through the preloaded data, put it back for when the game asks for it
+                    if was_preloaded {
+                        self.preloaded.lock().unwrap().insert(path.clone(), data);
+                    }
+
+                    result
+                },
+                None => Err(String::from("failed to read the file")),
+            };
+
+            match result {
+                Ok(()) => report.push(format!("PASS {} ({:#x})", path.display(), hash.0)),
+                Err(reason) => {
+                    failures += 1;
+                    report.push(format!("FAIL {} ({:#x}): {}", path.display(), hash.0, reason));
+                },
+            }
+        }
+
+        let summary = format!("{} file(s) checked, {} failure(s)", report.len(), failures);
+
+        if let Err(e) = std::fs::write("sd:/ultimate/arcropolis/integrity_report.txt", format!("{}\n\n{}\n", summary, report.join("\n"))) {
+            error!("Failed to write the integrity report. Reason: {:?}", e);
+        }
+
+        if failures == 0 {
+            info!("Integrity check passed: {}", summary);
+        } else {
+            error!("Integrity check failed: {}. See sd:/ultimate/arcropolis/integrity_report.txt", summary);
+        }
+
+        failures == 0
+    }
+
     /// Goes through and performs the required file manipulation in order to load mods
     pub fn process_mods(&mut self) {
+        // Recorded before anything is added to the arc, as the added files can't be told apart from the vanilla ones afterwards
+        let hashes: Vec<Hash40> = self.hash_lookup.keys().copied().collect();
+        for hash in hashes {
+            self.record_vanilla_size(hash);
+        }
+
         let mut context = LoadedArc::make_addition_context();
         let mut search_context = LoadedSearchSection::make_context();
 
@@ -467,13 +1407,18 @@ impl CachedFilesystem {
             replacement::addition::add_dir_info_with_base(&mut context, Path::new(new), Path::new(base));
         }
 
+        // Files that were not found in the data.arc, which are either new files or files with a mistake in their path. The ones the
+        // config.json of a mod declares are new files for sure.
+        let declared = unmatched::declared_files(&self.config);
+        let mut unmatched = Vec::new();
+
         // Go through and add any files that were not found in the data.arc
         self.loader.walk_patch(|node, ty| {
             if node.get_local().is_stream() || !ty.is_file() {
                 return;
             }
 
-            let _hash = if let Ok(hash) = node.get_local().smash_hash() {
+            let hash = if let Ok(hash) = node.get_local().smash_hash() {
                 if context.contains_file(hash) {
                     return;
                 }
@@ -482,10 +1427,22 @@ impl CachedFilesystem {
                 return;
             };
 
+            if !declared.contains(&hash) {
+                unmatched.push(node.get_local().to_path_buf());
+            }
             replacement::addition::add_file(&mut context, node.get_local());
             replacement::addition::add_searchable_file_recursive(&mut search_context, node.get_local());
         });
 
+        let unmatched: Vec<(PathBuf, Option<PathBuf>)> = unmatched
+            .into_iter()
+            .map(|local| {
+                let source = self.loader.query_actual_path(&local);
+                (local, source)
+            })
+            .collect();
+        unmatched::write_report(unmatched);
+
         // Don't unshare any files in the unshare blacklist (nus3audio handled during filesystem finish)
         let files = self.hash_lookup.iter().filter_map(
             |(hash, _path)| {
@@ -545,6 +1502,7 @@ impl CachedFilesystem {
         let ApiCallResult { hash, path, size } = Self::handle_panding_api_call(self.loader.virt_mut(), call);
 
         self.hash_lookup.insert(hash, path);
+        self.record_vanilla_size(hash);
         if let Some(size) = size {
             if let Some(old_size) = self.patch_file(hash, size) {
                 if let Some(size_mut) = self.hash_size_cache.get_mut(&hash) {
@@ -566,6 +1524,20 @@ impl CachedFilesystem {
     pub fn get_sum_size(&self) -> usize {
         self.total_size
     }
+
+    /// Amount of files that are replaced or patched
+    pub fn get_file_count(&self) -> usize {
+        self.hash_lookup.len()
+    }
+
+    pub fn ineffective_mods(&self) -> &[PathBuf] {
+        &self.ineffective_mods
+    }
+
+    /// Files that are replaced by a file of the SD, along with that file
+    pub fn modded_files(&self) -> Vec<(Hash40, PathBuf)> {
+        self.hash_lookup.keys().filter_map(|hash| Some((*hash, self.hash(*hash)?))).filter(|(_, path)| path.is_file()).collect()
+    }
 }
 
 pub enum GlobalFilesystem {
@@ -630,6 +1602,23 @@ impl GlobalFilesystem {
         }
     }
 
+    pub fn original_size(&self, hash: Hash40) -> Option<u64> {
+        match self {
+            Self::Initialized(fs) => fs.original_size(hash),
+            _ => None,
+        }
+    }
+
+    pub fn install_replacement(&mut self, hash: Hash40, data: Vec<u8>) -> bool {
+        match self {
+            Self::Initialized(fs) => fs.install_replacement(hash, data),
+            _ => {
+                error!("Cannot install a replacement because the filesystem is not initialized!");
+                false
+            },
+        }
+    }
+
     pub fn load_into(&self, hash: Hash40, buffer: &mut [u8]) -> Option<usize> {
         match self {
             Self::Initialized(fs) => fs.load_into(hash, buffer),
@@ -711,6 +1700,47 @@ impl GlobalFilesystem {
         }
     }
 
+    pub fn run_integrity_check(&self) -> bool {
+        match self {
+            Self::Initialized(fs) => fs.run_integrity_check(),
+            _ => {
+                error!("Cannot run the integrity check because the filesystem is not initialized!");
+                false
+            },
+        }
+    }
+
+    pub fn rescan_new_mods(&mut self) -> usize {
+        match self {
+            Self::Initialized(fs) => fs.rescan_new_mods(),
+            _ => {
+                error!("Cannot rescan the mods because the filesystem is not initialized!");
+                0
+            },
+        }
+    }
+
+    pub fn reload_params(&mut self, enabled: &[PathBuf], disabled: &[PathBuf]) -> bool {
+        match self {
+            Self::Initialized(fs) => fs.reload_params(enabled, disabled),
+            _ => false,
+        }
+    }
+
+    pub fn pending_lazy_namespace(&self, hash: Hash40) -> Option<(String, Vec<PathBuf>)> {
+        match self {
+            Self::Initialized(fs) => fs.pending_lazy_namespace(hash),
+            _ => None,
+        }
+    }
+
+    pub fn map_lazy_namespace(&mut self, namespace: &str, files: Vec<(PathBuf, PathBuf)>) -> (usize, Vec<PathBuf>) {
+        match self {
+            Self::Initialized(fs) => fs.map_lazy_namespace(namespace, files),
+            _ => (0, Vec::new()),
+        }
+    }
+
     pub fn config(&self) -> &ModConfig {
         match self {
             Self::Initialized(fs) => fs.config(),
@@ -754,4 +1784,80 @@ impl GlobalFilesystem {
             _ => None,
         }
     }
+
+    pub fn get_file_count(&self) -> Option<usize> {
+        match self {
+            Self::Initialized(fs) => Some(fs.get_file_count()),
+            _ => None,
+        }
+    }
+
+    pub fn ineffective_mods(&self) -> &[PathBuf] {
+        match self {
+            Self::Initialized(fs) => fs.ineffective_mods(),
+            _ => &[],
+        }
+    }
+
+    pub fn modded_files(&self) -> Vec<(Hash40, PathBuf)> {
+        match self {
+            Self::Initialized(fs) => fs.modded_files(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lays out a mod in a temporary folder, which is removed once the returned guard is dropped
+    struct TempMod(PathBuf);
+
+    impl TempMod {
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let root = std::env::temp_dir().join(name);
+            let _ = std::fs::remove_dir_all(&root);
+
+            for (local, contents) in files {
+                let path = root.join(local);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, contents).unwrap();
+            }
+
+            Self(root)
+        }
+    }
+
+    impl Drop for TempMod {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn based_layout_resolves_to_full_game_paths() {
+        let mod_root = TempMod::new(
+            "arcropolis_based_layout",
+            &[("info.toml", "base = \"/Fighter/Mario/\""), ("model/body/c00/model.numdlb", "")],
+        );
+        let root = &mod_root.0;
+
+        let base = discover::mod_base(root).unwrap();
+        let files = scan_late_files(root, &base, root.clone());
+
+        // info.toml sits at the root of the mod and isn't a file of the game
+        assert_eq!(files.len(), 1);
+
+        let (local, full_path) = &files[0];
+        assert_eq!(full_path, &root.join("model/body/c00/model.numdlb"));
+        assert_eq!(local.smash_hash().unwrap(), Hash40::from("fighter/mario/model/body/c00/model.numdlb"));
+    }
+
+    #[test]
+    fn base_cannot_leave_the_game_root() {
+        let mod_root = TempMod::new("arcropolis_based_escape", &[("info.toml", "base = \"../fighter\"")]);
+
+        assert!(discover::mod_base(&mod_root.0).is_none());
+    }
 }
@@ -0,0 +1,189 @@
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+};
+
+use camino::Utf8PathBuf;
+use serde::Deserialize;
+use smash_arc::Hash40;
+
+use crate::utils;
+
+/// The blocklist file, maintained by support teams to stop mods that are known to crash the game from being loaded
+#[derive(Debug, Default, Deserialize)]
+pub struct Blocklist {
+    #[serde(rename = "entry", default)]
+    pub entries: Vec<BlocklistEntry>,
+}
+
+/// A single incompatible mod. Every condition that is set has to match for the mod to be blocked.
+#[derive(Debug, Deserialize)]
+pub struct BlocklistEntry {
+    /// Stable identifier of the entry, which is what users override
+    pub id: String,
+    /// Shown to the user when the mod gets blocked
+    pub reason: String,
+    /// Hash40 of the mod's folder name
+    pub folder_hash: Option<u64>,
+    /// Readable alternative to `folder_hash`
+    pub folder: Option<String>,
+    /// A file of the mod, relative to its root, and optionally the CRC32 of its contents to target a specific build
+    pub file: Option<BlockedFile>,
+    /// Versions of the game the mod is incompatible with. The mod is blocked on every version if this is empty.
+    #[serde(default)]
+    pub game_versions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BlockedFile {
+    pub path: String,
+    pub crc32: Option<u32>,
+}
+
+impl BlocklistEntry {
+    fn matches(&self, root: &Path, game_version: &str) -> bool {
+        if !self.game_versions.is_empty() && !self.game_versions.iter().any(|version| version == game_version) {
+            return false;
+        }
+
+        // An entry without any condition on the mod itself would block everything
+        if self.folder_hash.is_none() && self.folder.is_none() && self.file.is_none() {
+            return false;
+        }
+
+        let folder_name = root.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+
+        let folder_matches = self.folder_hash.map_or(true, |hash| Hash40::from(folder_name).0 == hash)
+            && self.folder.as_ref().map_or(true, |folder| folder == folder_name);
+
+        folder_matches
+            && self.file.as_ref().map_or(true, |file| {
+                let path = root.join(&file.path);
+
+                match file.crc32 {
+                    Some(crc32) => std::fs::read(&path).map_or(false, |data| crc32fast::hash(&data) == crc32),
+                    None => path.is_file(),
+                }
+            })
+    }
+}
+
+/// How long the download can take before falling back to the blocklist on the SD, in seconds. This runs during boot.
+#[cfg(feature = "online")]
+const REFRESH_TIMEOUT: u64 = 5;
+
+/// Downloads the blocklist over the one on the SD, so support teams can update it without users doing anything. The one on the SD,
+/// from the last successful download, is used whenever the download fails.
+#[cfg(feature = "online")]
+fn refresh(path: &Utf8PathBuf) {
+    let url = match config::blocklist::get_url() {
+        Some(url) => url,
+        None => return,
+    };
+
+    let cached = if path.exists() { "keeping the cached one" } else { "no mod will be blocked" };
+
+    match minreq::get(&url).with_timeout(REFRESH_TIMEOUT).send() {
+        // Only keep it if it parses, a broken download shouldn't replace a working blocklist
+        Ok(response) if response.status_code == 200 => match response.as_str().map(toml::from_str::<Blocklist>) {
+            Ok(Ok(_)) => {
+                if let Err(e) = std::fs::write(path, response.as_bytes()) {
+                    warn!("Failed to save the blocklist to '{}'. Reason: {:?}", path, e);
+                }
+            },
+            _ => warn!("The blocklist downloaded from '{}' is not valid, {}.", url, cached),
+        },
+        Ok(response) => warn!("Failed to download the blocklist from '{}', {}. Status code: {}", url, cached, response.status_code),
+        Err(e) => warn!("Failed to download the blocklist from '{}', {}. Reason: {:?}", url, cached, e),
+    }
+}
+
+pub fn load() -> Blocklist {
+    let path = utils::paths::blocklist();
+
+    #[cfg(feature = "online")]
+    refresh(&path);
+
+    let contents = match std::fs::read_to_string(&path) {
+        Ok(contents) => contents,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Blocklist::default(),
+        Err(e) => {
+            warn!("Failed to read the blocklist. Reason: {:?}", e);
+            return Blocklist::default();
+        },
+    };
+
+    match toml::from_str(&contents) {
+        Ok(blocklist) => blocklist,
+        Err(e) => {
+            error!("Failed to parse the blocklist at '{}'. Reason: {}", path, e);
+            Blocklist::default()
+        },
+    }
+}
+
+/// Goes through every mod folder and returns the ones the blocklist prevents from loading.
+///
+/// The user is told about each blocked mod once, and can choose to load it anyway, which overrides the entry from then on.
+pub fn find_blocked_mods(mod_directories: &[Utf8PathBuf]) -> HashSet<PathBuf> {
+    let blocklist = load();
+
+    if blocklist.entries.is_empty() {
+        return HashSet::new();
+    }
+
+    let game_version = utils::env::get_game_version().to_string();
+    let mut overrides = config::blocklist::get_overrides();
+    let mut notified = config::blocklist::get_notified();
+    let notified_count = notified.len();
+
+    let blocked = mod_directories
+        .iter()
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| Some(entry.ok()?.path()))
+        .filter(|root| root.is_dir())
+        .filter(|root| {
+            let entry = match blocklist.entries.iter().find(|entry| !overrides.contains(&entry.id) && entry.matches(root, &game_version)) {
+                Some(entry) => entry,
+                None => return false,
+            };
+
+            let folder_name = root.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+
+            warn!("Mod '{}' is blocked by blocklist entry '{}'. Reason: {}", root.display(), entry.id, entry.reason);
+
+            if notified.insert(format!("{}/{}", entry.id, folder_name)) && notify(folder_name, &entry.reason) {
+                info!("Loading '{}' anyway, overriding blocklist entry '{}'.", root.display(), entry.id);
+
+                if let Err(e) = config::blocklist::set_override(&entry.id, true) {
+                    error!("Failed to save the override for blocklist entry '{}'. Reason: {:?}", entry.id, e);
+                }
+
+                overrides.insert(entry.id.clone());
+                return false;
+            }
+
+            true
+        })
+        .collect();
+
+    if notified.len() != notified_count {
+        if let Err(e) = config::blocklist::set_notified(&notified) {
+            error!("Failed to remember the blocked mods that were reported. Reason: {:?}", e);
+        }
+    }
+
+    blocked
+}
+
+/// Explains why the mod is blocked and returns whether the user wants to load it anyway
+#[cfg(feature = "ui")]
+fn notify(folder_name: &str, reason: &str) -> bool {
+    !utils::env::is_emulator() && menus::confirm_blocked_mod(folder_name, reason)
+}
+
+#[cfg(not(feature = "ui"))]
+fn notify(_folder_name: &str, _reason: &str) -> bool {
+    false
+}
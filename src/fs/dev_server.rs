@@ -0,0 +1,106 @@
+//! Serves the modded files from a server on the network instead of the SD, so mod authors can try out their changes without copying
+//! them over every time. Only meant for development, it is turned on with the `dev_server` flag and the `dev_server_url` field.
+//!
+//! The server only needs to understand plain HTTP requests:
+//!
+//! - `GET {dev_server_url}/{path}`, where `path` is the path of the file in the game, such as
+//!   `fighter/mario/model/body/c00/model.numdlb`. Only the files that mods replace are requested.
+//! - A `200` response holds the whole file as its body. An `ETag` header can be sent along with it, in which case it is sent back in
+//!   `If-None-Match` the next time the file is requested and a `304` response reuses the copy that was already downloaded.
+//! - Any other response, such as a `404` for files the server doesn't have, loads the file from the SD as usual.
+//!
+//! The server is no longer contacted for the rest of the boot once it can't be reached, so that every load doesn't wait on it.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex,
+    },
+};
+
+use smash_arc::Hash40;
+
+use crate::PathExtension;
+
+/// How long a request can take before falling back to the SD, in seconds
+const REQUEST_TIMEOUT: u64 = 2;
+
+/// Read once, the configuration is too slow to go through on every load
+static SERVER_URL: LazyLock<Option<String>> = LazyLock::new(config::dev_server_url);
+static UNREACHABLE: AtomicBool = AtomicBool::new(false);
+
+/// Last version of each file downloaded from the server, along with its ETag
+static CACHE: LazyLock<Mutex<HashMap<PathBuf, (String, Vec<u8>)>>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+pub fn is_enabled() -> bool {
+    SERVER_URL.is_some() && !UNREACHABLE.load(Ordering::Relaxed)
+}
+
+/// Fetches the replacement of a file the game is loading, given the size of the buffer the game made for it. The filesystem is only
+/// locked to look up the path of the file, the request is sent once it is released so that a slow server doesn't stall other loaders.
+pub fn fetch_replacement(hash: Hash40, max_size: usize) -> Option<Vec<u8>> {
+    if !is_enabled() {
+        return None;
+    }
+
+    let local = unsafe { crate::GLOBAL_FILESYSTEM.read().unwrap().local_hash(hash).cloned() }?;
+
+    if local.is_stream() {
+        return None;
+    }
+
+    let data = fetch(&local)?;
+
+    // Only hands over what the game has room for, the size of the files was settled at boot
+    if data.len() > max_size {
+        warn!(
+            "'{}' from the development server is larger than the size set at boot ({:#x} > {:#x}), loading it from the SD instead.",
+            local.display(),
+            data.len(),
+            max_size
+        );
+        return None;
+    }
+
+    Some(data)
+}
+
+/// Fetches a file from the development server. None if the server doesn't provide it, or if it can't be reached.
+fn fetch(local: &Path) -> Option<Vec<u8>> {
+    let server_url = SERVER_URL.as_deref()?;
+    let local_str = local.to_str()?.replace('\\', "/");
+    let url = format!("{}/{}", server_url.trim_end_matches('/'), local_str);
+
+    let mut request = minreq::get(&url).with_timeout(REQUEST_TIMEOUT);
+
+    if let Some((etag, _)) = CACHE.lock().unwrap().get(local) {
+        request = request.with_header("If-None-Match", etag);
+    }
+
+    match request.send() {
+        Ok(response) if response.status_code == 200 => {
+            let data = response.as_bytes().to_vec();
+
+            match response.headers.get("etag") {
+                Some(etag) => {
+                    CACHE.lock().unwrap().insert(local.to_path_buf(), (etag.clone(), data.clone()));
+                },
+                None => {
+                    CACHE.lock().unwrap().remove(local);
+                },
+            }
+
+            debug!("Loaded '{}' from the development server.", local_str);
+            Some(data)
+        },
+        Ok(response) if response.status_code == 304 => CACHE.lock().unwrap().get(local).map(|(_, data)| data.clone()),
+        Ok(_) => None,
+        Err(e) => {
+            UNREACHABLE.store(true, Ordering::Relaxed);
+            warn!("Failed to reach the development server at '{}', loading every file from the SD instead. Reason: {:?}", server_url, e);
+            None
+        },
+    }
+}
@@ -1,16 +1,427 @@
 use std::{
     collections::{HashMap, HashSet},
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
+    sync::{Mutex, RwLock},
 };
 
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
+use config::ConflictStrategy;
 use orbits::{ConflictHandler, ConflictKind, FileLoader, LaunchPad, StandardLoader, Tree};
+use serde::Serialize;
 use skyline::nn::{self, ro::*};
 use smash_arc::Hash40;
 
-use crate::{chainloader::*, utils};
+use super::{progress, MergeOrder};
+use crate::{chainloader::*, utils, PathExtension};
+
+/// How a single contested file was resolved during discovery, meant to be consumed by external tools
+#[derive(Debug, Serialize)]
+pub struct FileResolution {
+    pub hash: u64,
+    pub path: PathBuf,
+    pub winner: PathBuf,
+    pub losers: Vec<PathBuf>,
+}
+
+/// Writes a machine-readable version of the conflict map, so modpack tools can parse it instead of relying on the dialog
+fn write_conflict_report(conflict_map: &HashMap<PathBuf, Vec<PathBuf>>) {
+    let report: Vec<FileResolution> = conflict_map
+        .iter()
+        .filter_map(|(local, roots)| {
+            let (winner, losers) = roots.split_first()?;
+            Some(FileResolution {
+                hash: local.smash_hash().map(|hash| hash.0).unwrap_or_default(),
+                path: local.clone(),
+                winner: winner.clone(),
+                losers: losers.to_vec(),
+            })
+        })
+        .collect();
+
+    match serde_json::to_string_pretty(&report) {
+        Ok(json) => {
+            if let Err(e) = std::fs::write("sd:/ultimate/arcropolis/conflict_report.json", json.as_bytes()) {
+                error!("Failed to write conflict report to sd:/ultimate/arcropolis/conflict_report.json. Reason: {:?}", e);
+            }
+        },
+        Err(e) => error!("Failed to serialize conflict report to JSON. Reason: {:?}", e),
+    }
+}
+
+/// Namespaces left out of discovery, read from the ignore filter since it can't capture anything
+static LAZY_NAMESPACES: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Enabled mod folders to scan when a lazy namespace is first requested, ordered from the highest priority to the lowest
+static LAZY_ROOTS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// Hands over the mod folders collected for the lazy namespaces during discovery
+pub fn take_lazy_roots() -> Vec<PathBuf> {
+    std::mem::take(&mut *LAZY_ROOTS.lock().unwrap())
+}
+
+/// A file of a mod, along with the path of the game it stands for
+#[derive(Debug, Clone)]
+pub struct ModFile {
+    /// Folder of the mod the file belongs to
+    pub root: PathBuf,
+    pub full_path: PathBuf,
+    pub local: PathBuf,
+}
+
+impl ModFile {
+    /// A file laid out like the game, found at `local` in the mod
+    pub fn new(root: &Path, local: &Path) -> Self {
+        Self {
+            root: root.to_path_buf(),
+            full_path: root.join(local),
+            local: local.to_path_buf(),
+        }
+    }
+}
+
+/// Files of the enabled mods laid out relative to a base directory, which can't be discovered at the path they are found at
+pub struct BasedFiles {
+    /// Files that replace or add files of the game, ordered from the highest priority to the lowest
+    pub files: Vec<ModFile>,
+    /// Patches and the other files discovery sets aside
+    pub collected: Vec<ModFile>,
+}
+
+impl BasedFiles {
+    const fn new() -> Self {
+        Self {
+            files: Vec::new(),
+            collected: Vec::new(),
+        }
+    }
+}
+
+static BASED_FILES: Mutex<BasedFiles> = Mutex::new(BasedFiles::new());
+
+/// Hands over the files of the mods that declare a base directory, mounted after every other mod
+pub fn take_based_files() -> BasedFiles {
+    std::mem::replace(&mut *BASED_FILES.lock().unwrap(), BasedFiles::new())
+}
+
+/// Priority and folder name of every mod folder found at boot, enabled or not, keyed by the folder it is mounted from
+static MERGE_ORDER: Mutex<Vec<(PathBuf, MergeOrder)>> = Mutex::new(Vec::new());
+
+/// Hands over where each mod stands when several of them merge into the same file
+pub fn take_merge_order() -> HashMap<PathBuf, MergeOrder> {
+    std::mem::take(&mut *MERGE_ORDER.lock().unwrap()).into_iter().collect()
+}
+
+/// Patterns of the file groups disabled in the active workspace, for each mounted mod folder that has any
+static DISABLED_GROUP_PATTERNS: Mutex<Vec<(PathBuf, Vec<String>)>> = Mutex::new(Vec::new());
+
+/// Hands over the patterns of the disabled file groups collected during discovery
+pub fn take_disabled_group_patterns() -> HashMap<PathBuf, Vec<String>> {
+    std::mem::take(&mut *DISABLED_GROUP_PATTERNS.lock().unwrap()).into_iter().collect()
+}
+
+/// Gets the file patterns of the groups declared in a mod's info.toml that are disabled, as `[[group]]` tables with a `name` and `files`
+fn disabled_group_patterns(root: &Path, disabled_groups: &HashSet<Hash40>) -> Vec<String> {
+    let info = match read_mod_info(root) {
+        Some(info) => info,
+        None => return Vec::new(),
+    };
+
+    let mod_path = root.to_str().unwrap_or_default();
+
+    info.get("group")
+        .and_then(toml::Value::as_array)
+        .into_iter()
+        .flatten()
+        .filter(|group| {
+            group
+                .get("name")
+                .and_then(toml::Value::as_str)
+                .map_or(false, |name| disabled_groups.contains(&config::groups::group_hash(mod_path, name)))
+        })
+        .filter_map(|group| group.get("files").and_then(toml::Value::as_array))
+        .flatten()
+        .filter_map(|pattern| pattern.as_str().map(str::to_string))
+        .collect()
+}
+
+/// Folders that can only be found at the root of a mod, used to recognize where a mod really starts
+static ROOT_NAMESPACES: &[&str] = &[
+    "assist",
+    "boss",
+    "camera",
+    "common",
+    "effect",
+    "enemy",
+    "fighter",
+    "finalsmash",
+    "item",
+    "miihat",
+    "param",
+    "pokemon",
+    "prebuilt;",
+    "render",
+    "sound",
+    "spirits",
+    "stage",
+    "standard",
+    "stream;",
+    "ui",
+];
+
+/// Detects mods that were extracted with an extra folder in between (`mods/CoolMod/CoolMod/fighter/...`) and returns the folder the mod actually starts at.
+///
+/// This is intentionally conservative: every folder along the way must contain nothing but a single folder (hidden files aside), the root itself must not
+/// look like a mod already, and the chain has to end on a folder that contains one of the known namespaces.
+fn find_nested_root(root: &Path) -> Option<PathBuf> {
+    const MAX_DEPTH: usize = 3;
+
+    let has_namespace = |path: &Path| ROOT_NAMESPACES.iter().any(|namespace| path.join(namespace).is_dir());
+
+    if !root.is_dir() || has_namespace(root) {
+        return None;
+    }
+
+    let mut current = root.to_path_buf();
+
+    for _ in 0..MAX_DEPTH {
+        let mut children = std::fs::read_dir(&current)
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| !entry.file_name().to_str().map_or(false, |name| name.starts_with('.')));
+
+        let child = children.next()?.path();
+
+        if children.next().is_some() || !child.is_dir() {
+            return None;
+        }
+
+        if has_namespace(&child) {
+            return Some(child);
+        }
+
+        current = child;
+    }
+
+    None
+}
+
+/// Checks the `default_enabled` flag of a mod's info.toml to know if it should be enabled the first time it is encountered.
+/// Mods without an info.toml, or without the flag, are enabled.
+fn is_enabled_by_default(root: &Path) -> bool {
+    read_mod_info(root).and_then(|info| info.get("default_enabled").and_then(toml::Value::as_bool)).unwrap_or(true)
+}
+
+/// Finds the mods to enable because they are seen for the first time and want to be enabled by default. The cache doubles as the list
+/// of mods we've already seen, so a mod the user disabled doesn't get enabled again on the next boot.
+fn first_seen_mods(mod_roots: &HashMap<Hash40, PathBuf>, mod_cache: &HashSet<Hash40>, presets: &HashSet<Hash40>) -> HashSet<Hash40> {
+    mod_roots
+        .iter()
+        .filter(|(cached_mod, root)| !mod_cache.contains(cached_mod) && !presets.contains(cached_mod) && is_enabled_by_default(root))
+        .map(|(cached_mod, _)| *cached_mod)
+        .collect()
+}
+
+/// Points out the mods whose info.toml declares an `id` that is duplicated or isn't a valid one. Declared ids are never used, mods are told
+/// apart by an id derived from their folder instead, but they usually mean that an info.toml was copied from another mod. Returns the
+/// duplicated ids along with the mods that declare them.
+fn check_declared_mod_ids(mod_roots: &[(PathBuf, PathBuf, i32)]) -> HashMap<i64, Vec<&Path>> {
+    let mut declared: HashMap<i64, Vec<&Path>> = HashMap::new();
+
+    for (root, mounted_root, _) in mod_roots {
+        let id = match read_mod_info(mounted_root).and_then(|info| info.get("id").cloned()) {
+            Some(id) => id,
+            None => continue,
+        };
+
+        match id.as_integer() {
+            Some(id) if id >= 0 => declared.entry(id).or_default().push(root),
+            _ => warn!("Mod '{}' declares an invalid id ({}) in its info.toml, ignoring it.", root.display(), id),
+        }
+    }
+
+    declared.retain(|_, roots| roots.len() > 1);
+
+    for (id, roots) in declared.iter() {
+        let folders: Vec<String> = roots.iter().map(|root| format!("'{}'", root.display())).collect();
+
+        warn!(
+            "Mods {} all declare id {} in their info.toml. Declared ids are ignored, these mods are told apart by their folder instead.",
+            folders.join(", "),
+            id
+        );
+    }
+
+    declared
+}
+
+/// Gets the `base` directory declared in a mod's info.toml, which the files of the mod are laid out relative to instead of mirroring the
+/// full path of the game (`base = "fighter/mario"` maps `model/body/c00/model.numdlb` to `fighter/mario/model/body/c00/model.numdlb`).
+pub fn mod_base(root: &Path) -> Option<PathBuf> {
+    let base = read_mod_info(root)?.get("base")?.as_str()?.trim_matches('/').to_lowercase();
+
+    if base.is_empty() {
+        return None;
+    }
+
+    let base = PathBuf::from(base);
+
+    if !base.components().all(|component| matches!(component, Component::Normal(_))) {
+        warn!("Mod '{}' declares an invalid base ('{}') in its info.toml, ignoring it.", root.display(), base.display());
+        return None;
+    }
+
+    Some(base)
+}
+
+/// Gets the priority of a mod, where mods with a higher priority win file conflicts. The priority set from the mod manager takes precedence
+/// over the `priority` field of the mod's info.toml, and mods that have neither are at 0.
+fn mod_priority(root: &Path, mounted_root: &Path, overrides: &HashMap<u64, i32>) -> i32 {
+    root.to_str()
+        .and_then(|path| overrides.get(&config::priorities::mod_key(path)).copied())
+        .or_else(|| read_mod_info(mounted_root).and_then(|info| info.get("priority").and_then(toml::Value::as_integer)).map(|priority| priority as i32))
+        .unwrap_or(0)
+}
+
+/// Gets the last time anything directly inside of a mod folder was modified. Deeper changes aren't always reflected on the folders
+/// themselves, but checking the direct children catches files being added, removed or replaced without walking the whole mod.
+fn mod_modified_time(root: &Path) -> u64 {
+    let modified = |path: &Path| {
+        std::fs::metadata(path)
+            .and_then(|metadata| metadata.modified())
+            .ok()
+            .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+            .map_or(0, |duration| duration.as_secs())
+    };
+
+    std::fs::read_dir(root)
+        .map(|read_dir| read_dir.filter_map(|entry| entry.ok()).map(|entry| modified(&entry.path())).max().unwrap_or(0))
+        .unwrap_or(0)
+        .max(modified(root))
+}
+
+/// Records the installed mods so the mod manager can tell what changed since the previous boot
+fn take_mod_snapshot(mod_directories: &[Utf8PathBuf]) {
+    let snapshot: config::snapshots::ModSnapshot = mod_directories
+        .iter()
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let root = entry.ok()?.path();
+
+            if !root.is_dir() {
+                return None;
+            }
+
+            Some((root.to_str()?.to_string(), mod_modified_time(&root)))
+        })
+        .collect();
+
+    if let Err(e) = config::snapshots::rotate(&snapshot) {
+        warn!("Failed to save the snapshot of the installed mods. Reason: {:?}", e);
+    }
+}
+
+/// Hashes a mod folder the way presets and the mod cache refer to it. Folders whose path isn't UTF-8 can't be referred to and are skipped.
+fn mod_root_hash(root: &Path) -> Option<Hash40> {
+    match root.to_str() {
+        Some(path) => Some(Hash40::from(path)),
+        None => {
+            warn!("Skipping mod '{}' because its name is not valid UTF-8.", root.display());
+            None
+        },
+    }
+}
+
+/// Lists every folder of the mods directory, enabled or not
+pub fn list_mod_roots() -> HashSet<PathBuf> {
+    std::iter::once(utils::paths::mods())
+        .chain(utils::paths::extra_mods())
+        .flat_map(|directory| match std::fs::read_dir(&directory) {
+            Ok(read_dir) => read_dir.filter_map(|entry| entry.ok().map(|entry| entry.path()).filter(|path| path.is_dir())).collect(),
+            Err(e) => {
+                warn!("Unable to read the mods directory '{}'. Reason: {:?}", directory, e);
+                Vec::new()
+            },
+        })
+        .collect()
+}
+
+/// Finds the mod folders that were added since `known_roots` was listed and returns the ones that should be loaded.
+/// New mods are added to the active preset and to the mod cache the same way they would be on boot, so they stay in the same state afterwards.
+pub fn find_new_mod_roots(known_roots: &HashSet<PathBuf>) -> Vec<PathBuf> {
+    let new_roots: Vec<PathBuf> = list_mod_roots().into_iter().filter(|root| !known_roots.contains(root)).collect();
+
+    if new_roots.is_empty() {
+        return new_roots;
+    }
+
+    // Emulators can't use presets, so mods are only skipped if they start with a period
+    if utils::env::is_emulator() || config::legacy_discovery() {
+        return new_roots
+            .into_iter()
+            .filter(|root| root.file_name().and_then(|name| name.to_str()).map_or(false, |name| !name.starts_with('.')))
+            .collect();
+    }
+
+    let mut presets = config::presets::get_active_preset().unwrap_or_default();
+    let mut mod_cache: HashSet<Hash40> = config::get_mod_cache().unwrap_or_default();
+    let is_overridden = config::mods_path_override().is_some();
+
+    let enabled: Vec<PathBuf> = new_roots
+        .into_iter()
+        .filter(|root| {
+            let hash = match mod_root_hash(root) {
+                Some(hash) => hash,
+                None => return false,
+            };
+            let is_new = mod_cache.insert(hash);
+
+            if is_new && is_enabled_by_default(root) {
+                presets.insert(hash);
+            }
+
+            presets.contains(&hash)
+        })
+        .collect();
+
+    // The mods of an overridden directory are only around for this boot, so they stay out of the saved state
+    if is_overridden {
+        return enabled;
+    }
+
+    if let Err(e) = config::presets::replace_active_preset(&presets) {
+        error!("Failed to add the new mods to the active preset. Reason: {:?}", e);
+    }
+
+    if let Err(e) = config::set_mod_cache(&mod_cache) {
+        error!("Failed to update the mod cache. Reason: {:?}", e);
+    }
+
+    enabled
+}
+
+/// Reads the info.toml of a mod. A mod without one uses the defaults, but one that exists and can't be read is reported.
+fn read_mod_info(root: &Path) -> Option<toml::Value> {
+    let info = match std::fs::read_to_string(root.join("info.toml")) {
+        Ok(info) => info,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(e) => {
+            warn!("Failed to read info.toml for mod '{}', using the defaults. Reason: {}", root.display(), e);
+            return None;
+        },
+    };
+
+    match toml::from_str::<toml::Value>(&info) {
+        Ok(info) => Some(info),
+        Err(e) => {
+            warn!("Failed to parse info.toml for mod '{}'. Reason: {}", root.display(), e);
+            None
+        },
+    }
+}
 
 pub fn perform_discovery() -> LaunchPad<StandardLoader> {
+    let started = std::time::Instant::now();
     let is_emulator = utils::env::is_emulator();
 
     if is_emulator {
@@ -21,42 +432,59 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
 
     let legacy_discovery = config::legacy_discovery();
 
+    // Directories whose children are mod roots: the regular mods directory and the additional ones, plus the extracted modpack bundles
+    let mut mod_directories = vec![mods_path.clone()];
+    mod_directories.extend(utils::paths::extra_mods());
+
+    #[cfg(feature = "modpack")]
+    mod_directories.extend(super::modpack::prepare_modpacks());
+
+    take_mod_snapshot(&mod_directories);
+
     let mut presets = config::presets::get_active_preset().unwrap();
 
+    // The mods of an overridden directory are only around for this boot, so they are enabled without being saved to the preset or cache
+    let is_overridden = config::mods_path_override().is_some();
+
     // Emulators can't use presets, so don't run this logic
     if !is_emulator && !legacy_discovery {
         // Get the mod cache from last run
         let mod_cache: HashSet<Hash40> = config::get_mod_cache().unwrap_or_default();
 
         // Inspect the list of mods to see if some are new ones
-        let new_cache: HashSet<Hash40> = std::fs::read_dir(&mods_path)
-            .unwrap()
+        let mod_roots: HashMap<Hash40, PathBuf> = mod_directories
+            .iter()
+            .filter_map(|directory| std::fs::read_dir(directory).ok())
+            .flatten()
             .filter_map(|path| {
-                let path = PathBuf::from(&mods_path).join(path.unwrap().path());
+                let path = path.ok()?.path();
 
                 if path.is_file() {
                     None
                 } else {
-                    Some(Hash40::from(path.to_str().unwrap()))
+                    Some((mod_root_hash(&path)?, path))
                 }
             })
             .collect();
 
-        let new_mods: HashSet<&Hash40> = new_cache
-            .iter()
-            .filter(|cached_mod| !mod_cache.contains(cached_mod) && !presets.contains(cached_mod))
-            .collect();
+        let new_cache: HashSet<Hash40> = mod_roots.keys().copied().collect();
+
+        let new_mods = first_seen_mods(&mod_roots, &mod_cache, &presets);
 
         // We found hashes that weren't in the cache
         if !new_mods.is_empty() {
             // Add the new mods to the presets file
             presets.extend(new_mods);
             // Save it back
-            config::presets::replace_active_preset(&presets).unwrap();
+            if !is_overridden {
+                config::presets::replace_active_preset(&presets).unwrap();
+            }
         }
 
         // No matter what, the cache has to be updated
-        config::set_mod_cache(&new_cache).unwrap();
+        if !is_overridden {
+            config::set_mod_cache(&new_cache).unwrap();
+        }
     }
 
     #[cfg(feature = "ui")]
@@ -64,39 +492,60 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
 
     // If the user edited their mods again, we'll have to reload them here. This is obviously bad and inefficient but it wouldn't be ARCropolis if it wasn't.
     // Consider loading the active presets in a static RwLock so everything can manipulate them without reloading
-    let presets = config::presets::get_active_preset().unwrap();
+    let presets = if is_overridden { presets } else { config::presets::get_active_preset().unwrap() };
+
+    // Known incompatible mods stay out no matter what the presets say
+    let blocked_mods = super::blocklist::find_blocked_mods(&mod_directories);
 
     let filter = |path: &Path| {
+        if blocked_mods.contains(path) {
+            return false;
+        }
+
         // If we're not running on emulator
         if !is_emulator && !legacy_discovery {
             // If it's not in the presets, don't load
-            presets.contains(&Hash40::from(path.to_str().unwrap()))
+            mod_root_hash(path).map_or(false, |hash| presets.contains(&hash))
         } else {
             // Legacy filter, load the mod except if it has a period at the start of the name
 
             Utf8Path::from_path(path)
-                .unwrap()
-                .file_name()
+                .and_then(|path| path.file_name())
                 .map(|name| !name.starts_with('.'))
                 .unwrap_or(false)
         }
     };
 
+    let lazy_namespaces = config::lazy_namespaces();
+    *LAZY_NAMESPACES.write().unwrap() = lazy_namespaces.clone();
+
     let ignore = |path: &Path| {
-        let name = if let Some(name) = path.file_name().and_then(|x| x.to_str()) { name } else { return false };
+        progress::entry_scanned();
+
+        let name = match path.file_name().map(|x| x.to_str()) {
+            Some(Some(name)) => name,
+            Some(None) => {
+                // Game paths are hashed from strings, so such a file could never replace anything
+                warn!("Skipping '{}' because its name is not valid UTF-8.", path.display());
+                return true;
+            },
+            None => return false,
+        };
+
+        // Lazy namespaces are scanned on the first request instead
+        let is_lazy = path
+            .components()
+            .next()
+            .and_then(|component| component.as_os_str().to_str())
+            .map_or(false, |namespace| LAZY_NAMESPACES.read().unwrap().iter().any(|lazy| lazy == namespace));
 
         let is_root = path.parent().map(|parent| parent.as_os_str().is_empty()).unwrap_or(true);
 
         let is_dot = name.starts_with('.');
 
-        let is_out_of_region = if let Some(index) = name.find('+') {
-            let (_, end) = name.split_at(index + 1);
-            !end.starts_with(&config::region().to_string())
-        } else {
-            false
-        };
+        let is_out_of_region = crate::is_out_of_region(name, &config::region().to_string());
 
-        is_root || is_dot || is_out_of_region
+        is_root || is_dot || is_out_of_region || is_lazy
     };
 
     let collect = |x: &Path| {
@@ -121,15 +570,16 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
 
                     "motdiff",
 
-                    "yml"
+                    "yml",
+
+                    "bsdiff",
+
+                    "template",
+
+                    "music"
                 ];
                 RESERVED_NAMES.contains(&name) || {
-                    let is_out_of_region = if let Some(index) = name.find('+') {
-                        let (_, end) = name.split_at(index + 1);
-                        !end.starts_with(&config::region().to_string())
-                    } else {
-                        false
-                    };
+                    let is_out_of_region = crate::is_out_of_region(name, &config::region().to_string());
                     PATCH_EXTENSIONS.iter().any(|x| name.ends_with(x)) && !is_out_of_region
                 }
             },
@@ -137,12 +587,128 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
         }
     };
 
+    let priority_overrides = config::priorities::get_active_overrides();
+
+    // Every mod folder, as (mod folder, folder to mount, priority)
+    let mut mod_roots: Vec<(PathBuf, PathBuf, i32)> = mod_directories
+        .iter()
+        .filter_map(|directory| std::fs::read_dir(directory).ok())
+        .flatten()
+        .filter_map(|entry| {
+            let root = entry.ok()?.path();
+
+            if !root.is_dir() {
+                return None;
+            }
+
+            let nested = find_nested_root(&root);
+
+            if let Some(nested) = nested.as_ref() {
+                warn!(
+                    "Mod '{}' is nested too deeply, loading it from '{}' instead. Consider moving its contents up so that they sit directly in the mod folder.",
+                    root.display(),
+                    nested.display()
+                );
+            }
+
+            let mounted_root = nested.unwrap_or_else(|| root.clone());
+            let priority = mod_priority(&root, &mounted_root, &priority_overrides);
+
+            Some((root, mounted_root, priority))
+        })
+        .collect();
+
+    // The first mod to be mounted wins the files it shares with the others. The mod manager sorts them the same way to show conflicts.
+    let strategy = config::conflict_strategy();
+    strategy.sort_mods(
+        &mut mod_roots,
+        |(root, ..)| root.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default(),
+        |(_, _, priority)| *priority,
+    );
+    config::sort_mods_by_source(&mut mod_roots, |(root, ..)| root.to_string_lossy().to_string(), config::rom_overrides_sd());
+
+    check_declared_mod_ids(&mod_roots);
+
+    // Merged files follow the same order as the files that are replaced whole
+    *MERGE_ORDER.lock().unwrap() = mod_roots
+        .iter()
+        .enumerate()
+        .map(|(position, (_, mounted_root, _))| (mounted_root.clone(), MergeOrder { position }))
+        .collect();
+
+    // Their files would be discovered at the wrong paths, they are scanned under their base here and mounted after every other mod,
+    // in the same order among themselves
+    let mut based = BasedFiles::new();
+
+    mod_roots.retain(|(root, mounted_root, _)| match mod_base(mounted_root) {
+        Some(base) => {
+            if filter(root) {
+                for (local, full_path) in super::scan_late_files(mounted_root, &base, mounted_root.clone()) {
+                    let file = ModFile {
+                        root: mounted_root.clone(),
+                        full_path,
+                        local,
+                    };
+
+                    if collect(&file.local) {
+                        based.collected.push(file);
+                    } else {
+                        based.files.push(file);
+                    }
+                }
+            }
+            false
+        },
+        None => true,
+    });
+
+    *BASED_FILES.lock().unwrap() = based;
+
+    if !lazy_namespaces.is_empty() {
+        *LAZY_ROOTS.lock().unwrap() = mod_roots.iter().filter(|(root, ..)| filter(root)).map(|(_, mounted_root, _)| mounted_root.clone()).collect();
+    }
+
+    let workspace_name = config::workspaces::get_active_workspace_name().unwrap_or_else(|_| String::from("Default"));
+    let disabled_groups = config::groups::get_disabled(&workspace_name);
+
+    if !disabled_groups.is_empty() {
+        *DISABLED_GROUP_PATTERNS.lock().unwrap() = mod_roots
+            .iter()
+            .filter(|(root, ..)| filter(root))
+            .filter_map(|(root, mounted_root, _)| {
+                let patterns = disabled_group_patterns(root, &disabled_groups);
+                (!patterns.is_empty()).then(|| (mounted_root.clone(), patterns))
+            })
+            .collect();
+    }
+
+    // Every mod is mounted on its own, in the order above, since the first mod to provide a file is the one that wins the conflict.
+    // Mods that were extracted one folder too deep get mounted from the nested folder instead of their own root.
+    let enabled_mods = mod_roots.iter().filter(|(root, ..)| filter(root)).count();
+
+    let discover_mods = |launchpad: &mut LaunchPad<StandardLoader>| {
+        let mut conflicts = Vec::new();
+
+        progress::begin(enabled_mods);
+
+        // The presets refer to the mod folder itself, so that's what decides whether the mounted folder gets discovered. Each root is
+        // already known, so it is mounted directly rather than through a listing of the directory it is in.
+        for (_, mounted_root, _) in mod_roots.iter().filter(|(root, ..)| filter(root)) {
+            conflicts.extend(launchpad.discover_in_root(mounted_root));
+            progress::mods_scanned(1);
+        }
+
+        progress::finish();
+
+        conflicts
+    };
+
     let mut launchpad = LaunchPad::new(StandardLoader, ConflictHandler::NoRoot);
 
     launchpad.collecting(collect);
     launchpad.ignoring(ignore);
 
-    let conflicts = launchpad.discover_roots(&mods_path, 1, filter);
+    let conflicts = discover_mods(&mut launchpad);
 
     let has_conflicts = !conflicts.is_empty();
 
@@ -176,7 +742,7 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
         launchpad.collecting(collect);
         launchpad.ignoring(ignore);
 
-        let conflicts = launchpad.discover_roots(utils::paths::mods(), 1, filter);
+        let conflicts = discover_mods(&mut launchpad);
 
         let mut conflict_map: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
 
@@ -195,8 +761,12 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
             }
         }
 
+        write_conflict_report(&conflict_map);
+
         let should_log = match serde_json::to_string_pretty(&conflict_map) {
             Ok(json) => match std::fs::write("sd:/ultimate/arcropolis/conflicts.json", json.as_bytes()) {
+                // The mods are about to be refused with a dialog of their own
+                Ok(_) if strategy == ConflictStrategy::Error => false,
                 Ok(_) => {
                     crate::dialog_error("Conflict file created at sd:/ultimate/arcropolis/conflicts.json. Please open this file in a text editor to preview what mods are conflicting with one another and take the necessary changes to resolve them by either reslotting or removing these mods.");
                     false
@@ -216,13 +786,34 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
         };
 
         if should_log {
-            for (local, roots) in conflict_map {
+            for (local, roots) in conflict_map.iter() {
                 error!("The file {} is used by the following roots:", local.display());
                 for root in roots {
                     error!("{}", root.display());
                 }
             }
         }
+
+        if strategy == ConflictStrategy::Error {
+            refuse_conflicting_mods(&conflict_map);
+            // Lazy namespaces and based mods would otherwise still be loaded from the refused mods
+            LAZY_ROOTS.lock().unwrap().clear();
+            *BASED_FILES.lock().unwrap() = BasedFiles::new();
+            return LaunchPad::new(StandardLoader, ConflictHandler::NoRoot);
+        }
+    }
+
+    let progress = progress::discovery_progress();
+    info!(
+        "Discovered {} mods in {}ms, {} files and folders were scanned.",
+        progress.mods_scanned,
+        started.elapsed().as_millis(),
+        progress.entries_scanned
+    );
+
+    // Compared against the time spent scanning them once requested, which is logged every time one is
+    if !lazy_namespaces.is_empty() {
+        info!("Left the lazy namespaces ({}) out of discovery, they are scanned when first requested.", lazy_namespaces.join(", "));
     }
 
     match mount_prebuilt_nrr(launchpad.tree()) {
@@ -241,6 +832,35 @@ pub fn perform_discovery() -> LaunchPad<StandardLoader> {
     launchpad
 }
 
+/// Maximum amount of mods listed in the dialog when refusing to load conflicting mods, the complete list is in conflicts.json
+const MAX_REFUSED_MODS: usize = 20;
+
+/// Lists every mod involved in a conflict once, sorted by path
+fn conflicting_mods(conflict_map: &HashMap<PathBuf, Vec<PathBuf>>) -> Vec<String> {
+    let mut roots: Vec<String> = conflict_map.values().flatten().map(|root| root.display().to_string()).collect();
+    roots.sort();
+    roots.dedup();
+    roots
+}
+
+/// Tells the user that no mod is loaded because of the conflicts, as asked for by the `Error` conflict strategy
+fn refuse_conflicting_mods(conflict_map: &HashMap<PathBuf, Vec<PathBuf>>) {
+    let roots = conflicting_mods(conflict_map);
+
+    error!("Not loading any mod because {} of them conflict with one another.", roots.len());
+
+    let mut listed = roots.iter().take(MAX_REFUSED_MODS).map(|root| format!("* {}", root)).collect::<Vec<_>>().join("\n");
+
+    if roots.len() > MAX_REFUSED_MODS {
+        listed.push_str(&format!("\n...and {} more", roots.len() - MAX_REFUSED_MODS));
+    }
+
+    crate::dialog_error(format!(
+        "No mod was loaded, as conflicts are set to be refused and the following mods conflict with one another:\n{}\n\nThe conflicting files are listed in sd:/ultimate/arcropolis/conflicts.json.",
+        listed
+    ));
+}
+
 fn mount_prebuilt_nrr<A: FileLoader>(tree: &Tree<A>) -> Result<Option<RegistrationInfo>, NrrRegistrationFailedError>
 where
     <A as FileLoader>::ErrorType: std::fmt::Debug,
@@ -353,3 +973,108 @@ pub fn load_and_run_plugins(plugins: &[(PathBuf, PathBuf)]) {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_mods_are_seeded_from_default_enabled() {
+        let mods = std::env::temp_dir().join("discover_new_mods_are_seeded_from_default_enabled");
+        let _ = std::fs::remove_dir_all(&mods);
+
+        let roots: HashMap<Hash40, PathBuf> = ["Enabled", "Disabled", "NoInfo", "Seen"]
+            .iter()
+            .map(|name| {
+                let root = mods.join(name);
+                std::fs::create_dir_all(&root).unwrap();
+                (Hash40::from(root.to_str().unwrap()), root)
+            })
+            .collect();
+
+        std::fs::write(mods.join("Enabled/info.toml"), "default_enabled = true").unwrap();
+        std::fs::write(mods.join("Disabled/info.toml"), "default_enabled = false").unwrap();
+        std::fs::write(mods.join("Seen/info.toml"), "default_enabled = true").unwrap();
+
+        let hash = |name: &str| Hash40::from(mods.join(name).to_str().unwrap());
+
+        // A mod that was seen on a previous boot stays the way the user left it
+        let cache = HashSet::from([hash("Seen")]);
+        let seeded = first_seen_mods(&roots, &cache, &HashSet::new());
+
+        assert_eq!(seeded, HashSet::from([hash("Enabled"), hash("NoInfo")]));
+
+        // Once seen, nothing is seeded again
+        let cache: HashSet<Hash40> = roots.keys().copied().collect();
+        assert!(first_seen_mods(&roots, &cache, &HashSet::new()).is_empty());
+
+        std::fs::remove_dir_all(&mods).unwrap();
+    }
+
+    #[test]
+    fn duplicated_mod_ids_are_reported() {
+        let mods = std::env::temp_dir().join("discover_duplicated_mod_ids_are_reported");
+        let _ = std::fs::remove_dir_all(&mods);
+
+        let mod_roots: Vec<(PathBuf, PathBuf, i32)> = [("First", "id = 0"), ("Second", "id = 0"), ("Own", "id = 1"), ("Negative", "id = -1")]
+            .iter()
+            .map(|(name, info)| {
+                let root = mods.join(name);
+                std::fs::create_dir_all(&root).unwrap();
+                std::fs::write(root.join("info.toml"), info).unwrap();
+                (root.clone(), root, 0)
+            })
+            .collect();
+
+        let duplicated = check_declared_mod_ids(&mod_roots);
+
+        // Only the id shared by two mods is reported, along with both of their folders
+        assert_eq!(duplicated.len(), 1);
+        assert_eq!(duplicated[&0], [mods.join("First"), mods.join("Second")]);
+
+        std::fs::remove_dir_all(&mods).unwrap();
+    }
+
+    #[test]
+    fn refused_mods_are_listed_once() {
+        let conflict_map = HashMap::from([
+            (PathBuf::from("fighter/mario/model.numdlb"), vec![PathBuf::from("sd:/ultimate/mods/alpha"), PathBuf::from("sd:/ultimate/mods/Charlie")]),
+            (PathBuf::from("ui/param/database.prc"), vec![PathBuf::from("sd:/ultimate/mods/Bravo"), PathBuf::from("sd:/ultimate/mods/Charlie")]),
+        ]);
+
+        // Charlie conflicts with both of the other mods, but is only listed once
+        assert_eq!(conflicting_mods(&conflict_map), ["sd:/ultimate/mods/Bravo", "sd:/ultimate/mods/Charlie", "sd:/ultimate/mods/alpha"]);
+    }
+
+    #[test]
+    fn unreadable_info_tomls_fall_back_to_the_defaults() {
+        let root = std::env::temp_dir().join("discover_unreadable_info_tomls_fall_back_to_the_defaults");
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root).unwrap();
+
+        assert!(read_mod_info(&root).is_none());
+
+        std::fs::write(root.join("info.toml"), "default_enabled = false").unwrap();
+        assert!(!is_enabled_by_default(&root));
+
+        // Something that exists but can't be read as a file
+        std::fs::remove_file(root.join("info.toml")).unwrap();
+        std::fs::create_dir(root.join("info.toml")).unwrap();
+        assert!(read_mod_info(&root).is_none());
+        assert!(is_enabled_by_default(&root));
+
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn non_utf8_mod_folders_are_skipped() {
+        use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+        let valid = Path::new("sd:/ultimate/mods/Skins");
+        let invalid = Path::new(OsStr::from_bytes(b"sd:/ultimate/mods/Skins\xff"));
+
+        assert_eq!(mod_root_hash(valid), Some(Hash40::from("sd:/ultimate/mods/Skins")));
+        assert_eq!(mod_root_hash(invalid), None);
+    }
+}
@@ -0,0 +1,129 @@
+//! Sizes that some kinds of files can't go past, no matter how much room is made for them in the arc. Some files are read by the game
+//! with fixed-size structures, so an oversized file corrupts memory instead of failing to load. The limits are read from the
+//! `size_limits` field of the configuration, keyed by file extension such as `{ "nutexb": 134217728 }`, so they can be set as they are
+//! found without touching the code. No file is checked unless the configuration sets a limit for it.
+
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use smash_arc::Hash40;
+
+use crate::PathExtension;
+
+/// Largest size in bytes the game can handle, keyed by lowercase file extension
+pub type SizeLimits = HashMap<String, usize>;
+
+/// Replacement that is larger than what the game can handle for its kind of file
+#[derive(Debug)]
+pub struct OversizedFile {
+    pub hash: Hash40,
+    pub local: PathBuf,
+    pub size: usize,
+    pub max_size: usize,
+}
+
+impl OversizedFile {
+    pub fn describe(&self) -> String {
+        let extension = self.local.extension().and_then(|extension| extension.to_str()).unwrap_or_default();
+
+        format!(
+            "'{}' is too large for the game ({:#x} bytes, a .{} file can be at most {:#x} bytes)",
+            self.local.display(),
+            self.size,
+            extension,
+            self.max_size
+        )
+    }
+}
+
+/// Gets the largest size the game can handle for a file, for the kinds of files that have a limit
+fn limit_for(limits: &SizeLimits, local: &Path) -> Option<usize> {
+    // Streamed straight from the SD, they never go through the game's buffers
+    if local.is_stream() {
+        return None;
+    }
+
+    let extension = local.extension()?.to_str()?.to_lowercase();
+    limits.get(&extension).copied()
+}
+
+/// Checks a single replacement against the limits, returning it if it is too large
+pub fn check_file(limits: &SizeLimits, hash: Hash40, local: &Path, size: usize) -> Option<OversizedFile> {
+    let max_size = limit_for(limits, local)?;
+
+    (size > max_size).then(|| OversizedFile { hash, local: local.to_path_buf(), size, max_size })
+}
+
+/// Finds the replacements that are too large for the game, given the size and path of every replacement
+pub fn find_oversized_files(limits: &SizeLimits, sizes: &HashMap<Hash40, usize>, paths: &HashMap<Hash40, PathBuf>) -> Vec<OversizedFile> {
+    if limits.is_empty() {
+        return Vec::new();
+    }
+
+    let mut oversized: Vec<OversizedFile> =
+        paths.iter().filter_map(|(hash, local)| check_file(limits, *hash, local, *sizes.get(hash)?)).collect();
+
+    oversized.sort_by(|a, b| a.local.cmp(&b.local));
+    oversized
+}
+
+/// Logs every oversized file and tells the user about them, since the files will look like they aren't replaced
+pub fn report(oversized: &[OversizedFile]) {
+    if oversized.is_empty() {
+        return;
+    }
+
+    let lines: Vec<String> = oversized.iter().map(OversizedFile::describe).collect();
+
+    for line in lines.iter() {
+        error!("{}, it will not be replaced.", line);
+    }
+
+    crate::dialog_error(format!(
+        "{} file(s) are too large for the game and will not be replaced:\n\n{}",
+        lines.len(),
+        lines.join("\n")
+    ));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture() -> (SizeLimits, HashMap<Hash40, usize>, HashMap<Hash40, PathBuf>) {
+        let limits = SizeLimits::from([(String::from("nutexb"), 0x100)]);
+
+        let files = [
+            ("fighter/mario/model/body/c00/def_mario_001_col.nutexb", 0x200),
+            ("fighter/mario/model/body/c00/def_mario_001_nor.nutexb", 0x100),
+            ("fighter/mario/model/body/c00/model.numshb", 0x1000),
+            ("stream;/sound/bgm/bgm_test.nutexb", 0x1000),
+        ];
+
+        let sizes = files.iter().map(|(path, size)| (Hash40::from(*path), *size)).collect();
+        let paths = files.iter().map(|(path, _)| (Hash40::from(*path), PathBuf::from(path))).collect();
+
+        (limits, sizes, paths)
+    }
+
+    #[test]
+    fn over_limit_files_are_reported() {
+        let (limits, sizes, paths) = fixture();
+        let oversized = find_oversized_files(&limits, &sizes, &paths);
+
+        assert_eq!(oversized.len(), 1);
+        assert_eq!(oversized[0].local, Path::new("fighter/mario/model/body/c00/def_mario_001_col.nutexb"));
+        assert_eq!(
+            oversized[0].describe(),
+            "'fighter/mario/model/body/c00/def_mario_001_col.nutexb' is too large for the game (0x200 bytes, a .nutexb file can be at most 0x100 bytes)"
+        );
+    }
+
+    #[test]
+    fn nothing_is_checked_without_limits() {
+        let (_, sizes, paths) = fixture();
+        assert!(find_oversized_files(&SizeLimits::new(), &sizes, &paths).is_empty());
+    }
+}
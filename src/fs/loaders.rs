@@ -14,7 +14,7 @@ use serde_yaml::from_str;
 use smash_bgm_property::BgmPropertyFile;
 use xml::common::Position;
 
-use super::*;
+use super::{music::MusicTrack, *};
 
 #[derive(Debug, Deserialize)]
 pub struct Xmsbt {
@@ -73,6 +73,7 @@ enum ApiLoadType {
     Nus3audioPatch,
     MotionlistPatch,
     BgmPropertyPatch,
+    BinaryPatch,
     Generic,
     Stream,
     Extension,
@@ -92,6 +93,8 @@ impl ApiLoadType {
             Ok(ApiLoadType::MotionlistPatch)
         } else if root.ends_with("patch-bgm_property") {
             Ok(ApiLoadType::BgmPropertyPatch)
+        } else if root.ends_with("patch-binary") {
+            Ok(ApiLoadType::BinaryPatch)
         } else if root.ends_with("generic-cb") {
             Ok(ApiLoadType::Generic)
         } else if root.ends_with("stream-cb") {
@@ -144,17 +147,25 @@ impl ApiLoadType {
                 Ok((data.len(), data))
             },
             ApiLoadType::PrcPatch => {
-                let patches = if let Some(patches) = ApiLoader::get_prc_patches_for_hash(local.smash_hash()?) {
-                    patches
-                } else {
-                    return Err(ApiLoaderError::Other("[ARCropolis::loader] No patches found for file of type PRC!".to_string()));
+                // The tracks added by mods route the database through here even when no mod patches it
+                let is_music_database = local == Path::new(music::UI_BGM_DB) && !ApiLoader::get_music_tracks().is_empty();
+
+                let patches = match ApiLoader::get_prc_patches_for_hash(local.smash_hash()?) {
+                    Some(patches) => patches.as_slice(),
+                    None if is_music_database => &[],
+                    None => return Err(ApiLoaderError::Other("[ARCropolis::loader] No patches found for file of type PRC!".to_string())),
                 };
 
                 let data = ApiLoader::handle_load_base_file(local)?;
                 let mut param_data = prcx::read_stream(&mut Cursor::new(data))
                     .map_err(|_| ApiLoaderError::Other("Unable to parse param data!".to_string()))?;
 
-                for patch_path in patches.iter() {
+                // Patches to the UI databases are merged by entry id, so that every mod adding an entry keeps it
+                let keyed_lists = local.file_name().and_then(|name| name.to_str()).and_then(ui_database::keyed_lists);
+                let base_data = keyed_lists.as_ref().map(|_| param_data.clone());
+
+                // Applied from the lowest precedence to the highest, so that the first patch in the merge order overwrites the others
+                for patch_path in patches.iter().rev() {
                     let patch = if let Ok(patch) = prcx::open(patch_path) {
                         patch
                     } else {
@@ -164,7 +175,17 @@ impl ApiLoadType {
                         prcx::read_xml(&mut reader).map_err(|_| ApiLoaderError::Other("Unable to parse param patch data!".to_string()))?
                     };
 
-                    prcx::apply_patch(&patch, &mut param_data).map_err(|_| ApiLoaderError::Other("Unable to patch param data!".to_string()))?;
+                    match (&keyed_lists, &base_data) {
+                        (Some(lists), Some(base_data)) => {
+                            ui_database::merge_patch(&patch, base_data, &mut param_data, lists).map_err(ApiLoaderError::Other)?
+                        },
+                        _ => prcx::apply_patch(&patch, &mut param_data)
+                            .map_err(|_| ApiLoaderError::Other("Unable to patch param data!".to_string()))?,
+                    }
+                }
+
+                if is_music_database {
+                    music::add_tracks_to_database(&mut param_data, ApiLoader::get_music_tracks());
                 }
 
                 let mut writer = Cursor::new(Vec::new());
@@ -180,8 +201,14 @@ impl ApiLoadType {
                 };
 
                 let mut labels: HashMap<String, TextType> = HashMap::new();
+                // Which mod folder each label was taken from, and whether it came from a regional patch
+                let mut providers: HashMap<String, (&Path, bool)> = HashMap::new();
+                let depth = local.components().count();
 
                 for patch_path in patches.iter() {
+                    let root = patch_path.ancestors().nth(depth).unwrap_or(patch_path);
+                    let is_regional = patch_path.file_name().and_then(|name| name.to_str()).map_or(false, |name| name.contains('+'));
+
                     let mut reader = Cursor::new(fs::read(patch_path)?);
                     let xmsbt: Xmsbt = match serde_xml_rs::from_reader(&mut reader) {
                         Ok(xmsbt) => xmsbt,
@@ -199,6 +226,28 @@ impl ApiLoadType {
                     };
 
                     for entry in &xmsbt.entries {
+                        // Patches are sorted by merge order, so the first mod to provide a label has the highest precedence and keeps it.
+                        // Within a single mod, the patch for the current region takes precedence over the generic one.
+                        match providers.get(&entry.label) {
+                            Some((provider, provider_is_regional)) if *provider == root => {
+                                if *provider_is_regional && !is_regional {
+                                    continue;
+                                }
+                            },
+                            Some((provider, _)) => {
+                                debug!(
+                                    "XMSBT label {} from `{}` was skipped, `{}` provides it with a higher priority.",
+                                    entry.label,
+                                    root.display(),
+                                    provider.display()
+                                );
+                                continue;
+                            },
+                            None => {},
+                        }
+
+                        providers.insert(entry.label.to_owned(), (root, is_regional));
+
                         if entry.base64.unwrap_or(false) {
                             match BASE64_STANDARD.decode::<String>(entry.text.value.to_owned()) {
                                 Ok(mut decoded) => {
@@ -364,7 +413,7 @@ impl ApiLoadType {
                     let mut full_patches = 0;
 
                     for full_patch in yml_patches.iter() {
-                        println!("[ARCropolis::loader] Replacing motion_list.bin with {}.", full_patch.to_str().unwrap());
+                        println!("[ARCropolis::loader] Replacing motion_list.bin with {}.", full_patch.display());
                         let mut contents: String = String::default();
                         File::open(full_patch)?.read_to_string(&mut contents)?;
                         if let Some(full) = from_str(&contents)? {
@@ -374,7 +423,7 @@ impl ApiLoadType {
                     }
 
                     if full_patches > 1 {
-                        println!("[ARCropolis::loader] Multiple motion_list.yml files found for {}.", local.to_str().unwrap());
+                        println!("[ARCropolis::loader] Multiple motion_list.yml files found for {}.", local.display());
                         println!("                     The last applied .yml file will be used.");
                     }
                 }
@@ -399,10 +448,12 @@ impl ApiLoadType {
                 Ok((data.len(), data))
             },
             ApiLoadType::BgmPropertyPatch => {
-                let patches = if let Some(patches) = ApiLoader::get_bgm_property_patches_for_hash(local.smash_hash()?) {
-                    patches
-                } else {
-                    return Err(ApiLoaderError::Other("[ARCropolis::loader] No patches found for file bgm_property.bin!".to_string()));
+                let tracks = ApiLoader::get_music_tracks();
+
+                let patches = match ApiLoader::get_bgm_property_patches_for_hash(local.smash_hash()?) {
+                    Some(patches) => patches.as_slice(),
+                    None if !tracks.is_empty() => &[],
+                    None => return Err(ApiLoaderError::Other("[ARCropolis::loader] No patches found for file bgm_property.bin!".to_string())),
                 };
 
                 let data = ApiLoader::handle_load_base_file(local)?;
@@ -415,11 +466,35 @@ impl ApiLoadType {
                     bgm_property.entries.append(&mut patch_file.entries);
                 }
 
+                if !tracks.is_empty() {
+                    // The base tracks are looked up in the vanilla database, where their audio is named
+                    let database = ApiLoader::handle_load_vanilla_file(Path::new(music::UI_BGM_DB))
+                        .ok()
+                        .and_then(|data| prcx::read_stream(&mut Cursor::new(data)).ok());
+
+                    music::add_tracks_to_bgm_property(&mut bgm_property, database.as_ref(), tracks);
+                }
+
                 let mut writer = Cursor::new(Vec::new());
                 bgm_property.write(&mut writer).unwrap();
                 let data = writer.into_inner();
                 Ok((data.len(), data))
             },
+            ApiLoadType::BinaryPatch => {
+                let patches = if let Some(patches) = ApiLoader::get_binary_patches_for_hash(local.smash_hash()?) {
+                    patches
+                } else {
+                    return Err(ApiLoaderError::Other(format!("[ARCropolis::loader] No binary patches found for file {}!", local.display())));
+                };
+
+                let mut data = ApiLoader::handle_load_base_file(local)?;
+
+                for patch_path in patches.iter() {
+                    data = apply_chained_binary_patch(data, &fs::read(patch_path)?, patch_path);
+                }
+
+                Ok((data.len(), data))
+            },
             ApiLoadType::Generic if let ApiCallback::GenericCallback(cb) = usr_fn => {
                 let hash = local.smash_hash()?;
                 let mut size = 0;
@@ -464,6 +539,63 @@ impl ApiLoadType {
     }
 }
 
+/// Magic at the start of every binary patch
+static BINARY_PATCH_MAGIC: &[u8; 8] = b"ARCDIFF1";
+/// Magic, size of the source file (u64) and CRC32 of the source file (u32), all little-endian
+const BINARY_PATCH_HEADER_SIZE: usize = 0x14;
+
+/// Applies a binary patch to the data of a file.
+///
+/// Binary patches are `.bsdiff` files placed at the path of the file they patch. They start with a small header describing the file
+/// they were made against, followed by the output of `bsdiff::diff` from the [bsdiff](https://crates.io/crates/bsdiff) crate (the
+/// raw format of that crate, not the `BSDIFF40` one of the original tool):
+///
+/// | Offset | Size | Description                         |
+/// |--------|------|-------------------------------------|
+/// | 0x0    | 0x8  | `ARCDIFF1`                          |
+/// | 0x8    | 0x8  | Size of the source file             |
+/// | 0x10   | 0x4  | CRC32 of the source file            |
+/// | 0x14   | ...  | bsdiff patch                        |
+///
+/// The patch is rejected if the source doesn't match, since bsdiff would happily produce garbage out of the wrong file.
+fn apply_binary_patch(source: &[u8], patch: &[u8]) -> Result<Vec<u8>, ApiLoaderError> {
+    if patch.len() < BINARY_PATCH_HEADER_SIZE || &patch[..0x8] != BINARY_PATCH_MAGIC {
+        return Err(ApiLoaderError::Other("This isn't a binary patch file!".to_string()));
+    }
+
+    let source_size = u64::from_le_bytes(patch[0x8..0x10].try_into().unwrap());
+    let source_crc = u32::from_le_bytes(patch[0x10..0x14].try_into().unwrap());
+
+    if source_size != source.len() as u64 || source_crc != crc32fast::hash(source) {
+        return Err(ApiLoaderError::Other(format!(
+            "The patch was made for a different version of the file (expected size {:#x} and CRC32 {:#010x}, found size {:#x} and CRC32 {:#010x})",
+            source_size,
+            source_crc,
+            source.len(),
+            crc32fast::hash(source)
+        )));
+    }
+
+    let mut patched = Vec::new();
+    bsdiff::patch(source, &mut &patch[BINARY_PATCH_HEADER_SIZE..], &mut patched)?;
+
+    Ok(patched)
+}
+
+/// Applies one of the binary patches of a file on top of the ones before it, in merge order. Each patch is checked against the data as
+/// the previous patches left it rather than against the vanilla file, so a patch has to be made against that result to be chained.
+/// A patch that doesn't match is skipped and the data is kept as it was, which is safer than handing the game the output of a diff
+/// applied to the wrong data.
+fn apply_chained_binary_patch(data: Vec<u8>, patch: &[u8], patch_path: &Path) -> Vec<u8> {
+    match apply_binary_patch(&data, patch) {
+        Ok(patched) => patched,
+        Err(e) => {
+            error!("Skipping binary patch {}. Reason: {}", patch_path.display(), e);
+            data
+        },
+    }
+}
+
 #[derive(Copy, Clone)]
 pub enum ApiCallback {
     None,
@@ -482,8 +614,34 @@ struct ApiFunctionEntry {
     pub functions: VecDeque<(PathBuf, ApiCallback)>,
 }
 
+/// Where a mod stands when several of them merge into the same message or param file
+#[derive(Debug, Clone)]
+pub struct MergeOrder {
+    /// Position of the mod in the mount order of the conflict strategy, where the first mod wins
+    pub position: usize,
+}
+
+/// Sorts the patches of a file by precedence, which is the order the mods are mounted in for the configured conflict strategy. With
+/// the default `Priority` strategy, that is the highest priority first, then by folder name. Within a single mod, the patch for the
+/// current region comes before the generic one. Wherever patches overlap, the value of the first one in this order is the one kept,
+/// whatever order the mods were discovered in.
+fn merge_key(merge_order: &HashMap<PathBuf, MergeOrder>, path: &Path) -> (bool, usize, bool) {
+    let order = path.ancestors().find_map(|ancestor| merge_order.get(ancestor));
+    let is_generic = !path.file_name().and_then(|name| name.to_str()).map_or(false, |name| name.contains('+'));
+
+    // Patches that don't belong to a mod found at boot come after every mod
+    (order.is_none(), order.map_or(0, |order| order.position), is_generic)
+}
+
+fn insert_merged_patch(merge_order: &HashMap<PathBuf, MergeOrder>, list: &mut Vec<PathBuf>, path: &Path) {
+    let key = merge_key(merge_order, path);
+    let index = list.partition_point(|other| merge_key(merge_order, other) <= key);
+    list.insert(index, path.to_path_buf());
+}
+
 #[derive(Default)]
 pub struct ApiLoader {
+    merge_order: HashMap<PathBuf, MergeOrder>,
     function_map: HashMap<Hash40, UnsafeCell<ApiFunctionEntry>>,
     stream_size_map: UnsafeCell<HashMap<PathBuf, usize>>,
     param_patches: HashMap<Hash40, Vec<PathBuf>>,
@@ -491,6 +649,8 @@ pub struct ApiLoader {
     nus3audio_patches: HashMap<Hash40, Vec<PathBuf>>,
     motionlist_patches: HashMap<Hash40, Vec<PathBuf>>,
     bgm_property_patches: HashMap<Hash40, Vec<PathBuf>>,
+    binary_patches: HashMap<Hash40, Vec<PathBuf>>,
+    music_tracks: Vec<MusicTrack>,
 }
 
 unsafe impl Send for ApiLoader {}
@@ -592,20 +752,67 @@ impl ApiLoader {
         cached.virt().loader.bgm_property_patches.get(&hash)
     }
 
+    pub fn get_binary_patches_for_hash(hash: Hash40) -> Option<&'static Vec<PathBuf>> {
+        let filesystem = unsafe { &*crate::GLOBAL_FILESYSTEM.get_mut().unwrap() };
+        let cached = filesystem.get();
+
+        cached.virt().loader.binary_patches.get(&hash)
+    }
+
+    pub fn get_music_tracks() -> &'static [MusicTrack] {
+        let filesystem = unsafe { &*crate::GLOBAL_FILESYSTEM.get_mut().unwrap() };
+        let cached = filesystem.get();
+
+        &cached.virt().loader.music_tracks
+    }
+
+    /// Must be set before adding the PRC and MSBT patches, which are kept sorted by it
+    pub fn set_merge_order(&mut self, merge_order: HashMap<PathBuf, MergeOrder>) {
+        self.merge_order = merge_order;
+    }
+
     pub fn insert_prc_patch(&mut self, hash: Hash40, path: &Path) {
-        if let Some(list) = self.param_patches.get_mut(&hash) {
-            list.push(path.to_path_buf())
-        } else {
-            self.param_patches.insert(hash, vec![path.to_path_buf()]);
+        insert_merged_patch(&self.merge_order, self.param_patches.entry(hash).or_default(), path);
+    }
+
+    /// Amount of files provided by plugins through the API
+    pub fn callback_count(&self) -> usize {
+        self.function_map.len()
+    }
+
+    /// Whether the file is served as it is on the SD, without a callback or a patch changing its contents
+    pub fn is_plain(&self, hash: Hash40) -> bool {
+        !self.function_map.contains_key(&hash)
+            && [
+                &self.param_patches,
+                &self.msbt_patches,
+                &self.nus3audio_patches,
+                &self.motionlist_patches,
+                &self.bgm_property_patches,
+                &self.binary_patches,
+            ]
+            .iter()
+            .all(|patches| patches.get(&hash).map_or(true, |list| list.is_empty()))
+    }
+
+    pub fn has_prc_patches(&self, hash: Hash40) -> bool {
+        self.param_patches.get(&hash).map_or(false, |list| !list.is_empty())
+    }
+
+    pub fn prc_patches(&self, hash: Hash40) -> &[PathBuf] {
+        self.param_patches.get(&hash).map_or(&[], |list| list.as_slice())
+    }
+
+    /// Drops the PRC patches that come from the mod at `root`. The lists left empty are kept, so the files they belong to keep going
+    /// through the loader and are served unpatched.
+    pub fn remove_prc_patches(&mut self, root: &Path) {
+        for list in self.param_patches.values_mut() {
+            list.retain(|path| !path.starts_with(root));
         }
     }
 
     pub fn insert_msbt_patch(&mut self, hash: Hash40, path: &Path) {
-        if let Some(list) = self.msbt_patches.get_mut(&hash) {
-            list.push(path.to_path_buf())
-        } else {
-            self.msbt_patches.insert(hash, vec![path.to_path_buf()]);
-        }
+        insert_merged_patch(&self.merge_order, self.msbt_patches.entry(hash).or_default(), path);
     }
 
     pub fn insert_nus3audio_patch(&mut self, hash: Hash40, path: &Path) {
@@ -632,6 +839,19 @@ impl ApiLoader {
         }
     }
 
+    /// Binary patches are chained in merge order, so it has to be set first as well
+    pub fn insert_binary_patch(&mut self, hash: Hash40, path: &Path) {
+        insert_merged_patch(&self.merge_order, self.binary_patches.entry(hash).or_default(), path);
+    }
+
+    pub fn insert_music_track(&mut self, track: MusicTrack) {
+        self.music_tracks.push(track);
+    }
+
+    pub fn has_music_tracks(&self) -> bool {
+        !self.music_tracks.is_empty()
+    }
+
     fn get_stream_cb_path(&self, local: &Path) -> Option<String> {
         if let Some((root_path, callback)) = self.use_virtual_file(local) {
             let result = match ApiLoadType::from_root(root_path) {
@@ -772,7 +992,7 @@ impl FileLoader for ArcLoader {
 
     #[track_caller]
     fn load_path(&self, _root_path: &Path, local_path: &Path) -> Result<Vec<u8>, Self::ErrorType> {
-        let hash = if local_path.to_str().unwrap().is_empty() {
+        let hash = if local_path.as_os_str().is_empty() {
             Ok(Hash40(u64::from_str_radix(&local_path.to_str().unwrap()[2..], 16).unwrap()))
         } else {
             crate::get_smash_hash(local_path)
@@ -784,3 +1004,114 @@ impl FileLoader for ArcLoader {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use config::ConflictStrategy;
+
+    use super::*;
+
+    /// Three mods patching overlapping labels of the same message file, as (folder name, priority, labels and values)
+    const MODS: &[(&str, i32, &[(&str, &str)])] = &[
+        ("charlie", 0, &[("title", "charlie"), ("subtitle", "charlie")]),
+        ("alpha", 0, &[("title", "alpha"), ("caption", "alpha")]),
+        ("bravo", 5, &[("subtitle", "bravo"), ("caption", "bravo")]),
+    ];
+
+    /// Resolves every label to the value of the first patch providing it, the way overlapping merges are applied
+    fn resolve(strategy: ConflictStrategy) -> HashMap<&'static str, &'static str> {
+        let mut mods: Vec<_> = MODS.iter().collect();
+        strategy.sort_mods(&mut mods, |(name, ..)| name.to_string(), |(_, priority, _)| *priority);
+
+        let merge_order: HashMap<PathBuf, MergeOrder> = mods
+            .iter()
+            .enumerate()
+            .map(|(position, (name, ..))| (Path::new("sd:/ultimate/mods").join(name), MergeOrder { position }))
+            .collect();
+
+        // Listed in discovery order, which has nothing to do with the result
+        let mut patches = Vec::new();
+        for (name, ..) in MODS.iter() {
+            let path = Path::new("sd:/ultimate/mods").join(name).join("ui/message/msg_name.xmsbt");
+            insert_merged_patch(&merge_order, &mut patches, &path);
+        }
+
+        let mut resolved = HashMap::new();
+        for patch in patches.iter() {
+            let (_, _, labels) = MODS.iter().find(|(name, ..)| patch.starts_with(Path::new("sd:/ultimate/mods").join(name))).unwrap();
+
+            for (label, value) in labels.iter() {
+                resolved.entry(*label).or_insert(*value);
+            }
+        }
+
+        resolved
+    }
+
+    #[test]
+    fn merges_follow_the_priority_then_the_folder_name() {
+        let resolved = resolve(ConflictStrategy::Priority);
+
+        assert_eq!(resolved["title"], "alpha");
+        assert_eq!(resolved["subtitle"], "bravo");
+        assert_eq!(resolved["caption"], "bravo");
+    }
+
+    #[test]
+    fn merges_follow_the_conflict_strategy() {
+        let resolved = resolve(ConflictStrategy::FirstWins);
+        assert_eq!((resolved["title"], resolved["subtitle"], resolved["caption"]), ("alpha", "bravo", "alpha"));
+
+        let resolved = resolve(ConflictStrategy::LastWins);
+        assert_eq!((resolved["title"], resolved["subtitle"], resolved["caption"]), ("charlie", "charlie", "bravo"));
+    }
+
+    fn make_binary_patch(source: &[u8], target: &[u8]) -> Vec<u8> {
+        let mut patch = BINARY_PATCH_MAGIC.to_vec();
+        patch.extend((source.len() as u64).to_le_bytes());
+        patch.extend(crc32fast::hash(source).to_le_bytes());
+        bsdiff::diff(source, target, &mut patch).unwrap();
+        patch
+    }
+
+    #[test]
+    fn binary_patches_reproduce_the_target() {
+        let vanilla = b"param: attack = 10, speed = 1.5".to_vec();
+        let target = b"param: attack = 12, speed = 1.5".to_vec();
+
+        assert_eq!(apply_binary_patch(&vanilla, &make_binary_patch(&vanilla, &target)).unwrap(), target);
+    }
+
+    #[test]
+    fn chained_binary_patches_are_checked_against_the_previous_result() {
+        let vanilla = b"param: attack = 10, speed = 1.5".to_vec();
+        let first = b"param: attack = 12, speed = 1.5".to_vec();
+        let second = b"param: attack = 12, speed = 2.0".to_vec();
+
+        let chained = make_binary_patch(&first, &second);
+        let against_vanilla = make_binary_patch(&vanilla, b"param: attack = 99, speed = 1.5");
+
+        let data = apply_chained_binary_patch(vanilla.clone(), &make_binary_patch(&vanilla, &first), Path::new("first.bsdiff"));
+        let data = apply_chained_binary_patch(data, &chained, Path::new("chained.bsdiff"));
+        assert_eq!(data, second);
+
+        // Made against the vanilla file, which is no longer what it gets applied to
+        assert_eq!(apply_chained_binary_patch(data, &against_vanilla, Path::new("vanilla.bsdiff")), second);
+    }
+
+    #[test]
+    fn regional_patches_come_before_generic_ones() {
+        let merge_order = HashMap::from([(PathBuf::from("sd:/ultimate/mods/alpha"), MergeOrder { position: 0 })]);
+        let mut patches = Vec::new();
+
+        insert_merged_patch(&merge_order, &mut patches, Path::new("sd:/ultimate/mods/alpha/ui/message/msg_name.xmsbt"));
+        insert_merged_patch(&merge_order, &mut patches, Path::new("sd:/ultimate/mods/other/ui/message/msg_name.xmsbt"));
+        insert_merged_patch(&merge_order, &mut patches, Path::new("sd:/ultimate/mods/alpha/ui/message/msg_name+us_en.xmsbt"));
+
+        assert_eq!(patches, [
+            PathBuf::from("sd:/ultimate/mods/alpha/ui/message/msg_name+us_en.xmsbt"),
+            PathBuf::from("sd:/ultimate/mods/alpha/ui/message/msg_name.xmsbt"),
+            PathBuf::from("sd:/ultimate/mods/other/ui/message/msg_name.xmsbt"),
+        ]);
+    }
+}
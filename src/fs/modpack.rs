@@ -0,0 +1,180 @@
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Component, Path, PathBuf},
+};
+
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Deserialize;
+use thiserror::Error;
+use zip::ZipArchive;
+
+use crate::utils;
+
+/// Name of the manifest expected at the top level of every modpack bundle
+static MANIFEST_NAME: &str = "modpack.toml";
+/// Marker written next to the extracted mods so we only extract a bundle again when it changes
+static STAMP_NAME: &str = ".modpack";
+
+#[derive(Debug, Error)]
+pub enum ModpackError {
+    #[error("IO error: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to read the bundle: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("failed to parse the manifest: {0}")]
+    Manifest(#[from] toml::de::Error),
+    #[error("the bundle does not contain a {0}")]
+    MissingManifest(&'static str),
+    #[error("the mod id '{0}' is not a valid folder name")]
+    InvalidId(String),
+}
+
+/// The `modpack.toml` found at the root of a bundle
+#[derive(Debug, Deserialize)]
+pub struct ModpackManifest {
+    pub name: Option<String>,
+    #[serde(rename = "mod", default)]
+    pub mods: Vec<ModpackEntry>,
+}
+
+/// A single mod contained in a bundle
+#[derive(Debug, Deserialize)]
+pub struct ModpackEntry {
+    /// Stable identifier of the mod within the bundle, used as the folder name once extracted so presets survive bundle updates
+    pub id: String,
+    /// Folder inside of the bundle that holds the mod's files
+    pub folder: String,
+    /// Optional info.toml contents, written out if the mod folder doesn't provide its own
+    pub info: Option<toml::Value>,
+}
+
+/// Extracts every bundle found in the modpacks directory (if it changed since the last boot) and returns the directories
+/// that contain the extracted mods, to be discovered alongside the regular mods directory
+pub fn prepare_modpacks() -> Vec<Utf8PathBuf> {
+    let bundles = match std::fs::read_dir(utils::paths::modpacks()) {
+        Ok(read_dir) => read_dir,
+        Err(e) => {
+            warn!("Unable to read the modpacks directory. Reason: {:?}", e);
+            return Vec::new();
+        },
+    };
+
+    bundles
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+
+            if !path.is_file() || !path.extension().map_or(false, |ext| ext == "zip") {
+                return None;
+            }
+
+            let stem = path.file_stem().and_then(|stem| stem.to_str())?;
+            let destination = utils::paths::modpack_cache().join(stem);
+
+            match extract_modpack(&path, &destination) {
+                Ok(()) => Some(destination),
+                Err(e) => {
+                    error!("Failed to mount modpack '{}'. Reason: {}", path.display(), e);
+                    None
+                },
+            }
+        })
+        .collect()
+}
+
+/// Identifies the contents of a bundle from its size, its modification time and the checksums of its files, which are stored in the
+/// central directory so the files don't need to be read
+fn bundle_stamp(bundle: &Path, archive: &mut ZipArchive<File>) -> Result<String, ModpackError> {
+    let metadata = std::fs::metadata(bundle)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(std::time::UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_secs());
+
+    let mut hasher = crc32fast::Hasher::new();
+
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        hasher.update(file.name().as_bytes());
+        hasher.update(&file.crc32().to_le_bytes());
+    }
+
+    Ok(format!("{}:{}:{:08x}", metadata.len(), modified, hasher.finalize()))
+}
+
+/// Ids are used as folder names, so they can't point anywhere else than a folder right inside of the extracted bundle
+fn is_valid_id(id: &str) -> bool {
+    let mut components = Path::new(id).components();
+    matches!((components.next(), components.next()), (Some(Component::Normal(_)), None))
+}
+
+fn extract_modpack(bundle: &Path, destination: &Utf8Path) -> Result<(), ModpackError> {
+    let mut archive = ZipArchive::new(File::open(bundle)?)?;
+    let stamp = bundle_stamp(bundle, &mut archive)?;
+
+    if std::fs::read_to_string(destination.join(STAMP_NAME)).map_or(false, |previous| previous == stamp) {
+        debug!("Modpack '{}' is already extracted, skipping.", bundle.display());
+        return Ok(());
+    }
+
+    let manifest: ModpackManifest = {
+        let mut manifest = archive.by_name(MANIFEST_NAME).map_err(|_| ModpackError::MissingManifest(MANIFEST_NAME))?;
+        let mut contents = String::new();
+        manifest.read_to_string(&mut contents)?;
+        toml::from_str(&contents)?
+    };
+
+    info!(
+        "Extracting modpack '{}' ({} mods).",
+        manifest.name.as_deref().unwrap_or_else(|| destination.as_str()),
+        manifest.mods.len()
+    );
+
+    if let Some(entry) = manifest.mods.iter().find(|entry| !is_valid_id(&entry.id)) {
+        return Err(ModpackError::InvalidId(entry.id.clone()));
+    }
+
+    // Start from scratch so mods removed from the bundle don't linger around
+    if destination.exists() {
+        std::fs::remove_dir_all(destination)?;
+    }
+
+    for entry in manifest.mods.iter() {
+        let mod_root = destination.join(&entry.id);
+        std::fs::create_dir_all(&mod_root)?;
+
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index)?;
+
+            let local = match file.enclosed_name().and_then(|name| name.strip_prefix(&entry.folder).ok()) {
+                Some(local) if !local.as_os_str().is_empty() => local.to_path_buf(),
+                _ => continue,
+            };
+
+            let out_path = PathBuf::from(mod_root.as_str()).join(local);
+
+            if file.is_dir() {
+                std::fs::create_dir_all(&out_path)?;
+            } else {
+                if let Some(parent) = out_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                io::copy(&mut file, &mut File::create(&out_path)?)?;
+            }
+        }
+
+        let info_path = mod_root.join("info.toml");
+
+        if let Some(info) = entry.info.as_ref().filter(|_| !info_path.exists()) {
+            match toml::to_string(info) {
+                Ok(info) => std::fs::write(&info_path, info)?,
+                Err(e) => warn!("Failed to write info.toml for mod '{}' in modpack '{}'. Reason: {}", entry.id, bundle.display(), e),
+            }
+        }
+    }
+
+    std::fs::write(destination.join(STAMP_NAME), stamp)?;
+
+    Ok(())
+}
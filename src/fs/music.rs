@@ -0,0 +1,238 @@
+//! Adds new tracks to the game instead of replacing existing ones. A mod provides the audio as `stream;/sound/bgm/bgm_<name>.nus3audio`
+//! along with a `bgm_<name>.music` file next to it, which names the vanilla track to copy the settings of:
+//!
+//! ```toml
+//! base = "ui_bgm_a01_smb_chijyou"
+//! loop_start_ms = 12000
+//! loop_start_sample = 576000
+//! loop_end_ms = 96000
+//! loop_end_sample = 4608000
+//! total_time_ms = 98000
+//! total_samples = 4704000
+//! ```
+//!
+//! The entries of the base track in `ui_bgm_db.prc` and `bgm_property.bin` are copied under the ids of the new track, so it shows up
+//! in the same playlists as the base track. Loop points that aren't given are taken from the base track as well. The title is read
+//! from the `bgm_title_<name>` label of `msg_bgm.msbt`, which can be added with an `.xmsbt` like any other text.
+
+use std::path::Path;
+
+use prcx::{
+    hash40::{hash40, Hash40},
+    param::{ParamKind, ParamStruct},
+};
+use serde::Deserialize;
+use smash_bgm_property::BgmPropertyFile;
+use thiserror::Error;
+
+use super::ui_database::{field, field_mut};
+
+pub static UI_BGM_DB: &str = "ui/param/database/ui_bgm_db.prc";
+pub static BGM_PROPERTY: &str = "sound/config/bgm_property.bin";
+
+#[derive(Debug, Error)]
+pub enum MusicError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse the track: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("tracks must be named 'bgm_<name>.music' after their nus3audio")]
+    InvalidName,
+    #[error("the audio file '{0}' is missing")]
+    MissingAudio(String),
+}
+
+/// A `.music` file, which adds a new track using the settings of a vanilla one
+#[derive(Debug, Deserialize)]
+pub struct Track {
+    /// `ui_bgm_id` of the track the settings are copied from
+    pub base: String,
+    pub loop_start_ms: Option<u32>,
+    pub loop_start_sample: Option<u32>,
+    pub loop_end_ms: Option<u32>,
+    pub loop_end_sample: Option<u32>,
+    pub total_time_ms: Option<u32>,
+    pub total_samples: Option<u32>,
+}
+
+/// A track to add, with the name its ids are made from
+#[derive(Debug)]
+pub struct MusicTrack {
+    pub name: String,
+    pub track: Track,
+}
+
+/// Reads a `.music` file, found at `full_path` on the SD. The nus3audio it goes with has to be part of the same mod.
+pub fn load_track(full_path: &Path, local: &Path) -> Result<MusicTrack, MusicError> {
+    let name = local
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(|stem| stem.strip_prefix("bgm_"))
+        .filter(|name| !name.is_empty())
+        .ok_or(MusicError::InvalidName)?
+        .to_string();
+
+    let audio = full_path.with_extension("nus3audio");
+
+    if !audio.is_file() {
+        return Err(MusicError::MissingAudio(audio.display().to_string()));
+    }
+
+    let track: Track = toml::from_str(&std::fs::read_to_string(full_path)?)?;
+
+    Ok(MusicTrack { name, track })
+}
+
+fn hash_field(entry: &ParamStruct, name: &str) -> Option<Hash40> {
+    match field(entry, hash40(name)) {
+        Some(ParamKind::Hash(hash)) => Some(*hash),
+        _ => None,
+    }
+}
+
+fn set_field(entry: &mut ParamStruct, name: &str, value: ParamKind) {
+    if let Some(field) = field_mut(entry, hash40(name)) {
+        *field = value;
+    }
+}
+
+/// Finds the entry of a list of the database whose `key` field is `id`
+fn find_entry<'a>(database: &'a ParamStruct, list: &str, key: &str, id: Hash40) -> Option<&'a ParamStruct> {
+    match field(database, hash40(list))? {
+        ParamKind::List(list) => list.0.iter().find_map(|entry| match entry {
+            ParamKind::Struct(entry) if hash_field(entry, key) == Some(id) => Some(entry),
+            _ => None,
+        }),
+        _ => None,
+    }
+}
+
+fn push_entry(database: &mut ParamStruct, list: &str, entry: ParamStruct) {
+    if let Some(ParamKind::List(list)) = field_mut(database, hash40(list)) {
+        list.0.push(ParamKind::Struct(entry));
+    }
+}
+
+/// The entries of a track in the `db_root`, `stream_set`, `assigned_info` and `stream_property` lists, which refer to each other
+fn track_entries<'a>(database: &'a ParamStruct, id: Hash40) -> Option<[&'a ParamStruct; 4]> {
+    let bgm = find_entry(database, "db_root", "ui_bgm_id", id)?;
+    let set = find_entry(database, "stream_set", "stream_set_id", hash_field(bgm, "stream_set_id")?)?;
+    let info = find_entry(database, "assigned_info", "info_id", hash_field(set, "info0")?)?;
+    let stream = find_entry(database, "stream_property", "stream_id", hash_field(info, "stream_id")?)?;
+
+    Some([bgm, set, info, stream])
+}
+
+/// Copies the entries of the base track under the ids of the new one. Returns false if the base track isn't in the database.
+fn add_track_to_database(database: &mut ParamStruct, track: &MusicTrack) -> bool {
+    let name = &track.name;
+    let base_id = hash40(&track.track.base);
+
+    let Some([mut bgm, mut set, mut info, mut stream]) = track_entries(database, base_id).map(|entries| entries.map(Clone::clone)) else {
+        return false;
+    };
+
+    set_field(&mut bgm, "ui_bgm_id", ParamKind::Hash(hash40(&format!("ui_bgm_{}", name))));
+    set_field(&mut bgm, "stream_set_id", ParamKind::Hash(hash40(&format!("set_{}", name))));
+    set_field(&mut bgm, "name_id", ParamKind::Str(name.clone()));
+
+    // The other infos of a set are the alternate versions of the base track, which the new track doesn't have
+    set_field(&mut set, "stream_set_id", ParamKind::Hash(hash40(&format!("set_{}", name))));
+    set_field(&mut set, "info0", ParamKind::Hash(hash40(&format!("info_{}", name))));
+    for index in 1..16 {
+        set_field(&mut set, &format!("info{}", index), ParamKind::Hash(hash40("")));
+    }
+
+    set_field(&mut info, "info_id", ParamKind::Hash(hash40(&format!("info_{}", name))));
+    set_field(&mut info, "stream_id", ParamKind::Hash(hash40(&format!("stream_{}", name))));
+
+    set_field(&mut stream, "stream_id", ParamKind::Hash(hash40(&format!("stream_{}", name))));
+    set_field(&mut stream, "data_name0", ParamKind::Str(name.clone()));
+    for index in 1..5 {
+        set_field(&mut stream, &format!("data_name{}", index), ParamKind::Str(String::new()));
+    }
+
+    let new_id = hash40(&format!("ui_bgm_{}", name));
+    let keyed_lists = [hash40("db_root"), hash40("stream_set"), hash40("assigned_info"), hash40("stream_property")];
+
+    // Every other list refers to tracks by their ui_bgm_id, which puts the new track in the playlists of the base track
+    for (list_hash, list) in database.0.iter_mut() {
+        if keyed_lists.contains(list_hash) {
+            continue;
+        }
+
+        if let ParamKind::List(list) = list {
+            let copies: Vec<ParamKind> = list
+                .0
+                .iter()
+                .filter_map(|entry| match entry {
+                    ParamKind::Struct(entry) if hash_field(entry, "ui_bgm_id") == Some(base_id) => {
+                        let mut copy = entry.clone();
+                        set_field(&mut copy, "ui_bgm_id", ParamKind::Hash(new_id));
+                        Some(ParamKind::Struct(copy))
+                    },
+                    _ => None,
+                })
+                .collect();
+
+            list.0.extend(copies);
+        }
+    }
+
+    push_entry(database, "db_root", bgm);
+    push_entry(database, "stream_set", set);
+    push_entry(database, "assigned_info", info);
+    push_entry(database, "stream_property", stream);
+
+    true
+}
+
+/// Adds the tracks to `ui_bgm_db.prc`, skipping the ones already in it
+pub fn add_tracks_to_database(database: &mut ParamStruct, tracks: &[MusicTrack]) {
+    for track in tracks.iter() {
+        let id = hash40(&format!("ui_bgm_{}", track.name));
+
+        if find_entry(database, "db_root", "ui_bgm_id", id).is_some() {
+            warn!("The track '{}' is already in the database, it will not be added again.", track.name);
+            continue;
+        }
+
+        if !add_track_to_database(database, track) {
+            error!("Could not add the track '{}' as its base track '{}' was not found.", track.name, track.track.base);
+        }
+    }
+}
+
+/// Adds the loop points of the tracks to `bgm_property.bin`, copying the ones that aren't given from the base track
+pub fn add_tracks_to_bgm_property(bgm_property: &mut BgmPropertyFile, database: Option<&ParamStruct>, tracks: &[MusicTrack]) {
+    for track in tracks.iter() {
+        // bgm_property refers to tracks by the name of their audio, which the base track only has in the database
+        let base_name = database.and_then(|database| {
+            let [.., stream] = track_entries(database, hash40(&track.track.base))?;
+
+            match field(stream, hash40("data_name0")) {
+                Some(ParamKind::Str(name)) => Some(name.clone()),
+                _ => None,
+            }
+        });
+
+        let Some(mut entry) = base_name
+            .and_then(|base_name| bgm_property.entries.iter().find(|entry| entry.name_id == hash40(&base_name)).cloned())
+        else {
+            error!("Could not find the loop points of the base track '{}' for the track '{}'.", track.track.base, track.name);
+            continue;
+        };
+
+        let timing = &track.track;
+        entry.name_id = hash40(&track.name);
+        entry.loop_start_ms = timing.loop_start_ms.unwrap_or(entry.loop_start_ms);
+        entry.loop_start_sample = timing.loop_start_sample.unwrap_or(entry.loop_start_sample);
+        entry.loop_end_ms = timing.loop_end_ms.unwrap_or(entry.loop_end_ms);
+        entry.loop_end_sample = timing.loop_end_sample.unwrap_or(entry.loop_end_sample);
+        entry.total_time_ms = timing.total_time_ms.unwrap_or(entry.total_time_ms);
+        entry.total_samples = timing.total_samples.unwrap_or(entry.total_samples);
+
+        bgm_property.entries.retain(|other| other.name_id != entry.name_id);
+        bgm_property.entries.push(entry);
+    }
+}
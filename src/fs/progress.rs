@@ -0,0 +1,49 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+/// Counters updated while mods are discovered at boot, so that something can tell the user the game is not stuck on huge mod sets.
+/// Nothing waits on them, reading them is entirely up to whoever wants to display them.
+static IN_PROGRESS: AtomicBool = AtomicBool::new(false);
+static ENTRIES_SCANNED: AtomicUsize = AtomicUsize::new(0);
+static MODS_SCANNED: AtomicUsize = AtomicUsize::new(0);
+static MODS_TOTAL: AtomicUsize = AtomicUsize::new(0);
+
+#[repr(C)]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct DiscoveryProgress {
+    /// Files and folders looked at so far, in every mod
+    pub entries_scanned: usize,
+    /// Mods that were completely discovered
+    pub mods_scanned: usize,
+    /// Mods that are going to be discovered, disabled ones aren't counted
+    pub mods_total: usize,
+    pub in_progress: bool,
+}
+
+/// Resets the counters for a new pass. Discovery goes over the mods a second time to report the conflicts, in which case it starts over.
+pub(crate) fn begin(mods_total: usize) {
+    ENTRIES_SCANNED.store(0, Ordering::Relaxed);
+    MODS_SCANNED.store(0, Ordering::Relaxed);
+    MODS_TOTAL.store(mods_total, Ordering::Relaxed);
+    IN_PROGRESS.store(true, Ordering::Relaxed);
+}
+
+pub(crate) fn entry_scanned() {
+    ENTRIES_SCANNED.fetch_add(1, Ordering::Relaxed);
+}
+
+pub(crate) fn mods_scanned(count: usize) {
+    MODS_SCANNED.fetch_add(count, Ordering::Relaxed);
+}
+
+pub(crate) fn finish() {
+    IN_PROGRESS.store(false, Ordering::Relaxed);
+}
+
+pub fn discovery_progress() -> DiscoveryProgress {
+    DiscoveryProgress {
+        entries_scanned: ENTRIES_SCANNED.load(Ordering::Relaxed),
+        mods_scanned: MODS_SCANNED.load(Ordering::Relaxed),
+        mods_total: MODS_TOTAL.load(Ordering::Relaxed),
+        in_progress: IN_PROGRESS.load(Ordering::Relaxed),
+    }
+}
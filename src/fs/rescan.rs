@@ -0,0 +1,101 @@
+use std::{
+    collections::HashMap,
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, SystemTime},
+};
+
+use skyline::nn;
+use smash_arc::Hash40;
+
+use crate::{replacement, utils, GLOBAL_FILESYSTEM};
+
+/// How often the sentinel file is checked for
+static POLL_INTERVAL: Duration = Duration::from_millis(2000);
+static IS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Starts watching for the rescan file if the user enabled it. FTP clients are expected to create the file once they are done
+/// uploading, so that a mod is never picked up halfway through being copied. Besides picking up new mods, a rescan refreshes the
+/// replaced files the game kept loaded and that were edited since, so the game loads them again the next time they are used.
+pub fn start_watcher() {
+    if !config::rescan_watcher_enabled() || IS_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    // Don't act on a file left over from the previous session, everything on the SD was already discovered on boot
+    let _ = std::fs::remove_file(utils::paths::rescan_sentinel());
+
+    let watcher = std::thread::Builder::new().stack_size(0x10000).spawn(|| {
+        unsafe {
+            let curr_thread = nn::os::GetCurrentThread();
+            nn::os::ChangeThreadPriority(curr_thread, 16);
+        }
+        watch();
+    });
+
+    if let Err(e) = watcher {
+        error!("Failed to start the rescan watcher. Reason: {:?}", e);
+        IS_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Asks the watcher to stop, which it does the next time it wakes up
+pub fn stop_watcher() {
+    IS_RUNNING.store(false, Ordering::SeqCst);
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
+
+/// Modification time of every replaced file, to tell which of them were edited between two rescans
+fn modded_file_times() -> HashMap<Hash40, Option<SystemTime>> {
+    let files = unsafe { GLOBAL_FILESYSTEM.read().unwrap().modded_files() };
+    files.iter().map(|(hash, path)| (*hash, modified_time(path))).collect()
+}
+
+fn watch() {
+    let sentinel = utils::paths::rescan_sentinel();
+
+    info!("Watching for '{}' to rescan the mods directory.", sentinel);
+
+    let mut file_times = modded_file_times();
+
+    while IS_RUNNING.load(Ordering::SeqCst) {
+        std::thread::sleep(POLL_INTERVAL);
+
+        // Checked again after sleeping, a rescan must not start once the application is shutting down
+        if !IS_RUNNING.load(Ordering::SeqCst) || !config::rescan_watcher_enabled() {
+            break;
+        }
+
+        if !sentinel.exists() {
+            continue;
+        }
+
+        if let Err(e) = std::fs::remove_file(&sentinel) {
+            // Rescanning without being able to remove the file would keep rescanning forever
+            error!("Failed to remove '{}', stopping the rescan watcher. Reason: {:?}", sentinel, e);
+            break;
+        }
+
+        let replaced = unsafe { GLOBAL_FILESYSTEM.write().unwrap().rescan_new_mods() };
+
+        let new_times = modded_file_times();
+
+        // Only the files that changed since the last rescan, or that a new mod replaces, need to be loaded again
+        let invalidated = new_times
+            .iter()
+            .filter(|(hash, time)| file_times.get(hash) != Some(time))
+            .filter(|(hash, _)| replacement::invalidate_loaded_file(**hash))
+            .count();
+
+        file_times = new_times;
+
+        info!("Rescan finished, {} new file(s) will be replaced and {} loaded file(s) will be reloaded.", replaced, invalidated);
+    }
+
+    info!("Stopped watching for '{}'.", sentinel);
+
+    IS_RUNNING.store(false, Ordering::SeqCst);
+}
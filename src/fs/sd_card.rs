@@ -0,0 +1,63 @@
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use skyline::nn;
+
+use crate::utils;
+
+/// How often the SD card is checked for. The hooks only ever read the result, so this is the only place that pays for the check.
+static POLL_INTERVAL: Duration = Duration::from_millis(1000);
+static IS_RUNNING: AtomicBool = AtomicBool::new(false);
+
+/// Starts watching for the SD card being removed. Reading a file from a card that is gone fails, so until it is back the game is left
+/// to load its own files.
+pub fn start_watcher() {
+    if IS_RUNNING.swap(true, Ordering::SeqCst) {
+        return;
+    }
+
+    let watcher = std::thread::Builder::new().stack_size(0x10000).spawn(|| {
+        unsafe {
+            let curr_thread = nn::os::GetCurrentThread();
+            nn::os::ChangeThreadPriority(curr_thread, 16);
+        }
+        watch();
+    });
+
+    if let Err(e) = watcher {
+        error!("Failed to start the SD card watcher. Reason: {:?}", e);
+        IS_RUNNING.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Asks the watcher to stop, which it does the next time it wakes up
+pub fn stop_watcher() {
+    IS_RUNNING.store(false, Ordering::SeqCst);
+}
+
+fn watch() {
+    while IS_RUNNING.load(Ordering::SeqCst) {
+        std::thread::sleep(POLL_INTERVAL);
+
+        if !IS_RUNNING.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let available = utils::storage::is_sd_mounted();
+
+        // Only log when the state changes, the card can stay out for a long time
+        if available == config::sd_available() {
+            continue;
+        }
+
+        if available {
+            info!("The SD card is back, files are replaced again.");
+        } else {
+            warn!("The SD card can't be read, the game's own files are loaded until it is back.");
+        }
+
+        config::set_sd_available(available);
+    }
+}
@@ -0,0 +1,415 @@
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use smash_arc::Hash40;
+use thiserror::Error;
+
+use super::utils::matches_path_pattern;
+use crate::{
+    api::replace,
+    hashes,
+    replacement::nutexb::{self, FooterError},
+    resource, PathExtension,
+};
+
+#[derive(Debug, Error)]
+pub enum TemplateError {
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to parse the template: {0}")]
+    Parse(#[from] toml::de::Error),
+    #[error("invalid color '{0}', colors are written as RRGGBB or RRGGBBAA")]
+    InvalidColor(String),
+    #[error("palette swap has {0} source colors but {1} replacement colors")]
+    PaletteMismatch(usize, usize),
+    #[error("the base texture is invalid: {0}")]
+    InvalidTexture(#[from] FooterError),
+    #[error("the base texture uses format {0:#x}, only RGBA8 and BGRA8 textures can be generated from")]
+    UnsupportedFormat(u8),
+}
+
+/// A `.template` file, which generates the files of several slots from a single file of the same mod
+#[derive(Debug, Deserialize)]
+pub struct Template {
+    /// File the targets are generated from, relative to the root of the mod
+    pub base: String,
+    #[serde(rename = "target", default)]
+    pub targets: Vec<TemplateTarget>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TemplateTarget {
+    /// Game path of the generated file. `*` and `**` can be used like in file groups to generate every file of the game that matches,
+    /// such as all the costume slots of a texture.
+    pub path: String,
+    /// Byte range of the pixels in the base file. Defaults to the whole file, minus the footer for nutexb files. Pixels are read as
+    /// RGBA8, or in the format of the footer for nutexb files.
+    pub pixels: Option<(usize, usize)>,
+    #[serde(default)]
+    pub transforms: Vec<Transform>,
+}
+
+/// Built-in operations applied to the pixels of the base file, in order
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Transform {
+    /// Replaces every pixel close enough to one of the `from` colors with the matching `to` color, keeping its alpha
+    PaletteSwap {
+        from: Vec<String>,
+        to: Vec<String>,
+        #[serde(default)]
+        tolerance: u8,
+    },
+    /// Rotates the hue of every pixel
+    HueShift { degrees: f32 },
+}
+
+/// Order of the channels of the pixels in the base file, the transforms are written for RGBA
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChannelOrder {
+    Rgba,
+    Bgra,
+}
+
+impl ChannelOrder {
+    /// Reads the footer of a texture for the range of its pixels and their layout. Textures that aren't 4 bytes per pixel with one byte
+    /// per channel are refused, the transforms would mangle them.
+    fn from_nutexb(texture: &[u8]) -> Result<(Self, (usize, usize)), TemplateError> {
+        let footer = nutexb::parse_footer(texture)?;
+
+        let order = match footer.format {
+            nutexb::FORMAT_R8G8B8A8_UNORM | nutexb::FORMAT_R8G8B8A8_SRGB => Self::Rgba,
+            nutexb::FORMAT_B8G8R8A8_UNORM | nutexb::FORMAT_B8G8R8A8_SRGB => Self::Bgra,
+            format => return Err(TemplateError::UnsupportedFormat(format)),
+        };
+
+        Ok((order, (0, texture.len() - footer.size)))
+    }
+}
+
+/// A transform with its parameters parsed, ready to run on every load
+#[derive(Debug, Clone)]
+enum CompiledTransform {
+    PaletteSwap { swaps: Vec<([u8; 4], [u8; 4])>, tolerance: u8 },
+    HueShift { degrees: f32 },
+}
+
+fn parse_color(color: &str) -> Result<[u8; 4], TemplateError> {
+    let hex = color.trim_start_matches('#');
+    let invalid = || TemplateError::InvalidColor(color.to_string());
+
+    if hex.len() != 6 && hex.len() != 8 {
+        return Err(invalid());
+    }
+
+    let mut rgba = [0xFF; 4];
+
+    for (index, channel) in rgba.iter_mut().enumerate().take(hex.len() / 2) {
+        *channel = u8::from_str_radix(hex.get(index * 2..index * 2 + 2).ok_or_else(invalid)?, 16).map_err(|_| invalid())?;
+    }
+
+    Ok(rgba)
+}
+
+impl Transform {
+    fn compile(&self) -> Result<CompiledTransform, TemplateError> {
+        match self {
+            Self::PaletteSwap { from, to, tolerance } => {
+                if from.len() != to.len() {
+                    return Err(TemplateError::PaletteMismatch(from.len(), to.len()));
+                }
+
+                let swaps = from
+                    .iter()
+                    .zip(to.iter())
+                    .map(|(from, to)| Ok((parse_color(from)?, parse_color(to)?)))
+                    .collect::<Result<_, TemplateError>>()?;
+
+                Ok(CompiledTransform::PaletteSwap { swaps, tolerance: *tolerance })
+            },
+            Self::HueShift { degrees } => Ok(CompiledTransform::HueShift { degrees: *degrees }),
+        }
+    }
+}
+
+impl CompiledTransform {
+    fn apply(&self, pixel: &mut [u8]) {
+        match self {
+            Self::PaletteSwap { swaps, tolerance } => {
+                let close = |a: u8, b: u8| a.abs_diff(b) <= *tolerance;
+
+                if let Some((_, to)) = swaps.iter().find(|(from, _)| (0..3).all(|channel| close(pixel[channel], from[channel]))) {
+                    pixel[..3].copy_from_slice(&to[..3]);
+                }
+            },
+            Self::HueShift { degrees } => {
+                let [r, g, b] = [pixel[0], pixel[1], pixel[2]].map(|channel| channel as f32 / 255.0);
+
+                let max = r.max(g).max(b);
+                let min = r.min(g).min(b);
+                let delta = max - min;
+
+                if delta == 0.0 {
+                    return;
+                }
+
+                let hue = if max == r {
+                    60.0 * (((g - b) / delta) % 6.0)
+                } else if max == g {
+                    60.0 * ((b - r) / delta + 2.0)
+                } else {
+                    60.0 * ((r - g) / delta + 4.0)
+                };
+
+                let hue = (hue + degrees).rem_euclid(360.0);
+                let saturation = delta / max;
+
+                let chroma = max * saturation;
+                let x = chroma * (1.0 - ((hue / 60.0) % 2.0 - 1.0).abs());
+                let offset = max - chroma;
+
+                let (r, g, b) = match hue as u32 / 60 {
+                    0 => (chroma, x, 0.0),
+                    1 => (x, chroma, 0.0),
+                    2 => (0.0, chroma, x),
+                    3 => (0.0, x, chroma),
+                    4 => (x, 0.0, chroma),
+                    _ => (chroma, 0.0, x),
+                };
+
+                for (channel, value) in pixel.iter_mut().zip([r, g, b]) {
+                    *channel = ((value + offset) * 255.0).round().clamp(0.0, 255.0) as u8;
+                }
+            },
+        }
+    }
+}
+
+/// Runs the transforms over the pixels found in `range` of the data
+fn generate(data: &mut [u8], range: (usize, usize), order: ChannelOrder, transforms: &[CompiledTransform]) {
+    let end = range.1.min(data.len());
+    let start = range.0.min(end);
+
+    for pixel in data[start..end].chunks_exact_mut(4) {
+        if order == ChannelOrder::Bgra {
+            pixel.swap(0, 2);
+        }
+
+        for transform in transforms.iter() {
+            transform.apply(pixel);
+        }
+
+        if order == ChannelOrder::Bgra {
+            pixel.swap(0, 2);
+        }
+    }
+}
+
+/// A file to generate, to be registered in the filesystem
+pub struct GeneratedFile {
+    pub hash: Hash40,
+    pub local: PathBuf,
+    /// File on the SD the data is generated from
+    pub base: PathBuf,
+    pub size: usize,
+    range: (usize, usize),
+    order: ChannelOrder,
+    transforms: Vec<CompiledTransform>,
+}
+
+impl GeneratedFile {
+    /// Hooks the transforms into the pre-replace pipeline, so they run on the data of the base file every time the target is loaded
+    pub fn register_transforms(&self) {
+        let range = self.range;
+        let order = self.order;
+        let transforms = self.transforms.clone();
+
+        replace::register_pre_replace(self.hash, move |data| generate(data, range, order, &transforms));
+    }
+}
+
+/// Reads a template found at `full_path` in the mod folder `root` and lists the files it generates. Nothing is registered yet, since
+/// another mod could provide the same files.
+pub fn load_template(root: &Path, full_path: &Path) -> Result<Vec<GeneratedFile>, TemplateError> {
+    let template: Template = toml::from_str(&std::fs::read_to_string(full_path)?)?;
+
+    let base = root.join(&template.base);
+    let size = std::fs::metadata(&base)?.len() as usize;
+
+    let (order, default_range) = if base.has_extension("nutexb") {
+        ChannelOrder::from_nutexb(&std::fs::read(&base)?)?
+    } else {
+        (ChannelOrder::Rgba, (0, size))
+    };
+
+    let mut files = Vec::with_capacity(template.targets.len());
+
+    let is_pattern = |path: &str| path.contains('*');
+
+    // Read once for all the targets, the game paths only come from the hash list
+    let known_paths = if template.targets.iter().any(|target| is_pattern(&target.path)) {
+        hashes::known_paths()
+    } else {
+        Vec::new()
+    };
+
+    let expanded = template.targets.iter().flat_map(|target| {
+        let paths: Vec<String> = if is_pattern(&target.path) {
+            let matches: Vec<String> = known_paths
+                .iter()
+                .filter(|path| matches_path_pattern(&target.path, path))
+                // The hash list includes the paths of mods and folders, only the files of the game can be generated
+                .filter(|path| resource::arc().get_file_path_index_from_hash(Hash40::from(**path)).is_ok())
+                // The base file is most likely one of the matches, it already provides itself
+                .filter(|path| !path.eq_ignore_ascii_case(&template.base))
+                .map(|path| path.to_string())
+                .collect();
+
+            if matches.is_empty() {
+                warn!("Template '{}' has a target '{}' that doesn't match any file of the game.", full_path.display(), target.path);
+            }
+
+            matches
+        } else {
+            vec![target.path.clone()]
+        };
+
+        paths.into_iter().map(move |path| (path, target))
+    });
+
+    for (path, target) in expanded {
+        let target_local = PathBuf::from(&path);
+
+        let hash = match target_local.smash_hash() {
+            Ok(hash) => hash,
+            Err(_) => {
+                warn!("Template '{}' has a target with an invalid path '{}', skipping it.", full_path.display(), path);
+                continue;
+            },
+        };
+
+        let transforms = target.transforms.iter().map(Transform::compile).collect::<Result<Vec<_>, _>>()?;
+        files.push(GeneratedFile {
+            hash,
+            local: target_local,
+            base: base.clone(),
+            size,
+            range: target.pixels.unwrap_or(default_range),
+            order,
+            transforms,
+        });
+    }
+
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(transforms: &[Transform]) -> Vec<CompiledTransform> {
+        transforms.iter().map(|transform| transform.compile().unwrap()).collect()
+    }
+
+    fn palette_swap(from: &str, to: &str, tolerance: u8) -> Transform {
+        Transform::PaletteSwap {
+            from: vec![from.to_string()],
+            to: vec![to.to_string()],
+            tolerance,
+        }
+    }
+
+    /// A single layer texture with a single mipmap, whose pixels are followed by its footer
+    fn nutexb(pixels: &[u8], format: u8) -> Vec<u8> {
+        let mut texture = pixels.to_vec();
+
+        let mut mipmap_sizes = [0; 0x40];
+        mipmap_sizes[..4].copy_from_slice(&(pixels.len() as u32).to_le_bytes());
+        texture.extend_from_slice(&mipmap_sizes);
+
+        let mut info = [0; 0x70];
+        info[0x0..0x4].copy_from_slice(b" XNT");
+        for (offset, value) in [(0x44, 1), (0x48, 1), (0x4C, 1), (0x58, 1), (0x60, 1), (0x64, pixels.len() as u32)] {
+            info[offset..offset + 4].copy_from_slice(&u32::to_le_bytes(value));
+        }
+        info[0x50] = format;
+        info[0x68..0x6C].copy_from_slice(b" XET");
+        texture.extend_from_slice(&info);
+
+        texture
+    }
+
+    #[test]
+    fn palette_swap_matches_reference() {
+        let transforms = compile(&[palette_swap("FF0000", "0000FF", 4)]);
+        let mut data = vec![0xFF, 0x00, 0x00, 0x80, 0xFC, 0x02, 0x01, 0xFF, 0x00, 0xFF, 0x00, 0xFF];
+        let range = (0, data.len());
+
+        generate(&mut data, range, ChannelOrder::Rgba, &transforms);
+
+        // Alpha is kept, and the pixel outside of the tolerance is left alone
+        assert_eq!(data, [0x00, 0x00, 0xFF, 0x80, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0xFF, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn hue_shift_matches_reference() {
+        let transforms = compile(&[Transform::HueShift { degrees: 120.0 }]);
+        let mut data = vec![0xFF, 0x00, 0x00, 0xFF, 0x80, 0x80, 0x80, 0xFF];
+        let range = (0, data.len());
+
+        generate(&mut data, range, ChannelOrder::Rgba, &transforms);
+
+        // Red turns green, and gray has no hue to shift
+        assert_eq!(data, [0x00, 0xFF, 0x00, 0xFF, 0x80, 0x80, 0x80, 0xFF]);
+    }
+
+    #[test]
+    fn bgra_pixels_are_swapped_as_rgba() {
+        let transforms = compile(&[palette_swap("FF0000", "00FF00", 0)]);
+        // Red, stored as BGRA
+        let mut data = vec![0x00, 0x00, 0xFF, 0xFF];
+        let range = (0, data.len());
+
+        generate(&mut data, range, ChannelOrder::Bgra, &transforms);
+
+        assert_eq!(data, [0x00, 0xFF, 0x00, 0xFF]);
+    }
+
+    #[test]
+    fn generate_stays_in_range() {
+        let transforms = compile(&[palette_swap("FF0000", "0000FF", 0)]);
+        let mut data = vec![0xFF, 0x00, 0x00, 0xFF, 0xFF, 0x00, 0x00, 0xFF];
+
+        generate(&mut data, (4, 0x100), ChannelOrder::Rgba, &transforms);
+
+        assert_eq!(data, [0xFF, 0x00, 0x00, 0xFF, 0x00, 0x00, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn nutexb_pixels_exclude_footer() {
+        let texture = nutexb(&[0xFF; 0x10], nutexb::FORMAT_B8G8R8A8_SRGB);
+        let (order, range) = ChannelOrder::from_nutexb(&texture).unwrap();
+
+        assert_eq!(order, ChannelOrder::Bgra);
+        assert_eq!(range, (0, 0x10));
+    }
+
+    #[test]
+    fn nutexb_compressed_formats_are_refused() {
+        // BC1
+        let texture = nutexb(&[0xFF; 0x10], 0x80);
+
+        assert!(matches!(ChannelOrder::from_nutexb(&texture), Err(TemplateError::UnsupportedFormat(0x80))));
+    }
+
+    #[test]
+    fn palette_colors_must_pair_up() {
+        let transform = Transform::PaletteSwap {
+            from: vec!["FF0000".to_string(), "00FF00".to_string()],
+            to: vec!["0000FF".to_string()],
+            tolerance: 0,
+        };
+
+        assert!(matches!(transform.compile(), Err(TemplateError::PaletteMismatch(2, 1))));
+    }
+}
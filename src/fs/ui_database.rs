@@ -0,0 +1,106 @@
+//! The UI databases list one entry per character, stage or track, and mods adding some append entries to those lists. A param patch
+//! refers to list entries by their index though, so two mods appending an entry to the same list both write it at the first free
+//! index and only the last one applied is kept. For the lists below, the entries of every patch are merged by their id instead: an
+//! entry that a patch changes replaces the entry with the same id, and one with a new id is appended.
+
+use prcx::{
+    hash40::{hash40, Hash40},
+    param::{ParamKind, ParamList, ParamStruct},
+};
+
+/// The keyed lists of every database, as the file name, the name of the list and the field holding the id of an entry
+const KEYED_LISTS: &[(&str, &str, &str)] = &[
+    ("ui_chara_db.prc", "db_root", "ui_chara_id"),
+    ("ui_stage_db.prc", "db_root", "ui_stage_id"),
+    ("ui_series_db.prc", "db_root", "ui_series_id"),
+    ("ui_gametitle_db.prc", "db_root", "ui_gametitle_id"),
+    ("ui_bgm_db.prc", "db_root", "ui_bgm_id"),
+    ("ui_bgm_db.prc", "stream_set", "stream_set_id"),
+    ("ui_bgm_db.prc", "assigned_info", "info_id"),
+    ("ui_bgm_db.prc", "stream_property", "stream_id"),
+];
+
+/// Returns the keyed lists of a database as the hashes of the list and of its id field, or None if the file isn't one
+pub fn keyed_lists(file_name: &str) -> Option<Vec<(Hash40, Hash40)>> {
+    let lists: Vec<_> = KEYED_LISTS
+        .iter()
+        .filter(|(database, ..)| *database == file_name)
+        .map(|(_, list, id)| (hash40(list), hash40(id)))
+        .collect();
+
+    if lists.is_empty() {
+        None
+    } else {
+        Some(lists)
+    }
+}
+
+pub fn field(param: &ParamStruct, hash: Hash40) -> Option<&ParamKind> {
+    param.0.iter().find(|(field, _)| *field == hash).map(|(_, value)| value)
+}
+
+pub fn field_mut(param: &mut ParamStruct, hash: Hash40) -> Option<&mut ParamKind> {
+    param.0.iter_mut().find(|(field, _)| *field == hash).map(|(_, value)| value)
+}
+
+fn entry_id(entry: &ParamKind, id: Hash40) -> Option<Hash40> {
+    match entry {
+        ParamKind::Struct(entry) => match field(entry, id) {
+            Some(ParamKind::Hash(hash)) => Some(*hash),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Merges the entries of `patched` that differ from `base` into `merged` by their id. Entries without an id are compared by index
+/// like a regular patch would.
+fn merge_list(base: &ParamList, patched: &ParamList, merged: &mut ParamList, id: Hash40) {
+    for (index, entry) in patched.0.iter().enumerate() {
+        let Some(key) = entry_id(entry, id) else {
+            if base.0.get(index) != Some(entry) {
+                match merged.0.get_mut(index) {
+                    Some(merged_entry) => *merged_entry = entry.clone(),
+                    None => merged.0.push(entry.clone()),
+                }
+            }
+            continue;
+        };
+
+        if base.0.iter().any(|base_entry| base_entry == entry) {
+            continue;
+        }
+
+        match merged.0.iter_mut().find(|merged_entry| entry_id(merged_entry, id) == Some(key)) {
+            Some(merged_entry) => *merged_entry = entry.clone(),
+            None => merged.0.push(entry.clone()),
+        }
+    }
+}
+
+/// Applies a patch to `base` on its own, then merges what it changed into `merged`, which holds the result of the patches applied
+/// before it
+pub fn merge_patch(patch: &ParamStruct, base: &ParamStruct, merged: &mut ParamStruct, lists: &[(Hash40, Hash40)]) -> Result<(), String> {
+    let mut patched = base.clone();
+    prcx::apply_patch(patch, &mut patched).map_err(|_| "Unable to patch param data!".to_string())?;
+
+    for (hash, value) in patched.0.iter() {
+        let base_value = field(base, *hash);
+
+        if base_value == Some(value) {
+            continue;
+        }
+
+        let keyed_list = lists.iter().find(|(list, _)| list == hash).map(|(_, id)| *id);
+
+        match (keyed_list, base_value, value, field_mut(merged, *hash)) {
+            (Some(id), Some(ParamKind::List(base_list)), ParamKind::List(patched_list), Some(ParamKind::List(merged_list))) => {
+                merge_list(base_list, patched_list, merged_list, id)
+            },
+            (_, _, _, Some(merged_value)) => *merged_value = value.clone(),
+            (_, _, _, None) => merged.0.push((*hash, value.clone())),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,126 @@
+//! Mod files whose path doesn't exist in the game are added to it as new files. That is what mods adding content rely on, but it is
+//! also what happens to a file whose folder structure has a typo in it, which then never gets loaded. The files are listed every boot,
+//! along with the closest path of the game that has the same file name, so users can tell which ones were not meant to be new. Files
+//! that a config.json declares are left out, since those are new on purpose.
+
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Component, Path, PathBuf},
+};
+
+use arc_config::{Config as ModConfig, ToSmashArc};
+use smash_arc::Hash40;
+
+use crate::{hashes, utils};
+
+/// Files that the config.json of a mod adds to a directory or shares, which are expected not to exist in the game
+pub fn declared_files(config: &ModConfig) -> HashSet<Hash40> {
+    let added = config.new_dir_files.iter().flat_map(|(_, files)| files.iter().map(|hash| hash.to_smash_arc()));
+    let shared = config
+        .share_to_vanilla
+        .iter()
+        .chain(config.share_to_added.iter())
+        .flat_map(|(_, new_files)| new_files.0.iter().map(|new_file| new_file.full_path.to_smash_arc()));
+
+    added.chain(shared).collect()
+}
+
+/// How closely two paths match, by counting the folders they have in common when lined up from the start and from the end. Lining
+/// them up both ways catches a misspelled folder as well as a missing or extra one.
+fn similarity(a: &[&str], b: &[&str]) -> usize {
+    let from_start = a.iter().zip(b.iter()).filter(|(a, b)| a == b).count();
+    let from_end = a.iter().rev().zip(b.iter().rev()).filter(|(a, b)| a == b).count();
+    from_start.max(from_end)
+}
+
+fn components(path: &str) -> Vec<&str> {
+    Path::new(path)
+        .components()
+        .filter_map(|component| match component {
+            Component::Normal(name) => name.to_str(),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Finds the path of the game that is the closest to an unmatched one among the paths with the same file name
+fn suggest(local: &str, by_file_name: &HashMap<&str, Vec<&'static str>>) -> Option<&'static str> {
+    let file_name = Path::new(local).file_name()?.to_str()?;
+    let local_components = components(local);
+
+    by_file_name
+        .get(file_name)?
+        .iter()
+        .copied()
+        .max_by_key(|candidate| {
+            let score = similarity(&local_components, &components(candidate));
+            // Prefer the candidate the closest in length when several match as much
+            (score, std::cmp::Reverse(candidate.len().abs_diff(local.len())))
+        })
+}
+
+/// Writes the list of unmatched files, given as their path in the game and the file they come from. Looking for suggestions goes
+/// through every known path, so it is done on its own thread rather than holding up the boot.
+pub fn write_report(unmatched: Vec<(PathBuf, Option<PathBuf>)>) {
+    let report = utils::paths::unmatched_report();
+
+    if unmatched.is_empty() {
+        let _ = std::fs::remove_file(&report);
+        return;
+    }
+
+    let writer = std::thread::Builder::new().stack_size(0x40000).spawn(move || write_report_now(&unmatched));
+
+    if let Err(e) = writer {
+        error!("Failed to start writing '{}'. Reason: {:?}", report, e);
+    }
+}
+
+fn write_report_now(unmatched: &[(PathBuf, Option<PathBuf>)]) {
+    let report = utils::paths::unmatched_report();
+
+    let unmatched_locals: HashSet<&str> = unmatched.iter().filter_map(|(local, _)| local.to_str()).collect();
+
+    // Mod files are part of the hash list as well, they have to be left out for a file not to be suggested as itself
+    let mut by_file_name: HashMap<&str, Vec<&'static str>> = HashMap::new();
+
+    for path in hashes::known_paths() {
+        if unmatched_locals.contains(path) {
+            continue;
+        }
+
+        if let Some(file_name) = Path::new(path).file_name().and_then(|name| name.to_str()) {
+            by_file_name.entry(file_name).or_default().push(path);
+        }
+    }
+
+    let mut lines: Vec<String> = unmatched
+        .iter()
+        .map(|(local, source)| {
+            let mut line = format!("{}", local.display());
+
+            if let Some(source) = source {
+                line.push_str(&format!(" (from {})", source.display()));
+            }
+
+            if let Some(suggestion) = local.to_str().and_then(|local| suggest(local, &by_file_name)) {
+                line.push_str(&format!(", did you mean {}?", suggestion));
+            }
+
+            line
+        })
+        .collect();
+
+    lines.sort();
+
+    let contents = format!(
+        "# Mod files whose path doesn't exist in the game. They were added as new files, which is only expected from mods that add \
+         content.\n{}\n",
+        lines.join("\n")
+    );
+
+    match std::fs::write(&report, contents) {
+        Ok(_) => info!("{} mod file(s) don't exist in the game, they are listed in '{}'.", lines.len(), report),
+        Err(e) => error!("Failed to write '{}'. Reason: {:?}", report, e),
+    }
+}
@@ -5,8 +5,8 @@ use std::{
 };
 
 use arc_config::ToExternal;
-use orbits::{FileLoader, Tree};
-use smash_arc::Hash40;
+use orbits::{orbit::LaunchPad, FileLoader, StandardLoader, Tree};
+use smash_arc::{ArcLookup, Hash40, LoadedArc};
 
 use super::{ApiCallback, ApiLoader};
 use crate::{hashes, PathExtension};
@@ -24,7 +24,9 @@ where
     // To solve this I store the hash of every file which has a regional variant which has been found, and then if a non-regional variant is found
     // it is ignored
     // - blujay
-    let mut regional_overrides = HashSet::new();
+    // The region wildcard sits between the two, so every file is ranked with crate::regional_precedence and the order the files are
+    // found in only matters between files of the same rank.
+    let mut precedence = HashMap::new();
     let mut size_map = HashMap::new();
     let mut path_map = HashMap::new();
     tree.walk_paths(|node, ty| {
@@ -33,22 +35,21 @@ where
         }
 
         if let Some(size) = tree.query_filesize(node.get_local()) {
-            match node.get_local().smash_hash() {
-                Ok(hash) => {
-                    if regional_overrides.contains(&hash) {
-                        return;
-                    }
-
-                    let is_regional_variant = if let Some(node) = node.get_local().to_str() { node.contains('+') } else { false };
+            // The regional suffix or the region wildcard is stripped when hashing, so a file hashes to the path of the game it replaces
+            // whatever its region
+            let hash = match node.get_local().smash_hash() {
+                Ok(hash) => hash,
+                Err(e) => {
+                    error!("Failed to get hash for {}. Reason: {:?}", node.get_local().display(), e);
+                    return;
+                },
+            };
 
-                    size_map.insert(hash, size);
-                    path_map.insert(hash, node.get_local().to_path_buf());
+            let rank = node.get_local().to_str().map_or(0, crate::regional_precedence);
 
-                    if is_regional_variant {
-                        regional_overrides.insert(hash);
-                    }
-                },
-                Err(e) => error!("Failed to get hash for {}. Reason: {:?}", node.get_local().display(), e),
+            if takes_precedence(&mut precedence, hash, rank) {
+                size_map.insert(hash, size);
+                path_map.insert(hash, node.get_local().to_path_buf());
             }
         } else {
             error!("Failed to stat file {}. This file may have issues.", node.full_path().display());
@@ -58,6 +59,108 @@ where
     (size_map, path_map)
 }
 
+/// Records the rank of a file for a hash if it goes above the rank of the file found before it. Between files of the same rank, the
+/// first one found is kept, except for files without a regional suffix where the last one found is kept as it always was.
+fn takes_precedence(precedence: &mut HashMap<Hash40, u8>, hash: Hash40, rank: u8) -> bool {
+    match precedence.get(&hash) {
+        Some(&existing) if existing > rank || (existing == rank && rank != 0) => false,
+        _ => {
+            precedence.insert(hash, rank);
+            true
+        },
+    }
+}
+
+/// Finds the mods where none of the files correspond to a path known to the game, which usually means the files were misnamed or made for another version.
+/// Mods with a config.json are left alone, since they can legitimately add new files.
+///
+/// This has to run before the discovered paths are added to the known hashes.
+pub fn find_ineffective_mods(launchpad: &LaunchPad<StandardLoader>, arc: &LoadedArc) -> Vec<PathBuf> {
+    // For every mod root, (files matching a game path, total files)
+    let mut mods: HashMap<PathBuf, (usize, usize)> = HashMap::new();
+
+    launchpad.tree().walk_paths(|node, ty| {
+        if !ty.is_file() {
+            return;
+        }
+
+        let local = node.get_local();
+        let mut root = node.full_path().to_path_buf();
+
+        for _ in local.components() {
+            root.pop();
+        }
+
+        let is_known = local
+            .smash_hash()
+            .map_or(false, |hash| arc.get_file_path_index_from_hash(hash).is_ok() || hashes::try_find(hash).is_some());
+
+        let (matching, total) = mods.entry(root).or_default();
+        *total += 1;
+
+        if is_known {
+            *matching += 1;
+        }
+    });
+
+    let configured: HashSet<&Path> = launchpad
+        .collected_paths()
+        .iter()
+        .filter(|(_, local)| local.ends_with("config.json"))
+        .map(|(root, _)| Path::new(root))
+        .collect();
+
+    let mut ineffective: Vec<PathBuf> = mods
+        .into_iter()
+        .filter(|(root, (matching, total))| *matching == 0 && *total > 0 && !configured.contains(root.as_path()))
+        .map(|(root, _)| root)
+        .collect();
+
+    ineffective.sort();
+    ineffective
+}
+
+/// Gathers the files that the discovered mods want preloaded, through the `preload` list of their info.toml.
+/// Only discovered mods are considered, so disabled mods never get anything preloaded.
+pub fn get_preload_list<L: FileLoader>(tree: &Tree<L>) -> Vec<PathBuf>
+where
+    <L as FileLoader>::ErrorType: Debug,
+{
+    let mut roots = HashSet::new();
+
+    tree.walk_paths(|node, ty| {
+        if !ty.is_file() {
+            return;
+        }
+
+        let mut root = node.full_path().to_path_buf();
+
+        for _ in node.get_local().components() {
+            root.pop();
+        }
+
+        roots.insert(root);
+    });
+
+    roots
+        .into_iter()
+        .filter_map(|root| {
+            let info = std::fs::read_to_string(root.join("info.toml")).ok()?;
+            let info: toml::Value = toml::from_str(&info).ok()?;
+
+            let preload: Vec<PathBuf> = info
+                .get("preload")?
+                .as_array()?
+                .iter()
+                .filter_map(|path| path.as_str().map(PathBuf::from))
+                .collect();
+
+            Some(preload)
+        })
+        .flatten()
+        .collect()
+}
+
 pub fn get_required_nus3banks<L: FileLoader>(tree: &Tree<L>, unshare_blacklist: &[hash40::Hash40]) -> HashSet<PathBuf>
 where
     <L as FileLoader>::ErrorType: Debug,
@@ -115,9 +218,8 @@ pub fn add_file_to_api_tree<P: AsRef<Path>, Q: AsRef<Path>>(
     }
 }
 
-/// Adds a PRC patch file and information to the API loader
-pub fn add_prc_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, phys_root: P, local: Q) -> Option<Hash40> {
-    let local = local.as_ref();
+/// Path of the file a param patch applies to, without the region of the patch
+pub fn param_patch_base(local: &Path) -> PathBuf {
     let base_local = if local.has_extension("prcx") || local.has_extension("prcxml") {
         // patch files have different extensions
         local.with_extension("prc")
@@ -128,7 +230,8 @@ pub fn add_prc_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>,
     } else {
         unreachable!()
     };
-    let base_local = if let Some(name) = base_local.file_name().and_then(|os_str| os_str.to_str()) {
+
+    if let Some(name) = base_local.file_name().and_then(|os_str| os_str.to_str()) {
         if let Some(idx) = name.find('+') {
             let mut new_name = name.to_string();
             new_name.replace_range(idx..idx + 6, "");
@@ -138,14 +241,89 @@ pub fn add_prc_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>,
         }
     } else {
         base_local
-    };
-    let full_path = phys_root.as_ref().join(local); // need the full path so that our API loader can load it
+    }
+}
+
+/// Lists the params of a mod, full files and patches, relative to its root. None if the mod contains anything else that the game
+/// could load, the metadata ARCropolis reads itself left aside.
+pub fn find_param_files(root: &Path) -> Option<Vec<PathBuf>> {
+    static METADATA_NAMES: &[&str] = &["info.toml", "preview.webp"];
+
+    let region = config::region().to_string();
+    let mut params = Vec::new();
+
+    let entries = walkdir::WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| !entry.file_name().to_str().map_or(false, |name| name.starts_with('.')));
+
+    for entry in entries {
+        let entry = entry.ok()?;
+        let local = entry.path().strip_prefix(root).ok()?;
+        let name = entry.file_name().to_str()?;
+
+        if entry.file_type().is_dir() || (local.parent() == Some(Path::new("")) && METADATA_NAMES.contains(&name)) {
+            continue;
+        }
+
+        let is_out_of_region = crate::is_out_of_region(name, &region);
+
+        if is_out_of_region {
+            continue;
+        }
+
+        if !(local.has_extension("prc") || local.has_extension("prcx") || local.has_extension("prcxml")) {
+            return None;
+        }
+
+        params.push(local.to_path_buf());
+    }
+
+    Some(params)
+}
+
+/// A param of a mod that was enabled or disabled without a reboot, to check that the change went through
+pub struct ToggledParam {
+    pub hash: Hash40,
+    pub root: PathBuf,
+    /// Whether the mod patches the param instead of providing the full file
+    pub is_patch: bool,
+    pub enabled: bool,
+}
+
+/// Finds the params that didn't end up the way the preset change asked for, given where the full file of a param comes from and
+/// where its patches come from. A param of an enabled mod must come from it, and one of a disabled mod must not anymore.
+pub fn misapplied_params(
+    toggled: &[ToggledParam],
+    provider: impl Fn(Hash40) -> Option<PathBuf>,
+    patches: impl Fn(Hash40) -> Vec<PathBuf>,
+) -> Vec<Hash40> {
+    toggled
+        .iter()
+        .filter(|param| {
+            let from_root = if param.is_patch {
+                patches(param.hash).iter().any(|path| path.starts_with(&param.root))
+            } else {
+                provider(param.hash).map_or(false, |path| path.starts_with(&param.root))
+            };
+
+            from_root != param.enabled
+        })
+        .map(|param| param.hash)
+        .collect()
+}
+
+/// Adds a PRC patch file and information to the API loader
+pub fn add_prc_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, full_path: P, local: Q) -> Option<Hash40> {
+    let local = local.as_ref();
+    let base_local = param_patch_base(local);
+    let full_path = full_path.as_ref(); // need the full path so that our API loader can load it
     match base_local.smash_hash() {
         Ok(hash) => {
             tree.insert_file("api:/patch-prc", &base_local);
             tree.loader.push_entry(hash, Path::new("api:/patch-prc"), ApiCallback::None);
             // We need to add our file to the vector of patch files
-            tree.loader.insert_prc_patch(hash, &full_path);
+            tree.loader.insert_prc_patch(hash, full_path);
             if let Some(local) = local.to_str() {
                 hashes::add(local);
             }
@@ -161,8 +339,148 @@ pub fn add_prc_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>,
     }
 }
 
+/// Matches a single folder or file name against a pattern segment, where `*` stands for any number of characters
+fn matches_segment(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            name.starts_with(prefix)
+                && (prefix.len()..=name.len()).any(|index| name.is_char_boundary(index) && matches_segment(rest, &name[index..]))
+        },
+    }
+}
+
+fn matches_components(pattern: &[&str], path: &[&str]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((&"**", rest)) => (0..=path.len()).any(|skip| matches_components(rest, &path[skip..])),
+        Some((segment, rest)) => path
+            .split_first()
+            .map_or(false, |(name, path_rest)| matches_segment(segment, name) && matches_components(rest, path_rest)),
+    }
+}
+
+/// Checks if a local path belongs to a file group pattern. `*` matches part of a name and `**` any number of folders,
+/// and a pattern naming a folder covers everything inside of it.
+pub fn matches_file_pattern(pattern: &str, local: &Path) -> bool {
+    let pattern = pattern.trim_matches('/').to_lowercase().replace('\\', "/");
+    let pattern: Vec<&str> = pattern.split('/').collect();
+
+    local.ancestors().filter(|path| !path.as_os_str().is_empty()).any(|path| {
+        let path = path.to_string_lossy().to_lowercase().replace('\\', "/");
+        let path: Vec<&str> = path.split('/').collect();
+        matches_components(&pattern, &path)
+    })
+}
+
+/// Checks if a game path matches a pattern with the same syntax as [`matches_file_pattern`], without a folder covering its contents
+pub fn matches_path_pattern(pattern: &str, path: &str) -> bool {
+    let pattern = pattern.trim_matches('/').to_lowercase().replace('\\', "/");
+    let pattern: Vec<&str> = pattern.split('/').collect();
+    let path = path.to_lowercase();
+    let path: Vec<&str> = path.split('/').collect();
+
+    matches_components(&pattern, &path)
+}
+
+/// Lists the local paths provided by a file group that was disabled, given the patterns of the disabled groups for each mounted mod folder
+pub fn find_disabled_group_files(tree: &Tree<StandardLoader>, disabled_patterns: &HashMap<PathBuf, Vec<String>>) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+
+    if disabled_patterns.is_empty() {
+        return files;
+    }
+
+    tree.walk_paths(|node, ty| {
+        if !ty.is_file() {
+            return;
+        }
+
+        let local = node.get_local();
+
+        let patterns = match node.full_path().ancestors().nth(local.components().count()).and_then(|root| disabled_patterns.get(root)) {
+            Some(patterns) => patterns,
+            None => return,
+        };
+
+        if patterns.iter().any(|pattern| matches_file_pattern(pattern, local)) {
+            files.push(local.to_path_buf());
+        }
+    });
+
+    files
+}
+
+/// Folders next to an `.eff` file that hold the models and trails it refers to by index
+static EFFECT_COMPANION_DIRS: &[&str] = &["model", "trail"];
+
+/// Effects are split between the `.eff` file and the models and trails next to it, which must come from the same mod or the game reads
+/// the wrong ones. Whenever the mod that provides an `.eff` also has one of its companions, that copy is used even if another mod won it,
+/// and a warning is given for the companions it lacks. Returns the local paths of the companions to use, mapped to the file on the SD.
+pub fn pair_effect_files(tree: &Tree<StandardLoader>) -> HashMap<PathBuf, PathBuf> {
+    // Effect folder -> mod root providing its .eff
+    let mut effects: HashMap<PathBuf, PathBuf> = HashMap::new();
+    let mut files: Vec<(PathBuf, PathBuf)> = Vec::new();
+
+    tree.walk_paths(|node, ty| {
+        if !ty.is_file() {
+            return;
+        }
+
+        let local = node.get_local();
+        let root = match node.full_path().ancestors().nth(local.components().count()) {
+            Some(root) => root.to_path_buf(),
+            None => return,
+        };
+
+        if local.starts_with("effect") && local.has_extension("eff") {
+            if let Some(parent) = local.parent() {
+                effects.insert(parent.to_path_buf(), root);
+            }
+        } else if local.starts_with("effect") {
+            files.push((local.to_path_buf(), root));
+        }
+    });
+
+    let mut overrides = HashMap::new();
+    let mut mismatched = HashSet::new();
+
+    for (local, root) in files {
+        // The effect folder is the parent of the companion folder the file is in, however deep it is
+        let effect_dir = local.ancestors().skip(1).find_map(|dir| {
+            let name = dir.file_name()?.to_str()?;
+            EFFECT_COMPANION_DIRS.contains(&name).then(|| dir.parent()).flatten()
+        });
+
+        let (effect_dir, effect_root) = match effect_dir.and_then(|dir| Some((dir, effects.get(dir)?))) {
+            Some(effect) => effect,
+            None => continue,
+        };
+
+        if *effect_root == root {
+            continue;
+        }
+
+        let paired = effect_root.join(&local);
+
+        if paired.is_file() {
+            debug!("Using '{}' to go with the effect of the same mod instead of '{}'", paired.display(), root.join(&local).display());
+            overrides.insert(local, paired);
+        } else if mismatched.insert(effect_dir.to_path_buf()) {
+            warn!(
+                "The effect in '{}' comes from '{}', but some of its models or trails come from '{}'. The effect might not display correctly.",
+                effect_dir.display(),
+                effect_root.display(),
+                root.display()
+            );
+        }
+    }
+
+    overrides
+}
+
 /// Adds a MSBT patch file and information to the API loader
-pub fn add_msbt_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, phys_root: P, local: Q) -> Option<Hash40> {
+pub fn add_msbt_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, full_path: P, local: Q) -> Option<Hash40> {
     let local = local.as_ref();
     let base_local = local.with_extension("msbt"); // patch files have different extensions
     let mut is_current_region = true;
@@ -179,13 +497,13 @@ pub fn add_msbt_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>
     } else {
         base_local
     };
-    let full_path = phys_root.as_ref().join(local); // need the full path so that our API loader can load it
+    let full_path = full_path.as_ref(); // need the full path so that our API loader can load it
     match base_local.smash_hash() {
         Ok(hash) => {
             tree.insert_file("api:/patch-msbt", &base_local);
             tree.loader.push_entry(hash, Path::new("api:/patch-msbt"), ApiCallback::None);
             // We need to add our file to the vector of patch files
-            tree.loader.insert_msbt_patch(hash, &full_path);
+            tree.loader.insert_msbt_patch(hash, full_path);
             if let Some(local) = local.to_str() {
                 hashes::add(local);
             }
@@ -203,7 +521,7 @@ pub fn add_msbt_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>
     }
 }
 
-pub fn add_nus3audio_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, phys_root: P, local: Q) -> Option<Hash40> {
+pub fn add_nus3audio_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, full_path: P, local: Q) -> Option<Hash40> {
     let local = local.as_ref();
     let base_local = local.with_extension("nus3audio");
 
@@ -218,13 +536,13 @@ pub fn add_nus3audio_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLo
     } else {
         base_local
     };
-    let full_path = phys_root.as_ref().join(local); // need the full path so that our API loader can load it
+    let full_path = full_path.as_ref(); // need the full path so that our API loader can load it
     match base_local.smash_hash() {
         Ok(hash) => {
             tree.insert_file("api:/patch-nus3audio", &base_local);
             tree.loader.push_entry(hash, Path::new("api:/patch-nus3audio"), ApiCallback::None);
             // We need to add our file to the vector of patch files
-            tree.loader.insert_nus3audio_patch(hash, &full_path);
+            tree.loader.insert_nus3audio_patch(hash, full_path);
             if let Some(local) = local.to_str() {
                 hashes::add(local);
             }
@@ -240,7 +558,7 @@ pub fn add_nus3audio_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLo
     }
 }
 
-pub fn add_motionlist_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, phys_root: P, local: Q) -> Option<Hash40> {
+pub fn add_motionlist_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, full_path: P, local: Q) -> Option<Hash40> {
     let local = local.as_ref();
     let base_local = local.with_extension("bin");
 
@@ -255,15 +573,15 @@ pub fn add_motionlist_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiL
     } else {
         base_local
     };
-    let full_path = phys_root.as_ref().join(local); // need the full path so that our API loader can load it
+    let full_path = full_path.as_ref(); // need the full path so that our API loader can load it
     if let Some(name) = full_path.file_name() {
-        if name.to_str().unwrap().contains(&"motion_list") {
+        if name.to_str().map_or(false, |name| name.contains("motion_list")) {
             match base_local.smash_hash() {
                 Ok(hash) => {
                     tree.insert_file("api:/patch-motionlist", &base_local);
                     tree.loader.push_entry(hash, Path::new("api:/patch-motionlist"), ApiCallback::None);
                     // We need to add our file to the vector of patch files
-                    tree.loader.insert_motionlist_patch(hash, &full_path);
+                    tree.loader.insert_motionlist_patch(hash, full_path);
                     if let Some(local) = local.to_str() {
                         hashes::add(local);
                     }
@@ -286,7 +604,45 @@ pub fn add_motionlist_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiL
     None
 }
 
-pub fn add_bgm_property_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, phys_root: P, local: Q) -> Option<Hash40> {
+pub fn add_binary_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, full_path: P, local: Q) -> Option<Hash40> {
+    let local = local.as_ref();
+    // Diffs are named after the file they patch, so removing the extension gives us the patched file
+    let base_local = local.with_extension("");
+
+    let base_local = match base_local.file_name().and_then(|os_str| os_str.to_str()) {
+        Some(name) => base_local.with_file_name(crate::strip_regional_suffix(name)),
+        None => base_local,
+    };
+    let full_path = full_path.as_ref(); // need the full path so that our API loader can load it
+    if base_local.extension().is_none() {
+        error!(
+            "Could not add file {} to API tree. Reason: Binary patches must be named after the file they patch, such as vl.prc.bsdiff",
+            full_path.display()
+        );
+        return None;
+    }
+    match base_local.smash_hash() {
+        Ok(hash) => {
+            tree.insert_file("api:/patch-binary", &base_local);
+            tree.loader.push_entry(hash, Path::new("api:/patch-binary"), ApiCallback::None);
+            // We need to add our file to the vector of patch files
+            tree.loader.insert_binary_patch(hash, full_path);
+            if let Some(local) = local.to_str() {
+                hashes::add(local);
+            }
+            if let Some(base_local) = base_local.to_str() {
+                hashes::add(base_local);
+            }
+            Some(hash)
+        },
+        Err(e) => {
+            error!("Could not add file {} to API tree. Reason: {:?}", full_path.display(), e);
+            None
+        },
+    }
+}
+
+pub fn add_bgm_property_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<ApiLoader>, full_path: P, local: Q) -> Option<Hash40> {
     let local = local.as_ref();
     let base_local = local.with_extension("bin");
 
@@ -301,15 +657,15 @@ pub fn add_bgm_property_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<Ap
     } else {
         base_local
     };
-    let full_path = phys_root.as_ref().join(local); // need the full path so that our API loader can load it
+    let full_path = full_path.as_ref(); // need the full path so that our API loader can load it
     if let Some(name) = full_path.file_name() {
-        if name.to_str().unwrap().contains(&"bgm_property") {
+        if name.to_str().map_or(false, |name| name.contains("bgm_property")) {
             match base_local.smash_hash() {
                 Ok(hash) => {
                     tree.insert_file("api:/patch-bgm_property", &base_local);
                     tree.loader.push_entry(hash, Path::new("api:/patch-bgm_property"), ApiCallback::None);
                     // We need to add our file to the vector of patch files
-                    tree.loader.insert_bgm_property_patch(hash, &full_path);
+                    tree.loader.insert_bgm_property_patch(hash, full_path);
                     if let Some(local) = local.to_str() {
                         hashes::add(local);
                     }
@@ -331,3 +687,69 @@ pub fn add_bgm_property_patch<P: AsRef<Path>, Q: AsRef<Path>>(tree: &mut Tree<Ap
     );
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn winner(paths: &[&str]) -> String {
+        let hash = Hash40::from("ui/message/msg_menu.msbt");
+        let mut precedence = HashMap::new();
+        let mut winner = String::new();
+
+        for path in paths {
+            if takes_precedence(&mut precedence, hash, crate::regional_precedence(path)) {
+                winner = path.to_string();
+            }
+        }
+
+        winner
+    }
+
+    #[test]
+    fn param_reloads_are_checked_against_the_result() {
+        let fighter_param = Hash40::from("fighter/common/param/fighter_param.prc");
+        let item_param = Hash40::from("item/param/item_param.prc");
+        let enabled = PathBuf::from("sd:/ultimate/mods/Enabled");
+        let disabled = PathBuf::from("sd:/ultimate/mods/Disabled");
+
+        let toggled = [
+            ToggledParam { hash: fighter_param, root: enabled.clone(), is_patch: false, enabled: true },
+            ToggledParam { hash: item_param, root: disabled.clone(), is_patch: true, enabled: false },
+        ];
+
+        let providers = HashMap::from([(fighter_param, enabled.join("fighter/common/param/fighter_param.prc"))]);
+        let other_patch = PathBuf::from("sd:/ultimate/mods/Other/item/param/item_param.prcxml");
+        let patches = |hash: Hash40| if hash == item_param { vec![other_patch.clone()] } else { vec![] };
+
+        assert!(misapplied_params(&toggled, |hash| providers.get(&hash).cloned(), patches).is_empty());
+
+        // The full param is still served from the file it replaced, and the patch of the disabled mod is still applied
+        let stale_patches = |hash: Hash40| if hash == item_param { vec![disabled.join("item/param/item_param.prcxml")] } else { vec![] };
+
+        assert_eq!(misapplied_params(&toggled, |_| None, stale_patches), [fighter_param, item_param]);
+    }
+
+    #[test]
+    fn regional_precedence_does_not_depend_on_the_walk_order() {
+        let generic = "ui/message/msg_menu.msbt";
+        let wildcard = "ui/message/msg_menu+Region+.msbt";
+        let specific = "ui/message/msg_menu+us_en.msbt";
+
+        let orders = [
+            [generic, wildcard, specific],
+            [generic, specific, wildcard],
+            [wildcard, generic, specific],
+            [wildcard, specific, generic],
+            [specific, generic, wildcard],
+            [specific, wildcard, generic],
+        ];
+
+        for order in orders.iter() {
+            assert_eq!(winner(order), specific, "{:?}", order);
+        }
+
+        assert_eq!(winner(&[generic, wildcard]), wildcard);
+        assert_eq!(winner(&[wildcard, generic]), wildcard);
+    }
+}
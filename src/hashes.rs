@@ -40,6 +40,11 @@ pub fn find(hash: Hash40) -> &'static str {
     try_find(hash).unwrap_or("Unknown")
 }
 
+/// Every path known to the hash list, including the ones added by mods
+pub fn known_paths() -> Vec<&'static str> {
+    HASHES.read().unwrap().values().copied().collect()
+}
+
 pub fn add<S: AsRef<str>>(new_hash: S) {
     let new_hash = new_hash.as_ref();
     let mut hashes = HASHES.write().unwrap();
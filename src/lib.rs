@@ -23,6 +23,8 @@ use skyline::{hooks::InlineCtx, libc::c_char, nn};
 
 mod api;
 mod chainloader;
+#[cfg(feature = "ui")]
+mod console;
 mod fixes;
 mod fs;
 mod fuse;
@@ -37,7 +39,7 @@ mod utils;
 mod lua;
 
 use fs::GlobalFilesystem;
-use smash_arc::{Hash40, Region};
+use smash_arc::{ArcLookup, Hash40, Region};
 
 use crate::utils::save::{get_language_id_in_savedata, get_system_region_from_language_id, mount_save, unmount_save};
 
@@ -120,23 +122,27 @@ impl PathExtension for Path {
                 return Ok(hash);
             }
         }
-        let mut path = self
-            .as_os_str()
-            .to_str()
-            .ok_or(InvalidOsStrError)?
-            .to_lowercase()
-            .replace(';', ":")
-            .replace(".mp4", ".webm")
-            .replace(".lua", ".lc");
-
-        if let Some(regional_idx) = path.find('+') {
-            path.replace_range(regional_idx..regional_idx + 6, "")
-        }
+        let path = self.as_os_str().to_str().ok_or(InvalidOsStrError)?;
 
-        Ok(Hash40::from(path.trim_start_matches('/')))
+        Ok(Hash40(game_path_hash(path)))
     }
 }
 
+/// Normalizes a path relative to a mod root the same way discovery does and hashes it.
+///
+/// This lowercases the path, turns `;` into `:` and `\` into `/`, maps the extensions that are swapped on the SD card (`.mp4`, `.lua`)
+/// back to the ones used in the game, strips the regional suffix (`+us_en`) and any leading `/`. Prefer this over reimplementing the rules.
+pub fn game_path_hash(relative_path: &str) -> u64 {
+    let path = relative_path
+        .to_lowercase()
+        .replace('\\', "/")
+        .replace(';', ":")
+        .replace(".mp4", ".webm")
+        .replace(".lua", ".lc");
+
+    Hash40::from(strip_regional_suffix(&path).trim_start_matches('/')).0
+}
+
 /// Basic code for getting a hash40 from a path, ignoring things like if it exists
 fn get_smash_hash<P: AsRef<Path>>(path: P) -> Result<Hash40, InvalidOsStrError> {
     path.as_ref().smash_hash()
@@ -154,6 +160,23 @@ pub const REGIONS: &[&str] = &[
     "jp_ja", "us_en", "us_fr", "us_es", "eu_en", "eu_fr", "eu_es", "eu_de", "eu_nl", "eu_it", "eu_ru", "kr_ko", "zh_cn", "zh_tw",
 ];
 
+// Shared with the mod manager, which has to match the files of mods the same way
+pub use config::{is_out_of_region, strip_regional_suffix, REGION_WILDCARD};
+
+pub fn has_region_wildcard(path: &str) -> bool {
+    path.to_ascii_lowercase().contains(REGION_WILDCARD)
+}
+
+/// Precedence of a file over the other files of a mod that map to the same file of the game, the highest one being used. A file for a
+/// specific region (`+us_en`) comes first, then a file using the region wildcard, then a file without a regional suffix.
+pub fn regional_precedence(path: &str) -> u8 {
+    match path.find('+') {
+        None => 0,
+        Some(_) if has_region_wildcard(path) => 1,
+        Some(_) => 2,
+    }
+}
+
 /// Initializes the `nn::time` library, for creating a log file based off of the current time. For some reason Smash does not initialize this
 fn init_time() {
     unsafe {
@@ -197,6 +220,38 @@ fn get_news_data() {
     }
 }
 
+/// Uses the mods directory from the override file for this boot, if there is one. The file is renamed right away so the next boot goes
+/// back to the regular mods directory.
+fn apply_mods_path_override() {
+    let sentinel = utils::paths::mods_override_sentinel();
+
+    let path = match std::fs::read_to_string(&sentinel) {
+        Ok(path) => path.trim().to_string(),
+        Err(_) => return,
+    };
+
+    // A file left over from a previous override would make the rename fail
+    let consumed = sentinel.with_extension("txt.used");
+
+    if consumed.exists() {
+        let _ = std::fs::remove_file(&consumed);
+    }
+
+    if let Err(e) = std::fs::rename(&sentinel, consumed) {
+        // Keeping the override without being able to consume it would apply it on every boot
+        error!("Failed to consume '{}', ignoring the mods directory override. Reason: {:?}", sentinel, e);
+        return;
+    }
+
+    if path.is_empty() || !Path::new(&path).is_dir() {
+        warn!("The mods directory override '{}' is not a directory, using the regular mods directory instead.", path);
+        return;
+    }
+
+    warn!("Using '{}' as the mods directory for this boot only.", path);
+    *config::MODS_PATH_OVERRIDE.write().unwrap() = Some(path);
+}
+
 #[cfg(feature = "ui")]
 fn check_input_on_boot() {
     if !crate::utils::env::is_emulator() {
@@ -204,9 +259,62 @@ fn check_input_on_boot() {
         if ninput::any::is_down(ninput::Buttons::PLUS) {
             menus::show_main_menu();
         }
+
+        // Choose the workspace to load if L is held, so several setups can be kept side by side
+        if ninput::any::is_down(ninput::Buttons::L) {
+            menus::show_workspaces_on_boot();
+        }
+
+        // Boot without any replacement if R is held, the mods come back on the next boot
+        if ninput::any::is_down(ninput::Buttons::R) {
+            config::set_replacements_bypassed(true);
+        }
+
+        // Offer to reset the configuration if ZL and ZR are held, for when it keeps the game from booting
+        if ninput::any::is_down(ninput::Buttons::ZL | ninput::Buttons::ZR) {
+            menus::recovery::show_safe_mode();
+        }
     }
 }
 
+/// What ARCropolis did during boot, shown to the user instead of leaving them to guess from the game
+#[cfg(feature = "ui")]
+fn boot_summary(filesystem: &GlobalFilesystem) -> String {
+    let mut lines = vec![format!(
+        "{} file(s) loaded from {} mod(s).",
+        filesystem.get_file_count().unwrap_or(0),
+        fs::progress::discovery_progress().mods_total
+    )];
+
+    let callbacks = filesystem.get().virt().loader.callback_count();
+
+    if callbacks > 0 {
+        lines.push(format!("{} file(s) provided by plugins.", callbacks));
+    }
+
+    let ineffective_mods = filesystem.ineffective_mods();
+
+    if !ineffective_mods.is_empty() {
+        lines.push(format!(
+            "{} mod(s) contain no file matching a game path and will not change anything. Check that their files are named correctly and \
+             made for this version of the game:<br>{}",
+            ineffective_mods.len(),
+            ineffective_mods
+                .iter()
+                .map(|root| format!("- {}", root.file_name().unwrap_or(root.as_os_str()).to_string_lossy()))
+                .collect::<Vec<_>>()
+                .join("<br>")
+        ));
+    }
+
+    match logging::errors_logged() {
+        0 => {},
+        errors => lines.push(format!("{} error(s) were logged during boot, see the latest log for details.", errors)),
+    }
+
+    lines.join("<br>")
+}
+
 #[cfg(feature = "online")]
 fn check_for_update() {
     // Changed to pre because prerelease doesn't compile
@@ -231,6 +339,19 @@ fn check_for_update() {
     }
 }
 
+/// Stops the threads ARCropolis keeps running in the background, so that none of them starts working on the filesystem while the
+/// application is closing
+pub fn stop_background_threads() {
+    fs::rescan::stop_watcher();
+    fs::sd_card::stop_watcher();
+
+    // The buffered lines would otherwise be lost with the application
+    if replacement::coverage::is_enabled() {
+        replacement::coverage::stop();
+    }
+    log::logger().flush();
+}
+
 #[skyline::hook(offset = offsets::initial_loading(), inline)]
 fn initial_loading(_ctx: &InlineCtx) {
     #[cfg(feature = "online")]
@@ -266,6 +387,25 @@ fn initial_loading(_ctx: &InlineCtx) {
     filesystem.share_hashes();
     filesystem.patch_files();
 
+    // QA only, reads every mapped file ahead of time to catch broken ones before they crash the game
+    if config::integrity_check_enabled() {
+        let passed = filesystem.run_integrity_check();
+
+        if config::integrity_check_exit() {
+            log::logger().flush();
+            skyline::error::show_error(
+                69,
+                "ARCropolis integrity check finished, press Details.\0",
+                &format!(
+                    "The integrity check {}. The report can be found at sd:/ultimate/arcropolis/integrity_report.txt\0",
+                    if passed { "passed" } else { "failed" }
+                ),
+            );
+            stop_background_threads();
+            unsafe { skyline::nn::oe::ExitApplication() }
+        }
+    }
+
     if config::debug_enabled() {
         let mut output = BufWriter::new(std::fs::File::create("sd:/ultimate/arcropolis/filesystem_dump.txt").unwrap());
         filesystem.get().walk_patch(|node, entry_type| {
@@ -281,11 +421,23 @@ fn initial_loading(_ctx: &InlineCtx) {
         });
     }
 
+    #[cfg(feature = "ui")]
+    let summary = (config::boot_summary_enabled() && !utils::env::is_emulator()).then(|| boot_summary(&filesystem));
+
     drop(filesystem);
 
+    // Shown once the filesystem is released, the game would otherwise be stuck waiting on it for as long as the dialog is open
+    #[cfg(feature = "ui")]
+    if let Some(summary) = summary {
+        menus::show_boot_summary(&summary);
+    }
+
     fuse::mods::install_mod_fs();
     api::event::send_event(Event::ModFilesystemMounted);
 
+    fs::rescan::start_watcher();
+    fs::sd_card::start_watcher();
+
     // #[cfg(feature = "online")]
     // _updater.join().unwrap();
 }
@@ -433,7 +585,7 @@ pub fn main() {
         );
     }));
 
-    if utils::env::get_game_version() != semver::Version::new(13, 0, 4) {
+    if utils::env::get_game_version() != resource::LAYOUT_GAME_VERSION {
         skyline::error::show_error(69, "Smash Ultimate requires an update.\0", "ARCropolis cannot currently run on a Smash version other than 13.0.4\n\nConsider updating your game or uninstalling ARCropolis.\0");
         // Do not perform any of the hook installation and let the game proceed as normal.
         return;
@@ -475,6 +627,8 @@ pub fn main() {
         println!("[arcropolis] Failed to initialize logger. Reason: {:?}", err);
     }
 
+    apply_mods_path_override();
+
     // Acquire the filesystem and promise it to the initial_loading hook
     let mut filesystem = unsafe { GLOBAL_FILESYSTEM.write().unwrap() };
 
@@ -502,6 +656,28 @@ pub fn main() {
 
     skyline::install_hooks!(initial_loading, change_version_string, online_slot_spoof);
 
+    #[cfg(feature = "ui")]
+    menus::set_console_handler(console::handle_command);
+
+    // The sizes are only known once the filesystem is built, ARCadia shows them as unknown until then
+    #[cfg(feature = "ui")]
+    menus::set_file_resolver(|local| {
+        let hash = Path::new(local).smash_hash().unwrap_or_else(|_| Hash40(game_path_hash(local)));
+
+        menus::ResolvedFile {
+            hash: hash.0,
+            game_path: hashes::try_find(hash).map(String::from),
+            original_size: unsafe { GLOBAL_FILESYSTEM.try_read() }.ok().and_then(|filesystem| filesystem.original_size(hash)),
+            in_game: resource::initialized().then(|| resource::arc().get_file_path_index_from_hash(hash).is_ok()),
+        }
+    });
+
+    // Not waited on, ARCadia can be opened during discovery which is only over once the filesystem is released
+    #[cfg(feature = "ui")]
+    menus::set_preset_reloader(|enabled, disabled| {
+        unsafe { GLOBAL_FILESYSTEM.try_write() }.map_or(false, |mut filesystem| filesystem.reload_params(enabled, disabled))
+    });
+
     #[cfg(feature = "ui")]
     skyline::install_hook!(show_eshop);
 
@@ -521,3 +697,81 @@ pub fn main() {
 
     api::event::setup();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(path: &str) -> u64 {
+        Hash40::from(path).0
+    }
+
+    #[test]
+    fn game_path_hash_normalizes_case_and_separators() {
+        assert_eq!(game_path_hash("Fighter/Mario/Model/Body/C00/Model.NUMDLB"), hash("fighter/mario/model/body/c00/model.numdlb"));
+        assert_eq!(game_path_hash("fighter\\mario\\model\\body\\c00\\model.numdlb"), hash("fighter/mario/model/body/c00/model.numdlb"));
+        assert_eq!(game_path_hash("/fighter/mario/model/body/c00/model.numdlb"), hash("fighter/mario/model/body/c00/model.numdlb"));
+    }
+
+    #[test]
+    fn game_path_hash_restores_colons() {
+        assert_eq!(game_path_hash("stream;/sound/bgm/bgm_a01.nus3audio"), hash("stream:/sound/bgm/bgm_a01.nus3audio"));
+        assert_eq!(game_path_hash("prebuilt;/nro/release/lua2cpp_mario.nro"), hash("prebuilt:/nro/release/lua2cpp_mario.nro"));
+    }
+
+    #[test]
+    fn game_path_hash_maps_swapped_extensions() {
+        assert_eq!(game_path_hash("stream;/movie/fighter/mario.mp4"), hash("stream:/movie/fighter/mario.webm"));
+        assert_eq!(game_path_hash("script/ui/menu.lua"), hash("script/ui/menu.lc"));
+    }
+
+    #[test]
+    fn game_path_hash_strips_regions() {
+        assert_eq!(game_path_hash("ui/message/msg_menu+us_en.msbt"), hash("ui/message/msg_menu.msbt"));
+        assert_eq!(game_path_hash("ui/message/msg_menu+region+.msbt"), hash("ui/message/msg_menu.msbt"));
+        assert_eq!(game_path_hash("UI/Message/MSG_Menu+US_EN.msbt"), hash("ui/message/msg_menu.msbt"));
+    }
+
+    #[test]
+    fn region_wildcard_is_matched_whatever_its_case() {
+        assert_eq!(game_path_hash("ui/message/msg_menu+Region+.msbt"), hash("ui/message/msg_menu.msbt"));
+        assert!(has_region_wildcard("UI/msg_menu+REGION+.msbt"));
+        assert!(!has_region_wildcard("ui/msg_menu+us_en.msbt"));
+    }
+
+    #[test]
+    fn regional_suffixes_are_matched_whatever_their_case() {
+        assert!(!is_out_of_region("msg_menu+US_EN.msbt", "us_en"));
+        assert!(!is_out_of_region("msg_menu+us_en.msbt", "US_EN"));
+        assert!(is_out_of_region("msg_menu+eu_fr.msbt", "us_en"));
+        assert!(!is_out_of_region("msg_menu+Region+.msbt", "us_en"));
+        assert!(!is_out_of_region("msg_menu.msbt", "us_en"));
+    }
+
+    #[test]
+    fn specific_regions_take_precedence_over_the_wildcard() {
+        let generic = regional_precedence("ui/message/msg_menu.msbt");
+        let wildcard = regional_precedence("ui/message/msg_menu+REGION+.msbt");
+        let specific = regional_precedence("ui/message/msg_menu+us_en.msbt");
+
+        assert!(generic < wildcard && wildcard < specific);
+    }
+
+    #[test]
+    fn regional_suffixes_are_stripped_from_names() {
+        assert_eq!(strip_regional_suffix("vl+us_en.prc"), "vl.prc");
+        assert_eq!(strip_regional_suffix("vl+Region+.prc"), "vl.prc");
+        assert_eq!(strip_regional_suffix("vl.prc"), "vl.prc");
+        // A binary patch named `a+b.bsdiff` patches `a+b`, which used to be cut past its end
+        assert_eq!(strip_regional_suffix("a+b"), "a");
+    }
+
+    #[test]
+    fn game_path_hash_handles_short_suffixes() {
+        // Used to index past the end of the path
+        assert_eq!(game_path_hash("ui/a+b"), hash("ui/a"));
+        assert_eq!(game_path_hash("ui/a+"), hash("ui/a"));
+        // Used to split a character in two
+        assert_eq!(game_path_hash("ui/é+ééééé.msbt"), hash("ui/é.msbt"));
+    }
+}
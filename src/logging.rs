@@ -1,8 +1,8 @@
 use std::{
-    fs::File, io::{BufWriter, Write}, ops::Deref, path::Path, sync::{LazyLock, Mutex}, time::SystemTime
+    collections::HashMap, fs::File, io::{BufWriter, Write}, ops::Deref, path::Path, sync::{atomic::{AtomicUsize, Ordering}, LazyLock, Mutex}, time::{Duration, Instant, SystemTime}
 };
 
-use log::{LevelFilter, Metadata, Record, SetLoggerError};
+use log::{Level, LevelFilter, Metadata, Record, SetLoggerError};
 use skyline::nn::time;
 
 /// Since we can't rely on most time based libraries, this is a seconds -> date/time string based on the `chrono` crates implementation
@@ -14,6 +14,8 @@ fn get_time_string() -> String {
 
 static LOG_PATH: &str = "sd:/ultimate/arcropolis/logs";
 static FILE_LOG_BUFFER: usize = 0x2000; // Room for 0x2000 characters, might have performance issues if the logger level is "Info" or "Trace"
+/// Amount of log files kept on the SD, counting the one of the current boot
+static MAX_LOG_FILES: usize = 10;
 struct FileLogger(Option<Mutex<BufWriter<File>>>);
 
 impl Deref for FileLogger {
@@ -33,6 +35,26 @@ impl FileLogger {
     }
 }
 
+/// Removes the oldest log files so that the new one doesn't go past the limit. Their names start with the time they were created at,
+/// so sorting them by name sorts them by age.
+fn remove_old_logs() {
+    let mut logs: Vec<_> = match std::fs::read_dir(LOG_PATH) {
+        Ok(read_dir) => read_dir
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().map_or(false, |extension| extension == "log"))
+            .collect(),
+        Err(_) => return,
+    };
+
+    logs.sort();
+
+    for path in logs.iter().take((logs.len() + 1).saturating_sub(MAX_LOG_FILES)) {
+        if let Err(e) = std::fs::remove_file(path) {
+            error!(target: "std", "Failed to remove old log file '{}'. Reason: {:?}", path.display(), e);
+        }
+    }
+}
+
 // Summon the file logger and create a file for it based on the current time (requires time to be initialized)
 static FILE_WRITER: LazyLock<FileLogger> = LazyLock::new(|| {
     let seconds = SystemTime::now()
@@ -40,6 +62,7 @@ static FILE_WRITER: LazyLock<FileLogger> = LazyLock::new(|| {
         .expect("Clock may have gone backwards!");
     let path = Path::new(LOG_PATH).join(format!("{}.log", get_time_string()));
     let _ = std::fs::create_dir_all(LOG_PATH);
+    remove_old_logs();
     std::fs::File::create(path).map_or_else(
         |_| {
             error!(target: "std", "Unable to initialize the file logger!");
@@ -57,10 +80,90 @@ static FILE_WRITER: LazyLock<FileLogger> = LazyLock::new(|| {
     )
 });
 
+/// Read once, since it is consulted from the hooks
+static DEDUP_WINDOW: LazyLock<Duration> = LazyLock::new(|| Duration::from_millis(config::log_dedup_window_ms()));
+
+struct DedupEntry {
+    last_logged: Instant,
+    repeats: usize,
+}
+
+type DedupEntries = HashMap<(&'static str, u64), DedupEntry>;
+
+static DEDUP_ENTRIES: LazyLock<Mutex<DedupEntries>> = LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Amount of messages tracked at once. Past that, the ones whose window is over are forgotten, which only loses the count of repeats
+/// they skipped, and messages are logged without being tracked if every one of them is still within its window.
+const DEDUP_MAX_ENTRIES: usize = 0x1000;
+
+/// Decides if a message identified by `key` about the file `hash` should be logged. Returns `None` if it was already logged within the
+/// dedup window, otherwise the amount of times it was skipped since the last time it was logged.
+pub fn dedup(key: &'static str, hash: u64) -> Option<usize> {
+    let window = *DEDUP_WINDOW;
+
+    if window.is_zero() {
+        return Some(0);
+    }
+
+    dedup_entry(&mut DEDUP_ENTRIES.lock().unwrap(), key, hash, Instant::now(), window)
+}
+
+fn dedup_entry(entries: &mut DedupEntries, key: &'static str, hash: u64, now: Instant, window: Duration) -> Option<usize> {
+    if entries.len() >= DEDUP_MAX_ENTRIES && !entries.contains_key(&(key, hash)) {
+        entries.retain(|_, entry| now.duration_since(entry.last_logged) < window);
+
+        if entries.len() >= DEDUP_MAX_ENTRIES {
+            return Some(0);
+        }
+    }
+
+    match entries.get_mut(&(key, hash)) {
+        Some(entry) if now.duration_since(entry.last_logged) < window => {
+            entry.repeats += 1;
+            None
+        },
+        Some(entry) => {
+            entry.last_logged = now;
+            Some(std::mem::take(&mut entry.repeats))
+        },
+        None => {
+            entries.insert((key, hash), DedupEntry { last_logged: now, repeats: 0 });
+            Some(0)
+        },
+    }
+}
+
+/// Logs a message from a hook, collapsing repeats of the same message about the same file that happen within the dedup window.
+/// The level is checked before anything else so that nothing is locked or allocated when it is filtered out.
+///
+/// Usage: `dedup_log!(Level::Info, "replaced", hash.0, "Replaced file '{}'", hashes::find(hash))`
+#[macro_export]
+macro_rules! dedup_log {
+    (target: $target:expr, $lvl:expr, $key:expr, $hash:expr, $($arg:tt)+) => {
+        if log::log_enabled!(target: $target, $lvl) {
+            match $crate::logging::dedup($key, $hash) {
+                Some(0) => log::log!(target: $target, $lvl, $($arg)+),
+                Some(repeats) => log::log!(target: $target, $lvl, "{} ({} repeats skipped)", format_args!($($arg)+), repeats),
+                None => {},
+            }
+        }
+    };
+    ($lvl:expr, $key:expr, $hash:expr, $($arg:tt)+) => {
+        $crate::dedup_log!(target: module_path!(), $lvl, $key, $hash, $($arg)+)
+    };
+}
+
 struct ArcLogger;
 
 static LOGGER: ArcLogger = ArcLogger;
 
+/// Errors logged since boot, counted even when they don't go to a file so that the user can be told about them
+static ERRORS_LOGGED: AtomicUsize = AtomicUsize::new(0);
+
+pub fn errors_logged() -> usize {
+    ERRORS_LOGGED.load(Ordering::Relaxed)
+}
+
 pub fn init(filter: LevelFilter) -> Result<(), SetLoggerError> {
     log::set_logger(&LOGGER).map(|()| log::set_max_level(filter))
 }
@@ -81,6 +184,10 @@ impl log::Log for ArcLogger {
             None => return,
         };
 
+        if record.level() == Level::Error {
+            ERRORS_LOGGED.fetch_add(1, Ordering::Relaxed);
+        }
+
         let skip_mod_path = record.target() == "no-mod-path";
 
         let message = if record.level() == LevelFilter::Debug && !skip_mod_path {
@@ -129,3 +236,28 @@ impl log::Log for ArcLogger {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dedup_entries_stay_bounded() {
+        let window = Duration::from_millis(100);
+        let start = Instant::now();
+        let mut entries = DedupEntries::new();
+
+        for hash in 0..DEDUP_MAX_ENTRIES as u64 * 2 {
+            assert_eq!(dedup_entry(&mut entries, "replaced", hash, start, window), Some(0));
+        }
+        assert_eq!(entries.len(), DEDUP_MAX_ENTRIES);
+
+        // Tracked messages are still collapsed
+        assert_eq!(dedup_entry(&mut entries, "replaced", 0, start, window), None);
+
+        // Once their window is over, the old messages make room for new ones
+        let later = start + window;
+        assert_eq!(dedup_entry(&mut entries, "replaced", u64::MAX, later, window), Some(0));
+        assert_eq!(entries.len(), 1);
+    }
+}
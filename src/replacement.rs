@@ -3,6 +3,8 @@ pub mod lookup;
 
 pub mod addition;
 // pub mod config;
+pub mod coverage;
+pub mod nutexb;
 pub mod preprocess;
 mod stream;
 mod threads;
@@ -10,6 +12,7 @@ mod uncompressed;
 pub mod unshare;
 
 pub use extensions::*;
+pub use threads::invalidate_loaded_file;
 
 pub fn install() {
     stream::install();
@@ -0,0 +1,142 @@
+//! Lists the files the game loads that no mod replaces, for mod authors who want to know which files they still have to cover. Only
+//! meant for development, it is turned on with the `log_vanilla_files` flag.
+//!
+//! Each file is written once per boot, by its path when the hash list knows it and by its hash otherwise. The hooks only queue the files,
+//! a background thread writes them to the SD in batches so that the loaders never wait on it, and what is left is written once the game
+//! stops loading.
+
+use std::{
+    collections::HashSet,
+    io::Write,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        LazyLock, Mutex, Once,
+    },
+    time::Duration,
+};
+
+use smash_arc::Hash40;
+
+use crate::hashes;
+
+/// Time between two writes to the SD
+const WRITE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Files listed per boot at most, past which the list stops growing
+const MAX_FILES: usize = 100_000;
+
+static ENABLED: LazyLock<bool> = LazyLock::new(config::log_vanilla_files_enabled);
+static FLUSHER: Once = Once::new();
+static IS_STOPPED: AtomicBool = AtomicBool::new(false);
+
+struct Coverage {
+    seen: HashSet<Hash40>,
+    /// Files waiting for the next write
+    pending: Vec<Hash40>,
+}
+
+static COVERAGE: LazyLock<Mutex<Coverage>> = LazyLock::new(|| {
+    Mutex::new(Coverage {
+        seen: HashSet::new(),
+        pending: Vec::new(),
+    })
+});
+
+/// State of the list on the SD. Held for the whole write so that batches are written in order, without holding up the hooks.
+struct Writer {
+    has_written: bool,
+    warned_full: bool,
+}
+
+static WRITER: Mutex<Writer> = Mutex::new(Writer {
+    has_written: false,
+    warned_full: false,
+});
+
+pub fn is_enabled() -> bool {
+    *ENABLED
+}
+
+/// Queues a file the game loaded from the data.arc for the list, if it isn't on it yet. Called from the loading hooks, so the SD is
+/// left to the background thread.
+pub fn record(hash: Hash40) {
+    FLUSHER.call_once(spawn_flusher);
+
+    let mut coverage = COVERAGE.lock().unwrap();
+
+    if coverage.seen.len() >= MAX_FILES || !coverage.seen.insert(hash) {
+        return;
+    }
+
+    coverage.pending.push(hash);
+}
+
+/// Writes the queued files every [`WRITE_INTERVAL`]
+fn spawn_flusher() {
+    let flusher = std::thread::Builder::new().stack_size(0x10000).spawn(|| {
+        while !IS_STOPPED.load(Ordering::SeqCst) {
+            std::thread::sleep(WRITE_INTERVAL);
+            flush();
+        }
+    });
+
+    if let Err(e) = flusher {
+        error!("Failed to start writing the list of vanilla files in the background. Reason: {:?}", e);
+    }
+}
+
+/// Writes the queued files right away
+pub fn flush() {
+    let mut writer = WRITER.lock().unwrap();
+
+    // Taken out so that the hooks can keep queueing files during the write
+    let (pending, is_full) = {
+        let mut coverage = COVERAGE.lock().unwrap();
+        (std::mem::take(&mut coverage.pending), coverage.seen.len() >= MAX_FILES)
+    };
+
+    if pending.is_empty() {
+        return;
+    }
+
+    let path = crate::utils::paths::vanilla_files();
+    // Started over on every boot, the files seen during the previous one may not be loaded this time
+    let first_write = !writer.has_written;
+
+    let file = std::fs::OpenOptions::new().create(true).write(true).append(!first_write).truncate(first_write).open(&path);
+
+    let result = file.and_then(|mut file| {
+        let mut contents = pending
+            .iter()
+            .map(|hash| match hashes::try_find(*hash) {
+                Some(path) => path.to_string(),
+                None => format!("{:#x}", hash.0),
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        contents.push('\n');
+        file.write_all(contents.as_bytes())
+    });
+
+    match result {
+        Ok(()) => writer.has_written = true,
+        Err(e) => {
+            error!("Failed to write the list of vanilla files to '{}'. Reason: {:?}", path, e);
+            // Tried again with the next batch, ahead of the files queued in the meantime
+            let mut coverage = COVERAGE.lock().unwrap();
+            let queued = std::mem::replace(&mut coverage.pending, pending);
+            coverage.pending.extend(queued);
+        },
+    }
+
+    if is_full && !writer.warned_full {
+        writer.warned_full = true;
+        warn!("{} vanilla files were listed, the files loaded from now on will not be added to '{}'.", MAX_FILES, path);
+    }
+}
+
+/// Writes what is left of the list and stops the background writes, for when the application closes
+pub fn stop() {
+    IS_STOPPED.store(true, Ordering::SeqCst);
+    flush();
+}
@@ -194,6 +194,26 @@ pub trait LoadedArcEx {
     fn contains_file(&self, hash: Hash40) -> bool;
 }
 
+/// Sets the size the game allocates for a file to the size of its replacement. Returns whether the entry is stored raw, in which case
+/// its compressed size is patched as well.
+///
+/// Compressed entries still get their vanilla data decompressed into the bigger buffer before we replace it, so the compressed size
+/// has to stay untouched for the stream to be read correctly. Entries stored raw are read as-is though, and the game expects both
+/// sizes to match for those, otherwise it reads less than the buffer it allocated and considers the load incomplete. They are marked
+/// as not using zstd either, so that nothing tries to decompress the raw data.
+fn patch_file_data_sizes(file_data: &mut FileData, size: u32) -> bool {
+    file_data.decomp_size = size;
+
+    if file_data.flags.compressed() {
+        return false;
+    }
+
+    file_data.comp_size = size;
+    file_data.flags.set_compressed(false);
+    file_data.flags.set_use_zstd(false);
+    true
+}
+
 impl LoadedArcEx for LoadedArc {
     fn get_file_hash_to_path_index_mut(&mut self) -> &mut [HashToIndex] {
         unsafe {
@@ -230,7 +250,18 @@ impl LoadedArcEx for LoadedArc {
 
         let file_data = self.get_file_data_mut(&file_info, region);
         let old_size = file_data.decomp_size;
-        file_data.decomp_size = size;
+        let old_comp_size = file_data.comp_size;
+
+        if patch_file_data_sizes(file_data, size) {
+            debug!(
+                "File '{}' ({:#x}) is stored uncompressed, patching its compressed size as well. {:#x} -> {:#x}",
+                hashes::find(hash),
+                hash.0,
+                old_comp_size,
+                size
+            );
+        }
+
         Ok(old_size)
     }
 
@@ -1011,3 +1042,44 @@ impl FromPathExt for PathListEntry {
         Some(result)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use smash_arc::FileDataFlags;
+
+    use super::*;
+
+    fn file_data(comp_size: u32, decomp_size: u32, compressed: bool) -> FileData {
+        FileData {
+            offset_in_folder: 0,
+            comp_size,
+            decomp_size,
+            flags: FileDataFlags::new().with_compressed(compressed).with_use_zstd(compressed),
+        }
+    }
+
+    #[test]
+    fn compressed_entry_keeps_its_stream_size() {
+        let mut data = file_data(0x800, 0x2000, true);
+
+        assert!(!patch_file_data_sizes(&mut data, 0x3000));
+
+        assert_eq!({ data.decomp_size }, 0x3000);
+        assert_eq!({ data.comp_size }, 0x800);
+        assert!(data.flags.compressed());
+        assert!(data.flags.use_zstd());
+    }
+
+    #[test]
+    fn raw_entry_sizes_match_the_replacement() {
+        let mut data = file_data(0x2000, 0x2000, false);
+        data.flags.set_use_zstd(true);
+
+        assert!(patch_file_data_sizes(&mut data, 0x3000));
+
+        assert_eq!({ data.decomp_size }, 0x3000);
+        assert_eq!({ data.comp_size }, 0x3000);
+        assert!(!data.flags.compressed());
+        assert!(!data.flags.use_zstd());
+    }
+}
@@ -107,6 +107,9 @@ pub fn initialize_unshare(arc: Option<&LoadedArc>) {
             }
 
             match bincode::serialize(&lookup) {
+                Ok(data) if !crate::utils::storage::has_room_for(data.len() as u64) => {
+                    warn!("Not enough free space on the SD card to cache the unshare LUT, it will be generated again on the next boot.");
+                },
                 Ok(data) => {
                     let path = crate::utils::paths::cache().join("unshare.lut");
                     if let Err(e) = std::fs::write(&path, data) {
@@ -183,6 +186,9 @@ pub fn initialize_share(arc: Option<&LoadedArc>) {
             };
 
             match bincode::serialize(&lookup) {
+                Ok(data) if !crate::utils::storage::has_room_for(data.len() as u64) => {
+                    warn!("Not enough free space on the SD card to cache the share LUT, it will be generated again on the next boot.");
+                },
                 Ok(data) => {
                     let path = crate::utils::paths::cache().join("share.lut");
                     if let Err(e) = std::fs::write(&path, data) {
@@ -0,0 +1,136 @@
+//! The game reads the footer of a texture from the end of the buffer it allocated for it, which is sized for the vanilla texture. A
+//! smaller replacement leaves its footer somewhere in the middle of that buffer, so it has to be moved to the end for the game to pick
+//! up the right resolution and mipmaps instead of reading leftover data.
+//!
+//! The footer is made of the mipmap sizes of every layer, followed by the texture info:
+//!
+//! | Offset  | Size    | Field                                       |
+//! |---------|---------|---------------------------------------------|
+//! | `0x00`  | `0x40`  | Mipmap sizes, repeated for every layer      |
+//! | `+0x00` | `0x4`   | `" XNT"`                                    |
+//! | `+0x04` | `0x40`  | Name                                        |
+//! | `+0x44` | `0xC`   | Width, height, depth                        |
+//! | `+0x50` | `0x1`   | Format                                      |
+//! | `+0x51` | `0x7`   | Unknown                                     |
+//! | `+0x58` | `0x4`   | Mipmap count                                |
+//! | `+0x5C` | `0x4`   | Alignment                                   |
+//! | `+0x60` | `0x4`   | Layer count, 6 for cube maps                |
+//! | `+0x64` | `0x4`   | Size of the image data                      |
+//! | `+0x68` | `0x8`   | `" XET"` and the version                    |
+
+use thiserror::Error;
+
+const INFO_SIZE: usize = 0x70;
+const MIPMAP_SIZES_SIZE: usize = 0x40;
+const MAX_MIPMAPS: u32 = 16;
+/// Array textures can go past the 6 layers of a cube map, but nothing in the game comes close to this
+const MAX_LAYERS: u32 = 0x100;
+
+#[derive(Debug, Error)]
+pub enum FooterError {
+    #[error("the texture is too small to hold a footer ({0:#x} bytes)")]
+    TooSmall(usize),
+    #[error("the footer magic is missing")]
+    MissingMagic,
+    #[error("the texture has an invalid size ({width}x{height}x{depth})")]
+    InvalidDimensions { width: u32, height: u32, depth: u32 },
+    #[error("the texture has an invalid amount of mipmaps ({0})")]
+    InvalidMipmapCount(u32),
+    #[error("the texture has an invalid amount of layers ({0})")]
+    InvalidLayerCount(u32),
+    #[error("the image data doesn't fit before the footer ({data_size:#x} > {available:#x})")]
+    DataTooLarge { data_size: usize, available: usize },
+    #[error("the mipmaps don't fit in the image data ({mipmaps_size:#x} > {data_size:#x})")]
+    MipmapsTooLarge { mipmaps_size: usize, data_size: usize },
+}
+
+/// Formats whose pixels are 4 bytes with one byte per channel, which are the only ones that can be edited without decoding them
+pub const FORMAT_R8G8B8A8_UNORM: u8 = 0x0E;
+pub const FORMAT_R8G8B8A8_SRGB: u8 = 0x0F;
+pub const FORMAT_B8G8R8A8_UNORM: u8 = 0x50;
+pub const FORMAT_B8G8R8A8_SRGB: u8 = 0x51;
+
+#[derive(Debug)]
+pub struct Footer {
+    pub width: u32,
+    pub height: u32,
+    pub format: u8,
+    pub mipmap_count: u32,
+    /// Size of the whole footer, mipmap sizes included
+    pub size: usize,
+}
+
+fn read_u32(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// Reads and checks the footer at the end of a texture
+pub fn parse_footer(texture: &[u8]) -> Result<Footer, FooterError> {
+    if texture.len() < INFO_SIZE + MIPMAP_SIZES_SIZE {
+        return Err(FooterError::TooSmall(texture.len()));
+    }
+
+    let info = &texture[texture.len() - INFO_SIZE..];
+
+    if &info[0x0..0x4] != b" XNT" || &info[0x68..0x6C] != b" XET" {
+        return Err(FooterError::MissingMagic);
+    }
+
+    let (width, height, depth) = (read_u32(info, 0x44), read_u32(info, 0x48), read_u32(info, 0x4C));
+
+    if width == 0 || height == 0 || depth == 0 {
+        return Err(FooterError::InvalidDimensions { width, height, depth });
+    }
+
+    let mipmap_count = read_u32(info, 0x58);
+
+    if mipmap_count == 0 || mipmap_count > MAX_MIPMAPS {
+        return Err(FooterError::InvalidMipmapCount(mipmap_count));
+    }
+
+    let layer_count = read_u32(info, 0x60);
+
+    if layer_count == 0 || layer_count > MAX_LAYERS {
+        return Err(FooterError::InvalidLayerCount(layer_count));
+    }
+
+    let size = INFO_SIZE + layer_count as usize * MIPMAP_SIZES_SIZE;
+    let data_size = read_u32(info, 0x64) as usize;
+    let available = texture.len().saturating_sub(size);
+
+    if size > texture.len() || data_size > available {
+        return Err(FooterError::DataTooLarge { data_size, available });
+    }
+
+    // Alignment can pad the image data past the mipmaps, but a mipmap can't go past it
+    let mipmap_sizes = &texture[texture.len() - size..texture.len() - INFO_SIZE];
+    let mipmaps_size: usize = mipmap_sizes
+        .chunks_exact(MIPMAP_SIZES_SIZE)
+        .flat_map(|layer| (0..mipmap_count as usize).map(move |mipmap| read_u32(layer, mipmap * 4) as usize))
+        .sum();
+
+    if mipmaps_size > data_size {
+        return Err(FooterError::MipmapsTooLarge { mipmaps_size, data_size });
+    }
+
+    Ok(Footer {
+        width,
+        height,
+        format: info[0x50],
+        mipmap_count,
+        size,
+    })
+}
+
+/// Moves the footer of a texture of `size` bytes at the start of the buffer to the end of the buffer. Returns the footer that was moved,
+/// the buffer is left untouched if it isn't valid.
+pub fn move_footer_to_end(buffer: &mut [u8], size: usize) -> Result<Footer, FooterError> {
+    let footer = parse_footer(&buffer[..size])?;
+
+    if size < buffer.len() {
+        let start = size - footer.size;
+        buffer.copy_within(start..size, buffer.len() - footer.size);
+    }
+
+    Ok(footer)
+}
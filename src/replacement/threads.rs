@@ -1,11 +1,17 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::Level;
 use owo_colors::OwoColorize;
 use skyline::{hook, hooks::InlineCtx};
 use smash_arc::{ArcLookup, Hash40};
 
-use super::FileInfoFlagsExt;
+use super::{coverage, nutexb, FileInfoFlagsExt};
 use crate::{
+    api::replace::{self, TableEntryInfo},
+    dedup_log,
+    fs::GlobalFilesystem,
     hashes, offsets, reg_w, reg_x,
-    resource::{self, InflateFile, LoadInfo, LoadType},
+    resource::{self, InflateFile, LoadInfo, LoadState, LoadType},
     GLOBAL_FILESYSTEM,
 };
 
@@ -20,8 +26,11 @@ fn inflate_incoming(ctx: &InlineCtx) {
     let file_path = &arc.get_file_paths()[file_info.file_path_index];
     let path_hash = file_path.path.hash40();
 
-    info!(
+    dedup_log!(
         target: "no-mod-path",
+        Level::Info,
+        "inflate_incoming",
+        path_hash.0,
         "[ResInflateThread::inflate_incoming | #{:#08X} | Type: {} | {:>3} / {:>3}] Incoming '{}'",
         usize::from(file_info.file_path_index).green(),
         reg_w!(ctx, 21).green(),
@@ -30,12 +39,18 @@ fn inflate_incoming(ctx: &InlineCtx) {
         hashes::find(path_hash).bright_yellow()
     );
 
+    // Scanned before locking the filesystem, the SD is only read while the other loaders keep going
+    crate::fs::populate_lazy_namespace(path_hash);
+
     let mut fs = unsafe { GLOBAL_FILESYSTEM.write().unwrap() };
 
     let should_add = if let Some(path) = fs.hash(path_hash) {
-        info!("Added file '{}' to the queue.", path.display().yellow());
+        dedup_log!(Level::Info, "queued", path_hash.0, "Added file '{}' to the queue.", path.display().yellow());
         true
     } else {
+        if coverage::is_enabled() {
+            coverage::record(path_hash);
+        }
         false
     };
 
@@ -67,15 +82,15 @@ fn inflate_dir_file(arg: u64, out_decomp_data: &mut InflateFile, comp_data: &Inf
     result
 }
 
-pub fn handle_file_replace(hash: Hash40) {
+/// Writes the replacement of a file into the buffer the game allocated for it. Returns false if the buffer was left untouched.
+pub fn handle_file_replace(hash: Hash40) -> bool {
     let arc = resource::arc();
-    let filesystem_info = resource::filesystem_info();
 
     let file_info = match arc.get_file_info_from_hash(hash) {
         Ok(info) => info,
         Err(_) => {
             error!("Failed to find file info for '{}' ({:#x}) when replacing.", hashes::find(hash), hash.0);
-            return;
+            return false;
         },
     };
 
@@ -84,7 +99,7 @@ pub fn handle_file_replace(hash: Hash40) {
 
     let decompressed_size = arc.get_file_data(file_info, config::region()).decomp_size;
 
-    if filesystem_info.get_loaded_filepaths()[filepath_index].is_loaded == 0 {
+    if !resource::is_loaded(filepath_index) {
         warn!(
             "When replacing file '{}' ({:#x}), the file is not marked as loaded. FilepathIdx: {:#x}, LoadedDataIdx: {:#x}",
             hashes::find(hash),
@@ -94,31 +109,75 @@ pub fn handle_file_replace(hash: Hash40) {
         );
     }
 
-    if filesystem_info.get_loaded_datas()[file_info_indice_index].data.is_null() {
+    let mut table_entry = match resource::get_t2_mut(file_info_indice_index, decompressed_size as usize) {
+        Some(entry) => entry,
+        None => {
+            error!(
+                "When replacing file '{}' ({:#x}), the loaded data index is out of bounds. LoadedDataIdx: {:#x}",
+                hashes::find(hash),
+                hash.0,
+                file_info_indice_index
+            );
+            return false;
+        },
+    };
+
+    if !can_replace_table_entry(table_entry.state(), table_entry.data()) {
         warn!(
-            "When replacing file '{}' ({:#x}), the loaded data buffer is empty. FilepathIdx: {:#x}, LoadedDataIdx: {:#x}",
+            "When replacing file '{}' ({:#x}), the loaded data buffer is not usable (state: {:?}, empty: {}). FilepathIdx: {:#x}, LoadedDataIdx: {:#x}",
             hashes::find(hash),
             hash.0,
+            table_entry.state(),
+            table_entry.data().is_null(),
             filepath_index,
             file_info_indice_index
         );
-        return;
+        return false;
     }
 
+    #[cfg(feature = "online")]
+    let fetched = crate::fs::dev_server::fetch_replacement(hash, decompressed_size as usize);
+    #[cfg(not(feature = "online"))]
+    let fetched = None;
+
     let mut fs = unsafe { crate::GLOBAL_FILESYSTEM.write().unwrap() };
 
-    let buffer = unsafe {
-        std::slice::from_raw_parts_mut(
-            filesystem_info.get_loaded_datas()[file_info_indice_index].data as *mut u8,
-            decompressed_size as usize,
-        )
-    };
+    // Checked above, the buffer is there
+    let buffer = table_entry.buffer().unwrap();
+
+    if let Some(size) = load_replacement(&fs, hash, buffer, fetched) {
+        if size == 0 {
+            // Empty files can't blank out content. The game parses every file it loads by its format, so neither a zeroed buffer nor an
+            // entry patched down to no size reads as "present but empty", and the latter hands the game a buffer it can't allocate. Content
+            // is blanked out with a valid file that holds nothing (a silent nus3audio, a transparent texture) instead, the vanilla data is
+            // left in place here.
+            dedup_log!(
+                Level::Warn,
+                "empty",
+                hash.0,
+                "File '{}' ({:#x}) is empty and can't replace the original file, the game keeps its own. Use a valid file with no content to blank it out.",
+                hashes::find(hash),
+                hash.0
+            );
+            return false;
+        }
 
-    if let Some(size) = fs.load_into(hash, buffer) {
         if arc.get_file_paths()[filepath_index].ext.hash40() == Hash40::from("nutexb") {
-            if size < decompressed_size as usize {
-                let (contents, footer) = buffer.split_at_mut((decompressed_size - 0xb0) as usize);
-                footer.copy_from_slice(&contents[(size - 0xb0)..size]);
+            match nutexb::move_footer_to_end(buffer, size) {
+                Ok(footer) => debug!(
+                    "Moved the footer of texture '{}' ({:#x}), {}x{} with {} mipmap(s).",
+                    hashes::find(hash),
+                    hash.0,
+                    footer.width,
+                    footer.height,
+                    footer.mipmap_count
+                ),
+                Err(e) => warn!(
+                    "The texture '{}' ({:#x}) has an invalid footer and may not display properly. Reason: {}",
+                    hashes::find(hash),
+                    hash.0,
+                    e
+                ),
             }
         } else if file_info.flags.unshared_nus3bank() {
             static GRP_BYTES: &[u8] = &[0x47, 0x52, 0x50, 0x20];
@@ -129,7 +188,10 @@ pub fn handle_file_replace(hash: Hash40) {
                 }
             }
         }
-        info!(
+        dedup_log!(
+            Level::Info,
+            "replaced",
+            hash.0,
             "Replaced file '{}' ({:#x}) with buffer size {:#x} and file size {:#x}. Game buffer size: {:#x}",
             hashes::find(hash),
             hash.0,
@@ -137,6 +199,9 @@ pub fn handle_file_replace(hash: Hash40) {
             size,
             resource::res_service().buffer_size
         );
+
+        replace::run_post_replace(&TableEntryInfo::new(hash, filepath_index as u32, &table_entry, size));
+        true
     } else {
         warn!(
             "Failed to load file '{}' ({:#x}) into buffer with size {:#X}",
@@ -144,7 +209,84 @@ pub fn handle_file_replace(hash: Hash40) {
             hash.0,
             decompressed_size
         );
+        false
+    }
+}
+
+/// Marks the copy of a file the game kept loaded as unloaded, so that the game loads it again through the hooks the next time it uses
+/// it and picks up the changes made to it on the SD. Returns false if the game doesn't hold the file, or if something is using it.
+pub fn invalidate_loaded_file(hash: Hash40) -> bool {
+    let arc = resource::arc();
+
+    let file_info = match arc.get_file_info_from_hash(hash) {
+        Ok(info) => info,
+        Err(_) => return false,
+    };
+
+    // Held for the whole check, the game could otherwise start using the entry or free it in the meantime
+    let _tables = match resource::lock_tables() {
+        Some(tables) => tables,
+        None => return false,
+    };
+
+    if !resource::is_loaded(usize::from(file_info.file_path_index)) {
+        return false;
+    }
+
+    let decompressed_size = arc.get_file_data(file_info, config::region()).decomp_size;
+
+    match resource::get_t2_mut(usize::from(file_info.file_info_indice_index), decompressed_size as usize) {
+        Some(mut entry) if entry.state() == LoadState::Loaded && entry.ref_count() == 0 => entry.set_state(LoadState::Unloaded).is_ok(),
+        _ => false,
+    }
+}
+
+/// Decides whether the game's buffer for a file can receive our data.
+///
+/// | State                | Data     | Replace | Why                                                                  |
+/// |----------------------|----------|---------|----------------------------------------------------------------------|
+/// | `Loaded`             | non-null | yes     | Regular case, the game is done inflating into its buffer             |
+/// | `Unloaded`           | non-null | yes     | The buffer is allocated but the state isn't flipped until we return  |
+/// | `Unknown`, `Unused`  | non-null | yes     | The game only settles the state after the hooks return               |
+/// | Any                  | null     | no      | The game hasn't handed us a buffer, writing would dereference null   |
+///
+/// The null case can't be fixed up on our end since the buffer is allocated by the game, so it is skipped and logged instead.
+fn can_replace_table_entry(state: LoadState, data: *const u8) -> bool {
+    if data.is_null() {
+        return false;
+    }
+
+    if !matches!(state, LoadState::Loaded | LoadState::Unloaded) {
+        debug!("Replacing into a loaded data buffer in an unusual state ({:?}).", state);
+    }
+
+    true
+}
+
+/// Loads the replacement for a file into the game's buffer, running it through the registered pre-replace transforms first. Data
+/// `fetched` from the development server takes the place of what the filesystem provides.
+fn load_replacement(fs: &GlobalFilesystem, hash: Hash40, buffer: &mut [u8], fetched: Option<Vec<u8>>) -> Option<usize> {
+    let mut data = match fetched {
+        Some(data) => data,
+        None if !replace::has_pre_replace(hash) => return fs.load_into(hash, buffer),
+        None => fs.load(hash)?,
+    };
+
+    replace::run_pre_replace(hash, &mut data, buffer.len());
+
+    if data.len() > buffer.len() {
+        error!(
+            "The pre-replace callbacks for '{}' ({:#x}) grew the file past the size of the game's buffer ({:#x} > {:#x}).",
+            hashes::find(hash),
+            hash.0,
+            data.len(),
+            buffer.len()
+        );
+        return None;
     }
+
+    buffer[..data.len()].copy_from_slice(&data);
+    Some(data.len())
 }
 
 // handles submitting files to be loaded manually
@@ -158,7 +300,27 @@ fn res_loop_refresh(_: &InlineCtx) {
     res_loop_common();
 }
 
+static BYPASS_COMBO_HELD: AtomicBool = AtomicBool::new(false);
+
+/// Toggles the replacement bypass once per press of the combo, rather than every time the loop runs while it is held
+fn check_bypass_combo() {
+    if crate::utils::env::is_emulator() {
+        return;
+    }
+
+    // L + R + Minus, which the game does not use anywhere
+    let held = ninput::any::is_down(ninput::Buttons::L | ninput::Buttons::R | ninput::Buttons::MINUS);
+
+    if held && !BYPASS_COMBO_HELD.swap(true, Ordering::Relaxed) {
+        config::set_replacements_bypassed(!config::replacements_bypassed());
+    } else if !held {
+        BYPASS_COMBO_HELD.store(false, Ordering::Relaxed);
+    }
+}
+
 fn res_loop_common() {
+    check_bypass_combo();
+
     let arc = resource::arc();
     let service = resource::res_service_mut();
     let file_paths = arc.get_file_paths();
@@ -200,3 +362,24 @@ fn res_loop_common() {
 pub fn install() {
     skyline::install_hooks!(inflate_incoming, inflate_dir_file, res_loop_start, res_loop_refresh);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn replaces_into_any_allocated_buffer() {
+        let buffer = [0u8; 4];
+
+        for state in [LoadState::Unused, LoadState::Unloaded, LoadState::Unknown, LoadState::Loaded] {
+            assert!(can_replace_table_entry(state, buffer.as_ptr()), "{:?}", state);
+        }
+    }
+
+    #[test]
+    fn skips_null_buffers() {
+        for state in [LoadState::Unused, LoadState::Unloaded, LoadState::Unknown, LoadState::Loaded] {
+            assert!(!can_replace_table_entry(state, std::ptr::null()), "{:?}", state);
+        }
+    }
+}
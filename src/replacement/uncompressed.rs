@@ -18,9 +18,9 @@ fn memcpy_uncompressed_fix(ctx: &InlineCtx) {
     // For now, we will leave this as an unconditionally true if statement
     let buffer_size = reg_x!(ctx, 2) as usize;
     let hash = unsafe { crate::GLOBAL_FILESYSTEM.write().unwrap().sub_remaining_bytes(buffer_size) };
-    if let Some(hash) = hash {
-        super::threads::handle_file_replace(hash);
-    } else {
+
+    // The copy of the vanilla data was patched out, so it has to be done here whenever the file isn't replaced after all
+    if !hash.map_or(false, super::threads::handle_file_replace) {
         let dest = reg_x!(ctx, 0) as *mut c_void;
         let src = reg_x!(ctx, 1) as *const c_void;
         unsafe {
@@ -1,11 +1,29 @@
+//! Access to the resource tables of the game.
+//!
+//! The structs in here mirror the memory layout of the game, which can change between versions. Code that only needs to read or update
+//! the tables should go through the accessors below (`table1`, `table2`, `get_t2_mut`, `get_hash_from_t1_index`...), which check that
+//! the layout is the one they were written for. The raw fields are only meant for the code that rebuilds the tables when adding files.
+
 mod containers;
 mod types;
 
+use std::sync::LazyLock;
+
 pub use containers::*;
-use smash_arc::{LoadedArc, LoadedSearchSection};
+use smash_arc::{ArcLookup, Hash40, LoadedArc, LoadedSearchSection};
 pub use types::*;
 
-use crate::offsets;
+use crate::{offsets, utils};
+
+/// Version of the game the layout of the tables was mapped from
+pub const LAYOUT_GAME_VERSION: semver::Version = semver::Version::new(13, 0, 4);
+
+static LAYOUT_MATCHES: LazyLock<bool> = LazyLock::new(|| utils::env::get_game_version() == LAYOUT_GAME_VERSION);
+
+/// Whether the tables can be read, which requires the game to have set them up with the layout we know of
+fn tables_available() -> bool {
+    *LAYOUT_MATCHES && initialized()
+}
 
 fn offset_to_addr<T>(offset: usize) -> *mut T {
     unsafe { (skyline::hooks::getRegionAddress(skyline::hooks::Region::Text) as usize + offset) as *mut T }
@@ -50,3 +68,65 @@ pub fn res_service_mut() -> &'static mut ResServiceNX {
 pub fn initialized() -> bool {
     !offset_to_addr::<&'static FilesystemInfo>(offsets::filesystem_info()).is_null()
 }
+
+/// Gets the loaded filepath table (table1), indexed by file path index. Empty if the tables can't be read.
+pub fn table1() -> &'static [LoadedFilepath] {
+    if tables_available() {
+        filesystem_info().get_loaded_filepaths()
+    } else {
+        &[]
+    }
+}
+
+/// Gets the loaded data table (table2), indexed by file info indice index. Empty if the tables can't be read.
+pub fn table2() -> &'static [LoadedData] {
+    if tables_available() {
+        filesystem_info().get_loaded_datas()
+    } else {
+        &[]
+    }
+}
+
+/// Holds the mutex the game locks while it updates its resource tables, released when dropped
+pub struct TablesLock(*mut skyline::nn::os::MutexType);
+
+impl Drop for TablesLock {
+    fn drop(&mut self) {
+        unsafe { skyline::nn::os::UnlockMutex(self.0) }
+    }
+}
+
+/// Locks the resource tables the same way the game does, so that entries can't be taken or freed by the game while they are being
+/// looked at from another thread. None if the tables can't be read.
+pub fn lock_tables() -> Option<TablesLock> {
+    if !tables_available() {
+        return None;
+    }
+
+    let mutex = filesystem_info().mutex;
+    unsafe { skyline::nn::os::LockMutex(mutex) };
+    Some(TablesLock(mutex))
+}
+
+/// Whether the game holds the data of a file path in table1
+pub fn is_loaded(t1_index: usize) -> bool {
+    table1().get(t1_index).map_or(false, |filepath| filepath.is_loaded == 1)
+}
+
+/// Gets a checked view over an entry of table2, through which it can be updated. `size` is the size of the buffer the entry points to.
+pub fn get_t2_mut(t2_index: usize, size: usize) -> Option<TableEntry<'static>> {
+    if !tables_available() {
+        return None;
+    }
+
+    filesystem_info_mut().get_table_entry(t2_index, size)
+}
+
+/// Gets the hash of the file path an entry of table1 belongs to
+pub fn get_hash_from_t1_index(t1_index: usize) -> Option<Hash40> {
+    if !tables_available() {
+        return None;
+    }
+
+    arc().get_file_paths().get(t1_index).map(|file_path| file_path.path.hash40())
+}
@@ -5,6 +5,7 @@ use std::{
 
 use skyline::nn;
 use smash_arc::{LoadedArc, LoadedSearchSection};
+use thiserror::Error;
 
 use super::containers::{CppVector, ResList};
 
@@ -73,6 +74,62 @@ impl Clone for LoadedData {
     }
 }
 
+#[derive(Debug, Error)]
+pub enum TableEntryError {
+    #[error("the entry can't be marked as loaded without any data")]
+    LoadedWithoutData,
+}
+
+/// Checked view over an entry of the loaded data table (table2). Every access to the raw [`LoadedData`] fields, and the unsafe that
+/// comes with them, goes through here so that a layout change between game versions only has to be handled in one place.
+pub struct TableEntry<'a> {
+    index: usize,
+    entry: &'a mut LoadedData,
+    /// Size of the buffer pointed to by the data, which the game doesn't keep track of in the entry itself
+    size: usize,
+}
+
+impl<'a> TableEntry<'a> {
+    pub fn index(&self) -> usize {
+        self.index
+    }
+
+    pub fn data(&self) -> *const u8 {
+        self.entry.data
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn state(&self) -> LoadState {
+        self.entry.state
+    }
+
+    /// Amount of resources of the game currently holding on to the data
+    pub fn ref_count(&self) -> u32 {
+        self.entry.ref_count.load(Ordering::SeqCst)
+    }
+
+    /// Gets the buffer of the entry, if the game allocated one
+    pub fn buffer(&mut self) -> Option<&mut [u8]> {
+        if self.entry.data.is_null() {
+            None
+        } else {
+            Some(unsafe { std::slice::from_raw_parts_mut(self.entry.data as *mut u8, self.size) })
+        }
+    }
+
+    pub fn set_state(&mut self, state: LoadState) -> Result<(), TableEntryError> {
+        if state == LoadState::Loaded && self.entry.data.is_null() {
+            return Err(TableEntryError::LoadedWithoutData);
+        }
+
+        self.entry.state = state;
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 #[repr(C)]
 pub struct LoadedDirectory {
@@ -122,37 +179,48 @@ pub struct PathInformation {
     pub search: &'static mut LoadedSearchSection,
 }
 
+/// Layout of the game's resource tables. The fields are only meant for the code that rebuilds the tables when adding files, the
+/// accessors of [`crate::resource`] should be used anywhere else.
 #[repr(C)]
 pub struct FilesystemInfo {
-    pub mutex: *mut nn::os::MutexType,
-    pub loaded_filepaths: *mut LoadedFilepath,
-    pub loaded_datas: *mut LoadedData,
-    pub loaded_filepath_len: u32,
-    pub loaded_data_len: u32,
-    pub loaded_filepath_count: u32,
-    pub loaded_data_count: u32,
-    pub loaded_filepath_list: CppVector<u32>,
-    pub loaded_directories: *const LoadedDirectory,
-    pub loaded_directory_len: u32,
-    pub unk: u32,
-    pub unk2: CppVector<u32>,
-    pub unk3: u8,
-    pub unk4: [u8; 7],
-    pub addr: *const (),
-    pub path_info: &'static mut PathInformation,
-    pub version: u32,
+    pub(crate) mutex: *mut nn::os::MutexType,
+    pub(crate) loaded_filepaths: *mut LoadedFilepath,
+    pub(crate) loaded_datas: *mut LoadedData,
+    pub(crate) loaded_filepath_len: u32,
+    pub(crate) loaded_data_len: u32,
+    pub(crate) loaded_filepath_count: u32,
+    pub(crate) loaded_data_count: u32,
+    pub(crate) loaded_filepath_list: CppVector<u32>,
+    pub(crate) loaded_directories: *const LoadedDirectory,
+    pub(crate) loaded_directory_len: u32,
+    pub(crate) unk: u32,
+    pub(crate) unk2: CppVector<u32>,
+    pub(crate) unk3: u8,
+    pub(crate) unk4: [u8; 7],
+    pub(crate) addr: *const (),
+    pub(crate) path_info: &'static mut PathInformation,
+    pub(crate) version: u32,
 }
 
 impl FilesystemInfo {
-    pub fn get_loaded_filepaths(&self) -> &[LoadedFilepath] {
+    pub(crate) fn get_loaded_filepaths(&self) -> &[LoadedFilepath] {
         unsafe { std::slice::from_raw_parts(self.loaded_filepaths, self.loaded_filepath_len as usize) }
     }
 
-    pub fn get_loaded_datas(&self) -> &[LoadedData] {
+    pub(crate) fn get_loaded_datas(&self) -> &[LoadedData] {
         unsafe { std::slice::from_raw_parts(self.loaded_datas, self.loaded_data_len as usize) }
     }
 
-    pub fn get_loaded_directories(&self) -> &[LoadedDirectory] {
+    pub(crate) fn get_loaded_datas_mut(&mut self) -> &mut [LoadedData] {
+        unsafe { std::slice::from_raw_parts_mut(self.loaded_datas, self.loaded_data_len as usize) }
+    }
+
+    /// Gets a checked view over an entry of the loaded data table. `size` is the size of the buffer the entry points to.
+    pub(crate) fn get_table_entry(&mut self, index: usize, size: usize) -> Option<TableEntry<'_>> {
+        self.get_loaded_datas_mut().get_mut(index).map(|entry| TableEntry { index, entry, size })
+    }
+
+    pub(crate) fn get_loaded_directories(&self) -> &[LoadedDirectory] {
         unsafe { std::slice::from_raw_parts(self.loaded_directories, self.loaded_directory_len as usize) }
     }
 }
@@ -114,6 +114,7 @@ where
                 panic!("ARCropolis failed to extract update ZIP. Reason: {:?}", e);
             }
 
+            crate::stop_background_threads();
             unsafe { skyline::nn::oe::RequestToRelaunchApplication() };
         }
     }
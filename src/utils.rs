@@ -59,11 +59,60 @@ pub mod paths {
         std::fs::create_dir_all(config())?;
         std::fs::create_dir_all(logs())?;
         std::fs::create_dir_all(cache())?;
+        #[cfg(feature = "modpack")]
+        std::fs::create_dir_all(modpacks())?;
         Ok(())
     }
 
     pub fn mods() -> Utf8PathBuf {
-        Utf8PathBuf::from("sd:/ultimate/mods")
+        match ::config::mods_path_override() {
+            Some(path) => Utf8PathBuf::from(path),
+            None => Utf8PathBuf::from("sd:/ultimate/mods"),
+        }
+    }
+
+    /// The additional mods directories from the configuration that exist on the SD
+    pub fn extra_mods() -> Vec<Utf8PathBuf> {
+        ::config::extra_mods_paths()
+            .into_iter()
+            .map(Utf8PathBuf::from)
+            .filter(|path| {
+                let exists = path.is_dir();
+                if !exists {
+                    warn!("The additional mods directory '{}' does not exist, skipping it.", path);
+                }
+                exists
+            })
+            .collect()
+    }
+
+    /// File holding the path of a mods directory to use for the next boot only, meant for testing with another set of mods
+    pub fn mods_override_sentinel() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/override_mods_path.txt")
+    }
+
+    pub fn modpacks() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/modpacks")
+    }
+
+    pub fn modpack_cache() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/modpacks")
+    }
+
+    /// File touched by FTP clients once they are done uploading mods, to have them picked up without rebooting. Edits to the files
+    /// the game already loaded are picked up along with them.
+    pub fn rescan_sentinel() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/rescan")
+    }
+
+    /// Mods known to be incompatible, either maintained by hand or downloaded on boot
+    pub fn blocklist() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/blocklist.toml")
+    }
+
+    /// Files of lazy namespaces that were too large to be used when their namespace was scanned, to be mapped on the next boot
+    pub fn lazy_oversized_files() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/lazy_oversized_files.txt")
     }
 
     pub fn config() -> Utf8PathBuf {
@@ -74,11 +123,55 @@ pub mod paths {
         Utf8PathBuf::from("sd:/ultimate/arcropolis/logs")
     }
 
+    /// Mod files from the last boot whose path doesn't exist in the game
+    pub fn unmatched_report() -> Utf8PathBuf {
+        logs().join("unmatched.txt")
+    }
+
+    /// Files loaded by the game that no mod replaces, listed when the `log_vanilla_files` flag is set
+    pub fn vanilla_files() -> Utf8PathBuf {
+        Utf8PathBuf::from("sd:/ultimate/arcropolis/vanilla_files.txt")
+    }
+
     pub fn cache() -> Utf8PathBuf {
         Utf8PathBuf::from("sd:/ultimate/arcropolis/cache").join(get_game_version().to_string())
     }
 }
 
+pub mod storage {
+    /// Room left on top of the data being written, so the SD isn't filled to the last byte
+    const SAFETY_MARGIN: u64 = 0x100000;
+
+    extern "C" {
+        #[link_name = "_ZN2nn2fs16GetFreeSpaceSizeEPlPKc"]
+        fn get_free_space_size(out_size: *mut i64, path: *const u8) -> u32;
+    }
+
+    /// Queries how many bytes are left on the SD card
+    pub fn sd_free_space() -> Option<u64> {
+        let mut size = 0;
+
+        match unsafe { get_free_space_size(&mut size, "sd:/\0".as_ptr()) } {
+            0 => Some(size as u64),
+            result => {
+                warn!("Failed to query the free space of the SD card. Result: {:#x}", result);
+                None
+            },
+        }
+    }
+
+    /// Checks if the SD card can be read at all, without logging anything since it is polled
+    pub fn is_sd_mounted() -> bool {
+        let mut size = 0;
+        unsafe { get_free_space_size(&mut size, "sd:/\0".as_ptr()) == 0 }
+    }
+
+    /// Checks if `size` bytes can be written to the SD card. If the free space can't be queried, the write is allowed, as it would be without this check.
+    pub fn has_room_for(size: u64) -> bool {
+        sd_free_space().map_or(true, |free| free >= size + SAFETY_MARGIN)
+    }
+}
+
 pub mod save {
     use super::*;
     use smash_arc::Region;